@@ -25,10 +25,15 @@ async fn main() {
         .and(starterm::fs::dir("./examples/"))
         .with(starterm::compression::deflate());
 
+    let readme = starterm::path("readme")
+        .and(starterm::fs::file("./README.md"))
+        .with(starterm::compression::auto());
+
     // GET /todos => gzip -> toods.rs
     // GET /ws_chat => gzip -> ws_chat.rs
     // GET /ex/... => deflate -> ./examples/...
-    let routes = file_and_dir.or(examples);
+    // GET /readme => best of `Accept-Encoding` -> README.md
+    let routes = file_and_dir.or(examples).or(readme);
 
     starterm::serve(routes).run(([127, 0, 0, 1], 3030)).await;
 }
@@ -90,14 +90,8 @@ async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
         // This error happens if the body could not be deserialized correctly
         // We can use the cause to analyze the error and customize the error message
         message = match e.source() {
-            Some(cause) => {
-                if cause.to_string().contains("denom") {
-                    "FIELD_ERROR: denom"
-                } else {
-                    "BAD_REQUEST"
-                }
-            }
-            None => "BAD_REQUEST",
+            Some(cause) if cause.to_string().contains("denom") => "FIELD_ERROR: denom",
+            _ => "BAD_REQUEST",
         };
         code = StatusCode::BAD_REQUEST;
     } else if err.find::<starterm::reject::MethodNotAllowed>().is_some() {
@@ -1,9 +1,17 @@
 //! Socket Address filters.
 
 use std::convert::Infallible;
-use std::net::SocketAddr;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::Arc;
 
-use crate::filter::{filter_fn_one, Filter};
+use futures_util::future;
+use http::header::{HeaderMap, HeaderName};
+use http::uri::{Authority, Scheme};
+
+use crate::filter::{filter_fn, filter_fn_one, Filter};
 
 /// Creates a `Filter` to get the remote address of the connection.
 ///
@@ -24,3 +32,473 @@ use crate::filter::{filter_fn_one, Filter};
 pub fn remote() -> impl Filter<Extract = (Option<SocketAddr>,), Error = Infallible> + Copy {
     filter_fn_one(|route| futures_util::future::ok(route.remote_addr()))
 }
+
+/// Creates a `Filter` to get the IP address of the client, as reported by
+/// trusted proxies.
+///
+/// If the remote peer is one of the `trusted` proxies, the addresses it
+/// forwarded in the [header](TrustedProxies::header) they set are walked
+/// from right to left. The first address that isn't a trusted proxy is the
+/// client. Headers sent by anyone else are ignored, since a client could put
+/// any address in them.
+///
+/// This yields the remote address of the connection if it isn't a trusted
+/// proxy, and `None` if the transport doesn't use socket addresses.
+///
+/// # Example
+///
+/// ```
+/// use std::net::IpAddr;
+/// use starterm::Filter;
+/// use starterm::addr::TrustedProxies;
+///
+/// let trusted = TrustedProxies::new(vec!["10.0.0.0/8".parse().unwrap()]);
+/// let route = starterm::addr::client_ip(trusted)
+///     .map(|ip: Option<IpAddr>| {
+///         format!("client = {:?}", ip)
+///     });
+/// ```
+pub fn client_ip(
+    trusted: TrustedProxies,
+) -> impl Filter<Extract = (Option<IpAddr>,), Error = Infallible> + Clone {
+    filter_fn(move |route| {
+        let ip = trusted.client_ip(route.remote_addr(), route.headers());
+        future::ok::<_, Infallible>((ip,))
+    })
+}
+
+/// Creates a `Filter` to get the scheme the client used, as reported by
+/// trusted proxies.
+///
+/// If the remote peer is one of the `trusted` proxies, this is the `proto`
+/// of the last element of the `Forwarded` header, or the last value of
+/// `X-Forwarded-Proto`, depending on the [header](TrustedProxies::header)
+/// they set. It yields `None` otherwise, or if the value isn't a valid
+/// scheme.
+///
+/// # Example
+///
+/// ```
+/// use starterm::Filter;
+/// use starterm::addr::TrustedProxies;
+/// use starterm::http::uri::Scheme;
+///
+/// let trusted = TrustedProxies::new(vec!["10.0.0.0/8".parse().unwrap()]);
+/// let route = starterm::addr::forwarded_proto(trusted)
+///     .map(|proto: Option<Scheme>| {
+///         let https = proto.map_or(false, |proto| proto == Scheme::HTTPS);
+///         format!("https: {}", https)
+///     });
+/// ```
+pub fn forwarded_proto(
+    trusted: TrustedProxies,
+) -> impl Filter<Extract = (Option<Scheme>,), Error = Infallible> + Clone {
+    filter_fn(move |route| {
+        let proto = trusted
+            .forwarded_param(
+                route.remote_addr(),
+                route.headers(),
+                "proto",
+                X_FORWARDED_PROTO,
+            )
+            .and_then(|proto| proto.parse().ok());
+        future::ok::<_, Infallible>((proto,))
+    })
+}
+
+/// Creates a `Filter` to get the host the client asked for, as reported by
+/// trusted proxies.
+///
+/// If the remote peer is one of the `trusted` proxies, this is the `host`
+/// of the last element of the `Forwarded` header, or the last value of
+/// `X-Forwarded-Host`, depending on the [header](TrustedProxies::header)
+/// they set. It yields `None` otherwise, or if the value isn't a valid
+/// authority. The `Host` header is then available with
+/// [`host::optional`](crate::host::optional).
+///
+/// # Example
+///
+/// ```
+/// use starterm::Filter;
+/// use starterm::addr::TrustedProxies;
+/// use starterm::http::uri::Authority;
+///
+/// let trusted = TrustedProxies::new(vec!["10.0.0.0/8".parse().unwrap()]);
+/// let route = starterm::addr::forwarded_host(trusted)
+///     .and(starterm::host::optional())
+///     .map(|forwarded: Option<Authority>, host: Option<Authority>| {
+///         format!("host = {:?}", forwarded.or(host))
+///     });
+/// ```
+pub fn forwarded_host(
+    trusted: TrustedProxies,
+) -> impl Filter<Extract = (Option<Authority>,), Error = Infallible> + Clone {
+    filter_fn(move |route| {
+        let host = trusted
+            .forwarded_param(
+                route.remote_addr(),
+                route.headers(),
+                "host",
+                X_FORWARDED_HOST,
+            )
+            .and_then(|host| host.parse().ok());
+        future::ok::<_, Infallible>((host,))
+    })
+}
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+const X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+const X_REAL_IP: HeaderName = HeaderName::from_static("x-real-ip");
+
+/// The proxies whose forwarding headers are trusted, by [`client_ip`],
+/// [`forwarded_proto`] and [`forwarded_host`].
+///
+/// The default trusts no proxy, so that the headers are always ignored.
+///
+/// # Example
+///
+/// ```
+/// use starterm::addr::{ForwardedHeader, TrustedProxies};
+///
+/// let trusted = TrustedProxies::new(vec![
+///     "10.0.0.0/8".parse().unwrap(),
+///     "::1".parse().unwrap(),
+/// ])
+/// .header(ForwardedHeader::XForwardedFor);
+/// assert!(trusted.contains([10, 0, 0, 1].into()));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies {
+    cidrs: Arc<[Cidr]>,
+    header: ForwardedHeader,
+}
+
+/// The header that [`TrustedProxies`] set, see [`TrustedProxies::header`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ForwardedHeader {
+    /// The `Forwarded` header, with its `for`, `proto` and `host`
+    /// parameters.
+    #[default]
+    Forwarded,
+    /// The `X-Forwarded-For`, `X-Forwarded-Proto` and `X-Forwarded-Host`
+    /// headers.
+    XForwardedFor,
+    /// The `X-Real-IP` header. It only has the client address, so
+    /// [`forwarded_proto`] and [`forwarded_host`] yield `None`.
+    XRealIp,
+}
+
+impl TrustedProxies {
+    /// Trusts the proxies with an address in `cidrs`.
+    pub fn new(cidrs: impl IntoIterator<Item = Cidr>) -> Self {
+        TrustedProxies {
+            cidrs: cidrs.into_iter().collect(),
+            header: ForwardedHeader::default(),
+        }
+    }
+
+    /// Sets the header the trusted proxies forward the client information
+    /// in.
+    ///
+    /// Only this header is read, the others are ignored, since a client could
+    /// send them through the proxies. The default is
+    /// [`ForwardedHeader::Forwarded`].
+    pub fn header(mut self, header: ForwardedHeader) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Returns whether `addr` is a trusted proxy.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        self.cidrs.iter().any(|cidr| cidr.contains(addr))
+    }
+
+    pub(crate) fn client_ip(
+        &self,
+        remote_addr: Option<SocketAddr>,
+        headers: &HeaderMap,
+    ) -> Option<IpAddr> {
+        let mut client = remote_addr?.ip();
+        if !self.contains(client) {
+            return Some(client);
+        }
+
+        let hops = match self.header {
+            ForwardedHeader::Forwarded => forwarded_elements(headers)
+                .map(|element| forwarded_value(element, "for").unwrap_or(""))
+                .collect::<Vec<_>>(),
+            ForwardedHeader::XForwardedFor => header_list(headers, &X_FORWARDED_FOR).collect(),
+            ForwardedHeader::XRealIp => header_list(headers, &X_REAL_IP).collect(),
+        };
+        for hop in hops.into_iter().rev() {
+            // Such as `unknown`, or an obfuscated identifier, past which
+            // nothing can be trusted.
+            let ip = match parse_node(hop) {
+                Some(ip) => ip,
+                None => break,
+            };
+            client = ip;
+            if !self.contains(ip) {
+                break;
+            }
+        }
+        Some(client)
+    }
+
+    fn forwarded_param<'a>(
+        &self,
+        remote_addr: Option<SocketAddr>,
+        headers: &'a HeaderMap,
+        param: &str,
+        x_forwarded: HeaderName,
+    ) -> Option<&'a str> {
+        if !self.contains(remote_addr?.ip()) {
+            return None;
+        }
+        // Set by the proxy closest to the server, which is trusted.
+        match self.header {
+            ForwardedHeader::Forwarded => forwarded_elements(headers)
+                .last()
+                .and_then(|element| forwarded_value(element, param)),
+            ForwardedHeader::XForwardedFor => header_list(headers, &x_forwarded).last(),
+            ForwardedHeader::XRealIp => None,
+        }
+    }
+}
+
+// The comma separated values of every `name` header, in order.
+fn header_list<'a>(headers: &'a HeaderMap, name: &HeaderName) -> impl Iterator<Item = &'a str> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+}
+
+// The elements of every `Forwarded` header, in order. Quoted strings may
+// contain a comma, which doesn't end an element.
+fn forwarded_elements(headers: &HeaderMap) -> impl Iterator<Item = &str> {
+    headers
+        .get_all(http::header::FORWARDED)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| split_unquoted(value, ','))
+        .map(str::trim)
+}
+
+fn split_unquoted(s: &str, sep: char) -> impl Iterator<Item = &str> {
+    let mut quoted = false;
+    let mut escaped = false;
+    s.split(move |c| {
+        if escaped {
+            escaped = false;
+        } else if quoted && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            quoted = !quoted;
+        } else if c == sep && !quoted {
+            return true;
+        }
+        false
+    })
+}
+
+// The value of `param` in a `Forwarded` element, such as
+// `for=192.0.2.60;proto=http`, without its quotes.
+fn forwarded_value<'a>(element: &'a str, param: &str) -> Option<&'a str> {
+    split_unquoted(element, ';').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        if !key.trim().eq_ignore_ascii_case(param) {
+            return None;
+        }
+        let value = value.trim();
+        Some(
+            value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value),
+        )
+    })
+}
+
+// An address forwarded for a client, which may have a port, such as
+// `192.0.2.60:4711` or `[2001:db8::1]:4711`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Ok(ip) = node.parse() {
+        return Some(ip);
+    }
+    if let Some(rest) = node.strip_prefix('[') {
+        let (ip, _) = rest.split_once(']')?;
+        return ip.parse::<std::net::Ipv6Addr>().ok().map(IpAddr::V6);
+    }
+    node.parse::<SocketAddr>().ok().map(|addr| addr.ip())
+}
+
+/// A range of IP addresses, such as `10.0.0.0/8` or `fd00::/8`.
+///
+/// It's parsed from a string, where an address without a prefix length is a
+/// range of just that address.
+///
+/// # Example
+///
+/// ```
+/// use starterm::addr::Cidr;
+///
+/// let cidr: Cidr = "10.0.0.0/8".parse().unwrap();
+/// assert!(cidr.contains([10, 1, 2, 3].into()));
+/// assert!(!cidr.contains([192, 168, 0, 1].into()));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    /// Creates a `Cidr` of the addresses starting with the first
+    /// `prefix_len` bits of `addr`.
+    ///
+    /// Returns `None` if `prefix_len` is longer than the address.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Option<Cidr> {
+        let max = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix_len > max {
+            return None;
+        }
+        Some(Cidr { addr, prefix_len })
+    }
+
+    /// Returns whether `addr` is in this range.
+    ///
+    /// An IPv4-mapped IPv6 address, such as `::ffff:10.0.0.1`, is matched as
+    /// its IPv4 address.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        let addr = match addr {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(addr, IpAddr::V4),
+            addr => addr,
+        };
+        match (self.addr, addr) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix_len))
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix_len))
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::str::FromStr for Cidr {
+    type Err = InvalidCidr;
+
+    fn from_str(s: &str) -> Result<Cidr, InvalidCidr> {
+        let invalid = || InvalidCidr { _p: () };
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, len)) => {
+                let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+                // `u8::from_str` would also accept a `+`.
+                if len.is_empty() || !len.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(invalid());
+                }
+                (addr, len.parse().map_err(|_| invalid())?)
+            }
+            None => {
+                let addr: IpAddr = s.parse().map_err(|_| invalid())?;
+                let len = if addr.is_ipv4() { 32 } else { 128 };
+                (addr, len)
+            }
+        };
+        Cidr::new(addr, prefix_len).ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+unit_error! {
+    /// An error parsing a [`Cidr`].
+    pub InvalidCidr: "invalid CIDR"
+}
+
+/// Creates a `Filter` to get the peer of a Unix domain socket connection.
+///
+/// This yields `None` unless the server was bound with
+/// [`Server::bind_unix`](crate::Server::bind_unix).
+///
+/// *This function is only available on Unix platforms.*
+///
+/// # Example
+///
+/// ```
+/// use starterm::Filter;
+/// use starterm::addr::UnixPeer;
+///
+/// // Only root may call it.
+/// let route = starterm::addr::unix_peer()
+///     .and_then(|peer: Option<UnixPeer>| async move {
+///         match peer.and_then(|peer| peer.uid()) {
+///             Some(0) => Ok("hello root"),
+///             _ => Err(starterm::reject::not_found()),
+///         }
+///     });
+/// ```
+#[cfg(unix)]
+pub fn unix_peer() -> impl Filter<Extract = (Option<UnixPeer>,), Error = Infallible> + Copy {
+    crate::conn::optional::<UnixPeer>()
+}
+
+/// The peer of a Unix domain socket connection.
+///
+/// The credentials are those of the peer process when it connected, read
+/// with `SO_PEERCRED` or the equivalent of the platform. They are `None` if
+/// the platform doesn't provide them.
+///
+/// *This type is only available on Unix platforms.*
+#[cfg(unix)]
+#[derive(Clone, Debug)]
+pub struct UnixPeer {
+    path: Arc<Path>,
+    cred: Option<tokio::net::unix::UCred>,
+}
+
+#[cfg(unix)]
+impl UnixPeer {
+    pub(crate) fn new(path: Arc<Path>, cred: Option<tokio::net::unix::UCred>) -> UnixPeer {
+        UnixPeer { path, cred }
+    }
+
+    /// Returns the path of the socket the server is bound to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the user ID of the peer process.
+    pub fn uid(&self) -> Option<u32> {
+        self.cred.map(|cred| cred.uid())
+    }
+
+    /// Returns the group ID of the peer process.
+    pub fn gid(&self) -> Option<u32> {
+        self.cred.map(|cred| cred.gid())
+    }
+
+    /// Returns the process ID of the peer, on platforms that report it.
+    pub fn pid(&self) -> Option<i32> {
+        self.cred.and_then(|cred| cred.pid())
+    }
+}
@@ -62,6 +62,43 @@ pub fn content_length_limit(limit: u64) -> impl Filter<Extract = (), Error = Rej
         .untuple_one()
 }
 
+/// Create a `Filter` that decodes the request body according to its `content-encoding`.
+///
+/// The body is left in place for the following body filters, which then see
+/// the decoded content. The `content-encoding` and `content-length` headers
+/// are removed from the request.
+///
+/// Only the algorithms enabled through the `compression-*` features can be
+/// decoded. Requests with any other encoding, or with several of them, are
+/// rejected with `415 Unsupported Media Type`.
+///
+/// The decoded body may not be longer than `limit` bytes, whether it was
+/// encoded or not. This protects against small payloads that decompress
+/// into huge ones. Filters reading the body reject with
+/// `413 Payload Too Large` when the limit is exceeded.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::HashMap;
+/// use starterm::Filter;
+///
+/// // Accept gzipped JSON of up to 1mb once decompressed...
+/// let route = starterm::body::decompressed(1024 * 1024)
+///     .and(starterm::body::json())
+///     .map(|simple_map: HashMap<String, String>| {
+///         "Got a JSON body!"
+///     });
+/// ```
+#[cfg(any(
+    feature = "compression-brotli",
+    feature = "compression-gzip",
+    feature = "compression-zstd"
+))]
+pub fn decompressed(limit: u64) -> impl Filter<Extract = (), Error = Rejection> + Copy {
+    filter_fn(move |route| future::ready(decompress::decode_body(route, limit)))
+}
+
 /// Create a `Filter` that extracts the request body as a `futures::Stream`.
 ///
 /// If other filters have already extracted the body, this filter will reject
@@ -106,7 +143,7 @@ pub fn bytes() -> impl Filter<Extract = (Bytes,), Error = Rejection> + Copy {
     body().and_then(|body: hyper::Body| {
         hyper::body::to_bytes(body).map_err(|err| {
             tracing::debug!("to_bytes error: {}", err);
-            reject::known(BodyReadError(err))
+            read_error(err)
         })
     })
 }
@@ -144,7 +181,7 @@ pub fn aggregate() -> impl Filter<Extract = (impl Buf,), Error = Rejection> + Co
     body().and_then(|body: ::hyper::Body| {
         hyper::body::aggregate(body).map_err(|err| {
             tracing::debug!("aggregate error: {}", err);
-            reject::known(BodyReadError(err))
+            read_error(err)
         })
     })
 }
@@ -308,12 +345,100 @@ impl Stream for BodyStream {
     }
 }
 
+// ===== Decompression =====
+
+#[cfg(any(
+    feature = "compression-brotli",
+    feature = "compression-gzip",
+    feature = "compression-zstd"
+))]
+mod decompress {
+    use std::io;
+
+    #[cfg(feature = "compression-brotli")]
+    use async_compression::tokio::bufread::BrotliDecoder;
+    #[cfg(feature = "compression-zstd")]
+    use async_compression::tokio::bufread::ZstdDecoder;
+    #[cfg(feature = "compression-gzip")]
+    use async_compression::tokio::bufread::{GzipDecoder, ZlibDecoder};
+    use bytes::Bytes;
+    use futures_util::{Stream, StreamExt, TryStreamExt};
+    use http::header::{CONTENT_ENCODING, CONTENT_LENGTH};
+    use hyper::Body;
+    use tokio::io::AsyncRead;
+    use tokio_util::io::{ReaderStream, StreamReader};
+
+    use super::{BodyLengthExceeded, BoxError};
+    use crate::reject::{self, Rejection};
+    use crate::route::Route;
+
+    pub(super) fn decode_body(route: &mut Route, limit: u64) -> Result<(), Rejection> {
+        let encoding = match route.headers().get(CONTENT_ENCODING) {
+            Some(value) => match value.to_str() {
+                Ok(value) => value.trim().to_ascii_lowercase(),
+                Err(_) => {
+                    tracing::debug!("content-encoding {:?} is not valid", value);
+                    return Err(reject::unsupported_media_type());
+                }
+            },
+            None => String::from("identity"),
+        };
+
+        let body = route.take_body().ok_or_else(|| {
+            tracing::error!("request body already taken in previous filter");
+            reject::known(super::BodyConsumedMultipleTimes { _p: () })
+        })?;
+        let reader = StreamReader::new(body.map_err(io::Error::other));
+
+        let decoded: Box<dyn Stream<Item = Result<Bytes, BoxError>> + Send + Unpin> =
+            match encoding.as_str() {
+                "identity" => Box::new(reader_stream(reader)),
+                #[cfg(feature = "compression-brotli")]
+                "br" => Box::new(reader_stream(BrotliDecoder::new(reader))),
+                #[cfg(feature = "compression-gzip")]
+                "gzip" | "x-gzip" => Box::new(reader_stream(GzipDecoder::new(reader))),
+                #[cfg(feature = "compression-gzip")]
+                "deflate" => Box::new(reader_stream(ZlibDecoder::new(reader))),
+                #[cfg(feature = "compression-zstd")]
+                "zstd" => Box::new(reader_stream(ZstdDecoder::new(reader))),
+                _ => {
+                    tracing::debug!("content-encoding {:?} is not supported", encoding);
+                    return Err(reject::unsupported_media_type());
+                }
+            };
+
+        let mut length = 0u64;
+        let limited = decoded.map(move |chunk| {
+            let chunk = chunk?;
+            length += chunk.len() as u64;
+            if length > limit {
+                tracing::debug!("decompressed body is over limit {}", limit);
+                return Err(BoxError::from(BodyLengthExceeded { _p: () }));
+            }
+            Ok(chunk)
+        });
+
+        let headers = route.headers_mut();
+        headers.remove(CONTENT_ENCODING);
+        headers.remove(CONTENT_LENGTH);
+        route.put_body(Body::wrap_stream(limited));
+        Ok(())
+    }
+
+    fn reader_stream<R>(reader: R) -> impl Stream<Item = Result<Bytes, BoxError>> + Send + Unpin
+    where
+        R: AsyncRead + Send + Unpin,
+    {
+        ReaderStream::new(reader).map_err(BoxError::from)
+    }
+}
+
 // ===== Rejections =====
 
 /// An error used in rejections when deserializing a request body fails.
 #[derive(Debug)]
 pub struct BodyDeserializeError {
-    cause: BoxError,
+    pub(crate) cause: BoxError,
 }
 
 impl fmt::Display for BodyDeserializeError {
@@ -342,3 +467,21 @@ impl StdError for BodyReadError {}
 unit_error! {
     pub(crate) BodyConsumedMultipleTimes: "Request body consumed multiple times"
 }
+
+unit_error! {
+    pub(crate) BodyLengthExceeded: "Request body is over the length limit"
+}
+
+// Reject with `413 Payload Too Large` if reading stopped at a length limit,
+// and `400 Bad Request` otherwise.
+fn read_error(err: hyper::Error) -> Rejection {
+    let over_limit = err
+        .source()
+        .map(|cause| cause.is::<BodyLengthExceeded>())
+        .unwrap_or(false);
+    if over_limit {
+        reject::payload_too_large()
+    } else {
+        reject::known(BodyReadError(err))
+    }
+}
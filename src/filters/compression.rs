@@ -3,24 +3,35 @@
 //! Filters that compress the body of a response.
 
 #[cfg(feature = "compression-brotli")]
-use async_compression::tokio::bufread::BrotliEncoder;
+use async_compression::tokio::{bufread::BrotliEncoder, write::BrotliEncoder as BrotliWriter};
 
 #[cfg(feature = "compression-gzip")]
-use async_compression::tokio::bufread::{DeflateEncoder, GzipEncoder};
+use async_compression::tokio::{
+    bufread::{DeflateEncoder, GzipEncoder},
+    write::{DeflateEncoder as DeflateWriter, GzipEncoder as GzipWriter},
+};
+
+#[cfg(feature = "compression-zstd")]
+use async_compression::tokio::{bufread::ZstdEncoder, write::ZstdEncoder as ZstdWriter};
 
-use http::header::HeaderValue;
+use http::header::{HeaderMap, HeaderValue};
+use http::StatusCode;
 use hyper::{
-    header::{CONTENT_ENCODING, CONTENT_LENGTH},
+    body::HttpBody,
+    header::{CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, VARY},
     Body,
 };
+use mime::Mime;
 use tokio_util::io::{ReaderStream, StreamReader};
 
 use crate::filter::{Filter, WrapSealed};
+use crate::filters::header::rank_accepted;
 use crate::reject::IsReject;
 use crate::reply::{Reply, Response};
 
-use self::internal::{CompressionProps, WithCompression};
+use self::internal::{flush_every_chunk, CompressionProps, WithCompression};
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum CompressionAlgo {
     #[cfg(feature = "compression-brotli")]
     Br,
@@ -28,30 +39,335 @@ enum CompressionAlgo {
     Deflate,
     #[cfg(feature = "compression-gzip")]
     Gzip,
+    #[cfg(feature = "compression-zstd")]
+    Zstd,
 }
 
-impl From<CompressionAlgo> for HeaderValue {
-    #[inline]
-    fn from(algo: CompressionAlgo) -> Self {
-        HeaderValue::from_static(match algo {
+impl CompressionAlgo {
+    /// The enabled algorithms, in the order `auto()` prefers them when a
+    /// client weighs several of them equally.
+    const PREFERRED: &'static [CompressionAlgo] = &[
+        #[cfg(feature = "compression-zstd")]
+        CompressionAlgo::Zstd,
+        #[cfg(feature = "compression-brotli")]
+        CompressionAlgo::Br,
+        #[cfg(feature = "compression-gzip")]
+        CompressionAlgo::Gzip,
+        #[cfg(feature = "compression-gzip")]
+        CompressionAlgo::Deflate,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
             #[cfg(feature = "compression-brotli")]
             CompressionAlgo::Br => "br",
             #[cfg(feature = "compression-gzip")]
             CompressionAlgo::Deflate => "deflate",
             #[cfg(feature = "compression-gzip")]
             CompressionAlgo::Gzip => "gzip",
+            #[cfg(feature = "compression-zstd")]
+            CompressionAlgo::Zstd => "zstd",
+        }
+    }
+
+    /// Picks the best enabled algorithm allowed by the `Accept-Encoding`
+    /// header values of a request.
+    ///
+    /// Returns `None` if the header is missing, or if none of the enabled
+    /// algorithms has a non-zero quality value.
+    fn negotiate<'a, I>(accept_encoding: I) -> Option<CompressionAlgo>
+    where
+        I: IntoIterator<Item = &'a HeaderValue>,
+    {
+        rank_accepted(accept_encoding, CompressionAlgo::PREFERRED, |algo| {
+            algo.as_str()
         })
+        .into_iter()
+        .next()
+    }
+}
+
+impl From<CompressionAlgo> for HeaderValue {
+    #[inline]
+    fn from(algo: CompressionAlgo) -> Self {
+        HeaderValue::from_static(algo.as_str())
     }
 }
 
 /// Compression
+///
+/// Besides the algorithm, a `Compression` holds the policy deciding which
+/// responses are worth compressing at all. Responses are always passed
+/// through unchanged when they:
+///
+/// - already have a `content-encoding`,
+/// - are partial (`206 Partial Content`, or have a `content-range`),
+/// - have a status that never carries a body (`1xx`, `204`, `304`),
+/// - were marked with [`without_compression`].
 #[derive(Clone, Copy, Debug)]
 pub struct Compression<F> {
     func: F,
+    level: Level,
+    flush_every_chunk: bool,
+    min_length: u64,
+    mime_filter: fn(&Mime) -> bool,
+    vary: bool,
+}
+
+/// The level of compression used by a [`Compression`] filter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    /// The fastest compression, usually producing a bigger body.
+    Fastest,
+    /// The best compression, usually producing the smallest body.
+    Best,
+    /// The default level of the algorithm.
+    Default,
+    /// A specific level, interpreted by each algorithm, and clamped to its
+    /// range. For instance, `1` to `9` for gzip and deflate, `0` to `11`
+    /// for brotli, and `1` to `22` for zstd.
+    Precise(i32),
+}
+
+impl From<Level> for async_compression::Level {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Fastest => async_compression::Level::Fastest,
+            Level::Best => async_compression::Level::Best,
+            Level::Default => async_compression::Level::Default,
+            Level::Precise(level) => async_compression::Level::Precise(level),
+        }
+    }
+}
+
+impl<F> Compression<F> {
+    fn new(func: F) -> Self {
+        Compression {
+            func,
+            level: Level::Default,
+            flush_every_chunk: false,
+            min_length: 0,
+            mime_filter: is_compressible,
+            vary: false,
+        }
+    }
+
+    /// Set the level (or quality) of compression.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use starterm::Filter;
+    /// use starterm::compression::Level;
+    ///
+    /// let route = starterm::any()
+    ///     .map(|| "compressed as small as it gets")
+    ///     .with(starterm::compression::auto().level(Level::Best));
+    /// ```
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets whether the encoder is flushed after every chunk of the body.
+    ///
+    /// By default, the encoder decides when to emit compressed data, and may
+    /// hold on to a small chunk until more of the body arrives. Enabling this
+    /// sends each chunk to the client as soon as the wrapped reply yields it,
+    /// at the cost of a worse compression ratio. This is useful for bodies
+    /// that should stay real-time, such as server-sent events or streamed
+    /// NDJSON.
+    ///
+    /// Note that the default [`mime_filter`](Compression::mime_filter) never
+    /// compresses `text/event-stream`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use starterm::Filter;
+    ///
+    /// let route = starterm::any()
+    ///     .map(|| "streamed")
+    ///     .with(starterm::compression::gzip().flush_every_chunk(true));
+    /// ```
+    pub fn flush_every_chunk(mut self, flush: bool) -> Self {
+        self.flush_every_chunk = flush;
+        self
+    }
+
+    /// Skip compression of responses whose length is lower than `min_length`.
+    ///
+    /// The length is taken from `content-length`, or from the body itself
+    /// when it is fully in memory. Responses without a known length, such as
+    /// streams, are still compressed.
+    /// The default is `0`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use starterm::Filter;
+    ///
+    /// let route = starterm::any()
+    ///     .map(|| "too short to bother")
+    ///     .with(starterm::compression::auto().min_length(1024));
+    /// ```
+    pub fn min_length(mut self, min_length: u64) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// Set the predicate deciding which `content-type`s are compressed.
+    ///
+    /// Responses without a (valid) `content-type` are always compressed.
+    /// The default is [`is_compressible`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use starterm::Filter;
+    ///
+    /// let route = starterm::any()
+    ///     .map(|| "only text gets compressed")
+    ///     .with(starterm::compression::gzip().mime_filter(|mime| mime.type_() == "text"));
+    /// ```
+    pub fn mime_filter(mut self, filter: fn(&Mime) -> bool) -> Self {
+        self.mime_filter = filter;
+        self
+    }
+
+    fn should_compress(&self, resp: &Response) -> bool {
+        let status = resp.status();
+        if status.is_informational()
+            || status == StatusCode::NO_CONTENT
+            || status == StatusCode::NOT_MODIFIED
+            || status == StatusCode::PARTIAL_CONTENT
+        {
+            return false;
+        }
+
+        let headers = resp.headers();
+        if headers.contains_key(CONTENT_ENCODING) || headers.contains_key(CONTENT_RANGE) {
+            return false;
+        }
+        if resp.extensions().get::<NoCompression>().is_some() {
+            return false;
+        }
+
+        let len = headers
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+            .or_else(|| resp.body().size_hint().exact());
+        if matches!(len, Some(len) if len < self.min_length) {
+            return false;
+        }
+
+        let mime = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<Mime>().ok());
+        match mime {
+            Some(mime) => (self.mime_filter)(&mime),
+            None => true,
+        }
+    }
+}
+
+/// The default `content-type` predicate of [`Compression::mime_filter`].
+///
+/// Returns `false` for media that is already compressed, or that must not be
+/// buffered by an encoder: images (except SVG), audio, video, fonts in
+/// compressed formats, archives, and `text/event-stream`.
+pub fn is_compressible(mime: &Mime) -> bool {
+    match (mime.type_(), mime.subtype()) {
+        (mime::IMAGE, mime::SVG) => true,
+        (mime::IMAGE, _) | (mime::AUDIO, _) | (mime::VIDEO, _) => false,
+        (mime::TEXT, mime::EVENT_STREAM) => false,
+        (mime::FONT, subtype) => !matches!(subtype.as_str(), "woff" | "woff2"),
+        (mime::APPLICATION, subtype) => !matches!(
+            subtype.as_str(),
+            "zip"
+                | "gzip"
+                | "x-gzip"
+                | "x-bzip2"
+                | "x-xz"
+                | "x-7z-compressed"
+                | "x-rar-compressed"
+                | "vnd.rar"
+                | "zstd"
+                | "brotli"
+                | "font-woff"
+                | "wasm"
+        ),
+        _ => true,
+    }
+}
+
+/// Wrap an `impl Reply` so that compression filters leave it untouched.
+///
+/// # Example
+///
+/// ```
+/// use starterm::Filter;
+///
+/// let route = starterm::any()
+///     .map(|| starterm::compression::without_compression("sent as is"))
+///     .with(starterm::compression::gzip());
+/// ```
+pub fn without_compression<T: Reply>(reply: T) -> WithoutCompression<T> {
+    WithoutCompression { reply }
 }
 
-// TODO: The implementation of `gzip()`, `deflate()`, and `brotli()` could be replaced with
-// generics or a macro
+/// Wraps an `impl Reply` so that compression filters leave it untouched.
+///
+/// Returned by `starterm::compression::without_compression`.
+#[derive(Debug)]
+pub struct WithoutCompression<T> {
+    reply: T,
+}
+
+impl<T: Reply> Reply for WithoutCompression<T> {
+    fn into_response(self) -> Response {
+        let mut res = self.reply.into_response();
+        res.extensions_mut().insert(NoCompression);
+        res
+    }
+}
+
+/// Response extension marking a response as not to be compressed.
+#[derive(Clone, Copy, Debug)]
+struct NoCompression;
+
+/// Create a wrapping filter that compresses the Body of a [`Response`](crate::reply::Response)
+/// using the best algorithm the client accepts, based on the request's `Accept-Encoding` header.
+///
+/// Only the algorithms enabled through the `compression-*` features are considered. When
+/// the client weighs several of them equally, zstd is preferred, then brotli, gzip and
+/// deflate. If the client doesn't send `Accept-Encoding`, or accepts none of them, the
+/// response is passed through unchanged. Every response, including those skipped by the
+/// compression policy, gets `vary: accept-encoding` added to its
+/// [`HeaderMap`](hyper::HeaderMap).
+///
+/// # Example
+///
+/// ```
+/// use starterm::Filter;
+///
+/// let route = starterm::get()
+///     .and(starterm::path::end())
+///     .and(starterm::fs::file("./README.md"))
+///     .with(starterm::compression::auto());
+/// ```
+pub fn auto() -> Compression<impl Fn(CompressionProps) -> Response + Copy> {
+    let func = move |props: CompressionProps| match props.accepted {
+        Some(algo) => encode(algo, props),
+        None => Response::from_parts(props.head, props.body.into_inner()),
+    };
+    Compression {
+        vary: true,
+        ..Compression::new(func)
+    }
+}
 
 /// Create a wrapping filter that compresses the Body of a [`Response`](crate::reply::Response)
 /// using gzip, adding `content-encoding: gzip` to the Response's [`HeaderMap`](hyper::HeaderMap)
@@ -68,18 +384,8 @@ pub struct Compression<F> {
 /// ```
 #[cfg(feature = "compression-gzip")]
 pub fn gzip() -> Compression<impl Fn(CompressionProps) -> Response + Copy> {
-    let func = move |mut props: CompressionProps| {
-        let body = Body::wrap_stream(ReaderStream::new(GzipEncoder::new(StreamReader::new(
-            props.body,
-        ))));
-        props
-            .head
-            .headers
-            .append(CONTENT_ENCODING, CompressionAlgo::Gzip.into());
-        props.head.headers.remove(CONTENT_LENGTH);
-        Response::from_parts(props.head, body)
-    };
-    Compression { func }
+    let func = move |props: CompressionProps| encode(CompressionAlgo::Gzip, props);
+    Compression::new(func)
 }
 
 /// Create a wrapping filter that compresses the Body of a [`Response`](crate::reply::Response)
@@ -97,18 +403,8 @@ pub fn gzip() -> Compression<impl Fn(CompressionProps) -> Response + Copy> {
 /// ```
 #[cfg(feature = "compression-gzip")]
 pub fn deflate() -> Compression<impl Fn(CompressionProps) -> Response + Copy> {
-    let func = move |mut props: CompressionProps| {
-        let body = Body::wrap_stream(ReaderStream::new(DeflateEncoder::new(StreamReader::new(
-            props.body,
-        ))));
-        props
-            .head
-            .headers
-            .append(CONTENT_ENCODING, CompressionAlgo::Deflate.into());
-        props.head.headers.remove(CONTENT_LENGTH);
-        Response::from_parts(props.head, body)
-    };
-    Compression { func }
+    let func = move |props: CompressionProps| encode(CompressionAlgo::Deflate, props);
+    Compression::new(func)
 }
 
 /// Create a wrapping filter that compresses the Body of a [`Response`](crate::reply::Response)
@@ -126,18 +422,89 @@ pub fn deflate() -> Compression<impl Fn(CompressionProps) -> Response + Copy> {
 /// ```
 #[cfg(feature = "compression-brotli")]
 pub fn brotli() -> Compression<impl Fn(CompressionProps) -> Response + Copy> {
-    let func = move |mut props: CompressionProps| {
-        let body = Body::wrap_stream(ReaderStream::new(BrotliEncoder::new(StreamReader::new(
-            props.body,
-        ))));
-        props
-            .head
-            .headers
-            .append(CONTENT_ENCODING, CompressionAlgo::Br.into());
-        props.head.headers.remove(CONTENT_LENGTH);
-        Response::from_parts(props.head, body)
+    let func = move |props: CompressionProps| encode(CompressionAlgo::Br, props);
+    Compression::new(func)
+}
+
+/// Create a wrapping filter that compresses the Body of a [`Response`](crate::reply::Response)
+/// using zstd, adding `content-encoding: zstd` to the Response's [`HeaderMap`](hyper::HeaderMap)
+///
+/// # Example
+///
+/// ```
+/// use starterm::Filter;
+///
+/// let route = starterm::get()
+///     .and(starterm::path::end())
+///     .and(starterm::fs::file("./README.md"))
+///     .with(starterm::compression::zstd());
+/// ```
+#[cfg(feature = "compression-zstd")]
+pub fn zstd() -> Compression<impl Fn(CompressionProps) -> Response + Copy> {
+    let func = move |props: CompressionProps| encode(CompressionAlgo::Zstd, props);
+    Compression::new(func)
+}
+
+fn encode(algo: CompressionAlgo, mut props: CompressionProps) -> Response {
+    let level = props.level.into();
+    let body = if props.flush_every_chunk {
+        match algo {
+            #[cfg(feature = "compression-brotli")]
+            CompressionAlgo::Br => {
+                flush_every_chunk(BrotliWriter::with_quality(Vec::new(), level), props.body)
+            }
+            #[cfg(feature = "compression-gzip")]
+            CompressionAlgo::Deflate => {
+                flush_every_chunk(DeflateWriter::with_quality(Vec::new(), level), props.body)
+            }
+            #[cfg(feature = "compression-gzip")]
+            CompressionAlgo::Gzip => {
+                flush_every_chunk(GzipWriter::with_quality(Vec::new(), level), props.body)
+            }
+            #[cfg(feature = "compression-zstd")]
+            CompressionAlgo::Zstd => {
+                flush_every_chunk(ZstdWriter::with_quality(Vec::new(), level), props.body)
+            }
+        }
+    } else {
+        let reader = StreamReader::new(props.body);
+        match algo {
+            #[cfg(feature = "compression-brotli")]
+            CompressionAlgo::Br => Body::wrap_stream(ReaderStream::new(
+                BrotliEncoder::with_quality(reader, level),
+            )),
+            #[cfg(feature = "compression-gzip")]
+            CompressionAlgo::Deflate => Body::wrap_stream(ReaderStream::new(
+                DeflateEncoder::with_quality(reader, level),
+            )),
+            #[cfg(feature = "compression-gzip")]
+            CompressionAlgo::Gzip => {
+                Body::wrap_stream(ReaderStream::new(GzipEncoder::with_quality(reader, level)))
+            }
+            #[cfg(feature = "compression-zstd")]
+            CompressionAlgo::Zstd => {
+                Body::wrap_stream(ReaderStream::new(ZstdEncoder::with_quality(reader, level)))
+            }
+        }
     };
-    Compression { func }
+    props.head.headers.append(CONTENT_ENCODING, algo.into());
+    props.head.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(props.head, body)
+}
+
+fn add_vary_accept_encoding(headers: &mut HeaderMap) {
+    let already_varies = headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|name| {
+            let name = name.trim();
+            name == "*" || name.eq_ignore_ascii_case("accept-encoding")
+        });
+    if !already_varies {
+        headers.append(VARY, HeaderValue::from_static("accept-encoding"));
+    }
 }
 
 impl<FN, F> WrapSealed<F> for Compression<FN>
@@ -162,16 +529,21 @@ mod internal {
     use std::pin::Pin;
     use std::task::{Context, Poll};
 
+    use std::mem;
+
     use bytes::Bytes;
-    use futures_util::{ready, Stream, TryFuture};
+    use futures_util::{ready, stream, Stream, TryFuture, TryStreamExt};
+    use hyper::header::ACCEPT_ENCODING;
     use hyper::Body;
     use pin_project::pin_project;
+    use tokio::io::{AsyncWrite, AsyncWriteExt};
 
     use crate::filter::{Filter, FilterBase, Internal};
     use crate::reject::IsReject;
     use crate::reply::{Reply, Response};
+    use crate::route;
 
-    use super::Compression;
+    use super::{add_vary_accept_encoding, Compression, CompressionAlgo, Level};
 
     /// A wrapper around any type that implements [`Stream`](futures::Stream) to be
     /// compatible with async_compression's Stream based encoders
@@ -201,17 +573,93 @@ mod internal {
         }
     }
 
+    impl<S, E> CompressableBody<S, E>
+    where
+        E: std::error::Error,
+        S: Stream<Item = Result<Bytes, E>>,
+    {
+        pub(super) fn into_inner(self) -> S {
+            self.body
+        }
+    }
+
     impl From<Body> for CompressableBody<Body, hyper::Error> {
         fn from(body: Body) -> Self {
             CompressableBody { body }
         }
     }
 
+    /// An encoder writing its compressed output into a `Vec<u8>`.
+    pub(super) trait WriteEncoder: AsyncWrite + Unpin + Send + 'static {
+        fn output(&mut self) -> &mut Vec<u8>;
+    }
+
+    macro_rules! impl_write_encoder {
+        ($($(#[$attr:meta])* $ty:ty,)*) => {
+            $(
+                $(#[$attr])*
+                impl WriteEncoder for $ty {
+                    fn output(&mut self) -> &mut Vec<u8> {
+                        self.get_mut()
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_write_encoder! {
+        #[cfg(feature = "compression-brotli")]
+        super::BrotliWriter<Vec<u8>>,
+        #[cfg(feature = "compression-gzip")]
+        super::DeflateWriter<Vec<u8>>,
+        #[cfg(feature = "compression-gzip")]
+        super::GzipWriter<Vec<u8>>,
+        #[cfg(feature = "compression-zstd")]
+        super::ZstdWriter<Vec<u8>>,
+    }
+
+    /// Compresses `body` with `encoder`, flushing it after every chunk so that
+    /// each one reaches the client without waiting for the next.
+    pub(super) fn flush_every_chunk<E: WriteEncoder>(
+        encoder: E,
+        body: CompressableBody<Body, hyper::Error>,
+    ) -> Body {
+        let chunks = stream::try_unfold(
+            (encoder, Some(body)),
+            |(mut encoder, mut body)| async move {
+                loop {
+                    let chunk = match body.as_mut() {
+                        Some(body) => body.try_next().await?,
+                        None => return Ok(None),
+                    };
+                    match chunk {
+                        Some(chunk) if chunk.is_empty() => continue,
+                        Some(chunk) => {
+                            encoder.write_all(&chunk).await?;
+                            encoder.flush().await?;
+                        }
+                        None => {
+                            encoder.shutdown().await?;
+                            body = None;
+                        }
+                    }
+                    let compressed = Bytes::from(mem::take(encoder.output()));
+                    return Ok::<_, std::io::Error>(Some((compressed, (encoder, body))));
+                }
+            },
+        );
+        Body::wrap_stream(chunks)
+    }
+
     /// Compression Props
     #[derive(Debug)]
     pub struct CompressionProps {
         pub(super) body: CompressableBody<Body, hyper::Error>,
         pub(super) head: http::response::Parts,
+        /// The algorithm negotiated from the request's `Accept-Encoding`.
+        pub(super) accepted: Option<CompressionAlgo>,
+        pub(super) level: Level,
+        pub(super) flush_every_chunk: bool,
     }
 
     impl From<http::Response<Body>> for CompressionProps {
@@ -220,6 +668,9 @@ mod internal {
             CompressionProps {
                 body: body.into(),
                 head,
+                accepted: None,
+                level: Level::Default,
+                flush_every_chunk: false,
             }
         }
     }
@@ -253,8 +704,12 @@ mod internal {
         type Future = WithCompressionFuture<FN, F::Future>;
 
         fn filter(&self, _: Internal) -> Self::Future {
+            let accepted = route::with(|route| {
+                CompressionAlgo::negotiate(route.headers().get_all(ACCEPT_ENCODING))
+            });
             WithCompressionFuture {
                 compress: self.compress.clone(),
+                accepted,
                 future: self.filter.filter(Internal),
             }
         }
@@ -264,6 +719,7 @@ mod internal {
     #[pin_project]
     pub struct WithCompressionFuture<FN, F> {
         compress: Compression<FN>,
+        accepted: Option<CompressionAlgo>,
         #[pin]
         future: F,
     }
@@ -282,7 +738,18 @@ mod internal {
             let result = ready!(pin.future.try_poll(cx));
             match result {
                 Ok(reply) => {
-                    let resp = (self.compress.func)(reply.into_response().into());
+                    let mut resp = reply.into_response();
+                    if self.compress.vary {
+                        add_vary_accept_encoding(resp.headers_mut());
+                    }
+                    if !self.compress.should_compress(&resp) {
+                        return Poll::Ready(Ok((Compressed(resp),)));
+                    }
+                    let mut props = CompressionProps::from(resp);
+                    props.accepted = self.accepted;
+                    props.level = self.compress.level;
+                    props.flush_every_chunk = self.compress.flush_every_chunk;
+                    let resp = (self.compress.func)(props);
                     Poll::Ready(Ok((Compressed(resp),)))
                 }
                 Err(reject) => Poll::Ready(Err(reject)),
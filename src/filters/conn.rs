@@ -0,0 +1,132 @@
+//! Connection information filters.
+//!
+//! Everything known about the connection a request came on is kept as typed
+//! values, and can be read by type with [`info`] or [`optional`]. The
+//! built-in listeners provide [`RemoteAddr`], [`LocalAddr`] and [`TlsInfo`],
+//! and [`UnixPeer`](crate::addr::UnixPeer) for Unix domain sockets. A custom
+//! [`Transport`](crate::Transport) can add any other type.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use futures_util::future;
+
+use crate::filter::{filter_fn_one, Filter};
+use crate::reject::{self, Rejection};
+
+/// Get a value of type `T` provided by the connection of the current
+/// request.
+///
+/// If the connection didn't provide one, this rejects with a
+/// `MissingConnInfo`.
+///
+/// # Example
+///
+/// ```
+/// use starterm::Filter;
+/// use starterm::conn::LocalAddr;
+///
+/// let route = starterm::conn::info::<LocalAddr>()
+///     .map(|local: LocalAddr| {
+///         format!("accepted on port {}", local.0.port())
+///     });
+/// ```
+pub fn info<T: Clone + Send + Sync + 'static>(
+) -> impl Filter<Extract = (T,), Error = Rejection> + Copy {
+    filter_fn_one(|route| {
+        let info = route
+            .conn_extensions()
+            .get::<T>()
+            .cloned()
+            .ok_or_else(|| reject::known(MissingConnInfo { _p: () }));
+        future::ready(info)
+    })
+}
+
+/// Get a value of type `T` provided by the connection of the current
+/// request.
+///
+/// If the connection didn't provide one, it yields `None`.
+///
+/// # Example
+///
+/// ```
+/// use starterm::Filter;
+/// use starterm::conn::TlsInfo;
+///
+/// let route = starterm::conn::optional::<TlsInfo>()
+///     .map(|tls: Option<TlsInfo>| {
+///         let h2 = tls.map_or(false, |tls| tls.alpn_protocol() == Some(b"h2"));
+///         format!("negotiated h2: {}", h2)
+///     });
+/// ```
+pub fn optional<T: Clone + Send + Sync + 'static>(
+) -> impl Filter<Extract = (Option<T>,), Error = Infallible> + Copy {
+    filter_fn_one(|route| future::ok(route.conn_extensions().get::<T>().cloned()))
+}
+
+/// The address of the remote peer of a connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RemoteAddr(pub SocketAddr);
+
+/// The local address a connection was accepted on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LocalAddr(pub SocketAddr);
+
+/// The TLS session of a connection.
+///
+/// A custom [`Transport`](crate::Transport) terminating TLS can create one
+/// with [`TlsInfo::new`] and the `with_` methods.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TlsInfo {
+    server_name: Option<String>,
+    alpn_protocol: Option<Vec<u8>>,
+    peer_certificates: Vec<Vec<u8>>,
+}
+
+impl TlsInfo {
+    /// Creates a `TlsInfo` with nothing negotiated.
+    pub fn new() -> Self {
+        TlsInfo::default()
+    }
+
+    /// Sets the server name the client asked for with SNI.
+    pub fn with_server_name(mut self, name: impl Into<String>) -> Self {
+        self.server_name = Some(name.into());
+        self
+    }
+
+    /// Sets the protocol negotiated with ALPN.
+    pub fn with_alpn_protocol(mut self, protocol: impl Into<Vec<u8>>) -> Self {
+        self.alpn_protocol = Some(protocol.into());
+        self
+    }
+
+    /// Sets the DER encoded certificate chain of the client, starting with
+    /// its own certificate.
+    pub fn with_peer_certificates(mut self, certs: Vec<Vec<u8>>) -> Self {
+        self.peer_certificates = certs;
+        self
+    }
+
+    /// Returns the server name the client asked for with SNI.
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+
+    /// Returns the protocol negotiated with ALPN, such as `b"h2"`.
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        self.alpn_protocol.as_deref()
+    }
+
+    /// Returns the DER encoded certificate chain of the client, which is
+    /// empty without client authentication.
+    pub fn peer_certificates(&self) -> &[Vec<u8>] {
+        &self.peer_certificates
+    }
+}
+
+unit_error! {
+    /// An error used to reject if `info` cannot find the connection info.
+    pub MissingConnInfo: "Missing connection info"
+}
@@ -1,25 +1,37 @@
 //! File System Filters
 
 use std::cmp;
+use std::collections::HashMap;
 use std::convert::Infallible;
+use std::convert::TryFrom;
+use std::fmt;
 use std::fs::Metadata;
-use std::future::Future;
 use std::io;
+use std::iter::FromIterator;
+use std::ops;
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::task::Poll;
+use std::time::{Duration, Instant, SystemTime};
 
 use bytes::{Bytes, BytesMut};
-use futures_util::future::Either;
-use futures_util::{future, ready, stream, FutureExt, Stream, StreamExt, TryFutureExt};
+use futures_util::future::{BoxFuture, Either};
+use futures_util::stream::BoxStream;
+use futures_util::{future, ready, stream, FutureExt, SinkExt, Stream, StreamExt, TryFutureExt};
 use headers::{
-    AcceptRanges, ContentLength, ContentRange, ContentType, HeaderMapExt, IfModifiedSince, IfRange,
-    IfUnmodifiedSince, LastModified, Range,
+    AcceptRanges, ContentLength, ContentRange, ContentType, ETag, HeaderMapExt, IfMatch,
+    IfModifiedSince, IfNoneMatch, IfRange, IfUnmodifiedSince, LastModified, Range,
 };
-use http::StatusCode;
+use http::header::{
+    HeaderValue, ACCEPT, ACCEPT_ENCODING, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_TYPE, LOCATION,
+    VARY,
+};
+use http::{Method, StatusCode};
 use hyper::Body;
-use percent_encoding::percent_decode_str;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use sha1::{Digest, Sha1};
 use tokio::fs::File as TkFile;
 use tokio::io::AsyncSeekExt;
 use tokio_util::io::poll_read_buf;
@@ -45,14 +57,53 @@ use crate::reply::{Reply, Response};
 /// let route = starterm::fs::file("/www/static/app.js");
 /// ```
 pub fn file(path: impl Into<PathBuf>) -> impl FilterClone<Extract = One<File>, Error = Rejection> {
+    file_with(path, FileOptions::default())
+}
+
+/// Creates a `Filter` that serves a File at the `path`, configured by `options`.
+///
+/// See [file](fn@file) and [FileOptions].
+///
+/// # Example
+///
+/// ```
+/// use starterm::fs::FileOptions;
+///
+/// // Serves `app.js.br` or `app.js.gz` instead, if they exist
+/// // and the client accepts them.
+/// let route = starterm::fs::file_with(
+///     "/www/static/app.js",
+///     FileOptions::new().precompressed(true),
+/// );
+/// ```
+pub fn file_with(
+    path: impl Into<PathBuf>,
+    options: FileOptions,
+) -> impl FilterClone<Extract = One<File>, Error = Rejection> {
     let path = Arc::new(path.into());
-    crate::any()
-        .map(move || {
+    let options = Arc::new(options);
+    // Without a root, paths are opened as they are.
+    let storage = Arc::new(LocalStorage::new(""));
+    conditionals()
+        .and(accepted_sidecars(options.precompressed))
+        .and_then(move |conditionals, sidecars| {
             tracing::trace!("file: {:?}", path);
-            ArcPath(path.clone())
+            let path = ArcPath(path.clone());
+            let storage = storage.clone();
+            let options = options.clone();
+            async move {
+                let storage_path = path.as_ref().to_owned();
+                file_reply(
+                    &*storage,
+                    &storage_path,
+                    path,
+                    conditionals,
+                    sidecars,
+                    &options,
+                )
+                .await
+            }
         })
-        .and(conditionals())
-        .and_then(file_reply)
 }
 
 /// Creates a `Filter` that serves a directory at the base `path` joined
@@ -78,36 +129,1047 @@ pub fn file(path: impl Into<PathBuf>) -> impl FilterClone<Extract = One<File>, E
 /// // - `GET /static/css/app.css` would serve the file `/www/static/css/app.css`
 /// ```
 pub fn dir(path: impl Into<PathBuf>) -> impl FilterClone<Extract = One<File>, Error = Rejection> {
-    let base = Arc::new(path.into());
+    dir_with(path, DirOptions::default())
+}
+
+/// Creates a `Filter` that serves a directory at the base `path`, configured
+/// by `options`.
+///
+/// See [dir] and [DirOptions].
+///
+/// # Example
+///
+/// ```
+/// use starterm::Filter;
+/// use starterm::fs::DirOptions;
+///
+/// let route = starterm::path("static")
+///     .and(starterm::fs::dir_with("/www/static", DirOptions::new().precompressed(true)));
+/// ```
+pub fn dir_with(
+    path: impl Into<PathBuf>,
+    options: DirOptions,
+) -> impl FilterClone<Extract = One<File>, Error = Rejection> {
+    let base = path.into();
+    let storage =
+        LocalStorage::new(base.clone()).symlinks_outside_root(options.symlinks_outside_base);
+    serve_dir(storage, base, options)
+}
+
+/// Creates a `Filter` that serves the files of a [Storage], looked up by the
+/// request path.
+///
+/// This behaves like [dir], with the files coming from `storage` instead of
+/// a directory of the local file system.
+///
+/// # Example
+///
+/// ```
+/// use starterm::Filter;
+/// use starterm::fs::LocalStorage;
+///
+/// let route = starterm::path("static")
+///     .and(starterm::fs::dir_from(LocalStorage::new("/www/static")));
+/// ```
+pub fn dir_from<S: Storage>(
+    storage: S,
+) -> impl FilterClone<Extract = One<File>, Error = Rejection> {
+    dir_from_with(storage, DirOptions::default())
+}
+
+/// Creates a `Filter` that serves the files of a [Storage], configured by
+/// `options`.
+///
+/// See [dir_from] and [DirOptions]. The [File]s it extracts have the paths
+/// they have in the storage.
+pub fn dir_from_with<S: Storage>(
+    storage: S,
+    options: DirOptions,
+) -> impl FilterClone<Extract = One<File>, Error = Rejection> {
+    serve_dir(storage, PathBuf::new(), options)
+}
+
+// A directory being served. `base` is only prepended to the paths of the
+// extracted `File`s, the storage is given paths relative to it.
+struct ServeDir<S> {
+    storage: S,
+    base: PathBuf,
+    options: DirOptions,
+    file_options: FileOptions,
+}
+
+fn serve_dir<S: Storage>(
+    storage: S,
+    base: PathBuf,
+    options: DirOptions,
+) -> impl FilterClone<Extract = One<File>, Error = Rejection> {
+    let mut file_options = options.file.clone();
+    file_options.base = Some(Arc::new(base.clone()));
+    file_options.cache_scope = next_cache_scope();
+    let precompressed = file_options.precompressed;
+    let dir = Arc::new(ServeDir {
+        storage,
+        base,
+        options,
+        file_options,
+    });
+    crate::get()
+        .or(crate::head())
+        .unify()
+        .and(path_from_tail(dir.clone()))
+        .and(conditionals())
+        .and(accepted_sidecars(precompressed))
+        .and(dir_request())
+        .and_then(move |resolved, conditionals, sidecars, request| {
+            let dir = dir.clone();
+            async move {
+                match resolved {
+                    Resolved::File(path) => {
+                        dir.file_reply(path, conditionals, sidecars, request).await
+                    }
+                    Resolved::Listing(path) => dir.listing_reply(path, request).await,
+                }
+            }
+        })
+}
+
+impl<S: Storage> ServeDir<S> {
+    // Serves a file, or the fallback file instead of a missing one.
+    async fn file_reply(
+        &self,
+        path: PathBuf,
+        conditionals: Conditionals,
+        sidecars: Vec<Sidecar>,
+        request: DirRequest,
+    ) -> Result<File, Rejection> {
+        let display = ArcPath(Arc::new(self.base.join(&path)));
+        let fallback = match self.options.fallback {
+            Some(ref fallback) if wants_fallback(&request) => fallback,
+            _ => {
+                return file_reply(
+                    &self.storage,
+                    &path,
+                    display,
+                    conditionals,
+                    sidecars,
+                    &self.file_options,
+                )
+                .await
+            }
+        };
+
+        match file_reply(
+            &self.storage,
+            &path,
+            display,
+            conditionals.clone(),
+            sidecars.clone(),
+            &self.file_options,
+        )
+        .await
+        {
+            Err(rej) if rej.is_not_found() => {
+                tracing::debug!("dir: serving fallback for {:?}", request.path());
+                let display = ArcPath(Arc::new(self.base.join(fallback)));
+                let mut file = file_reply(
+                    &self.storage,
+                    fallback,
+                    display,
+                    conditionals,
+                    sidecars,
+                    &self.file_options,
+                )
+                .await?;
+                if file.resp.status().is_success() || file.resp.status() == StatusCode::NOT_MODIFIED
+                {
+                    file.resp
+                        .headers_mut()
+                        .insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+                }
+                Ok(file)
+            }
+            res => res,
+        }
+    }
+}
+
+// Only page navigations get the fallback, a missing asset is still a 404.
+fn wants_fallback(request: &DirRequest) -> bool {
+    let last = request.path().rsplit('/').next().unwrap_or_default();
+    request.accepts_html && !last.contains('.')
+}
+
+/// Creates a `Filter` that serves in-memory files, looked up by the request
+/// path.
+///
+/// This behaves like [dir], only without a file system: the `content-type`
+/// is guessed from the path, and `Range`, `HEAD` and conditional requests are
+/// supported. Directory paths serve their `index.html`, and hidden files are
+/// never served. Each file gets a strong `ETag` from a hash of its contents.
+///
+/// # Example
+///
+/// ```
+/// use starterm::Filter;
+/// use starterm::fs::EmbeddedFiles;
+///
+/// let files = EmbeddedFiles::new()
+///     .file("index.html", &b"<h1>Hello</h1>"[..])
+///     .file("css/app.css", &b"h1 { color: red; }"[..]);
+///
+/// let route = starterm::path("static").and(starterm::fs::embedded(files));
+/// ```
+pub fn embedded(files: EmbeddedFiles) -> impl FilterClone<Extract = One<File>, Error = Rejection> {
+    let mut files = files;
+    files.last_modified.get_or_insert_with(SystemTime::now);
+    let files = Arc::new(files);
     crate::get()
         .or(crate::head())
         .unify()
-        .and(path_from_tail(base))
+        .and(crate::path::tail())
         .and(conditionals())
-        .and_then(file_reply)
+        .and_then(move |tail: crate::path::Tail, conditionals| {
+            future::ready(embedded_reply(&files, tail.as_str(), conditionals))
+        })
+}
+
+/// A set of in-memory files, served with [embedded].
+///
+/// It can be built with [EmbeddedFiles::file], or collected from pairs of
+/// paths and contents, such as the ones of an asset embedding macro.
+#[derive(Clone, Debug, Default)]
+pub struct EmbeddedFiles {
+    files: HashMap<String, EmbeddedFile>,
+    last_modified: Option<SystemTime>,
+}
+
+#[derive(Clone, Debug)]
+struct EmbeddedFile {
+    contents: Bytes,
+    etag: ETag,
+}
+
+impl EmbeddedFiles {
+    /// Creates an empty set of files.
+    pub fn new() -> Self {
+        EmbeddedFiles::default()
+    }
+
+    /// Adds a file at `path`, relative to where the filter is mounted.
+    pub fn file(mut self, path: impl Into<String>, contents: impl Into<Bytes>) -> Self {
+        self.insert(path.into(), contents.into());
+        self
+    }
+
+    /// Sets the `Last-Modified` time of all files.
+    ///
+    /// Defaults to the time the [embedded] filter is created.
+    pub fn last_modified(mut self, time: SystemTime) -> Self {
+        self.last_modified = Some(time);
+        self
+    }
+
+    fn insert(&mut self, path: String, contents: Bytes) {
+        let mut hasher = Sha1::new();
+        hasher.update(&contents);
+        let etag = hash_etag(hasher);
+        let path = path.trim_start_matches('/').to_owned();
+        self.files.insert(path, EmbeddedFile { contents, etag });
+    }
+}
+
+impl<P, B> FromIterator<(P, B)> for EmbeddedFiles
+where
+    P: Into<String>,
+    B: Into<Bytes>,
+{
+    fn from_iter<I: IntoIterator<Item = (P, B)>>(iter: I) -> Self {
+        let mut files = EmbeddedFiles::new();
+        for (path, contents) in iter {
+            files.insert(path.into(), contents.into());
+        }
+        files
+    }
+}
+
+fn embedded_reply(
+    files: &EmbeddedFiles,
+    tail: &str,
+    conditionals: Conditionals,
+) -> Result<File, Rejection> {
+    let path = sanitize_path("", tail)?;
+    if is_hidden(&path) {
+        tracing::debug!("embedded: rejecting hidden file {:?}", path);
+        return Err(reject::not_found());
+    }
+
+    let key = path
+        .components()
+        .filter_map(|c| c.as_os_str().to_str())
+        .collect::<Vec<_>>()
+        .join("/");
+    let index = if key.is_empty() {
+        "index.html".to_owned()
+    } else {
+        format!("{}/index.html", key)
+    };
+    let (key, file) = match files.files.get(&key) {
+        Some(file) => (key, file),
+        None => match files.files.get(&index) {
+            Some(file) => (index, file),
+            None => {
+                tracing::debug!("embedded: file not found: {:?}", key);
+                return Err(reject::not_found());
+            }
+        },
+    };
+    tracing::trace!("embedded: {:?}", key);
+
+    let contents = &file.contents;
+    let len = contents.len() as u64;
+    let modified = files.last_modified.map(LastModified::from);
+
+    let mut resp = match conditionals.check(modified, Some(&file.etag)) {
+        Cond::NoBody(resp) => resp,
+        Cond::WithBody(range) => match bytes_ranges(range, len) {
+            Ok(ranges) => {
+                let mime = mime_guess::from_path(&key).first_or_octet_stream();
+                let parts = ranges
+                    .iter()
+                    .map(|&(start, end)| {
+                        let slice = contents.slice(start as usize..end as usize);
+                        stream::once(future::ok::<_, io::Error>(slice))
+                    })
+                    .collect();
+
+                let mut resp = ranges_response(&ranges, len, &mime, parts);
+                resp.headers_mut().typed_insert(AcceptRanges::bytes());
+                if let Some(last_modified) = modified {
+                    resp.headers_mut().typed_insert(last_modified);
+                }
+                resp
+            }
+            Err(BadRange) => unsatisfiable_range(len),
+        },
+    };
+
+    if resp.status() != StatusCode::PRECONDITION_FAILED {
+        resp.headers_mut().typed_insert(file.etag.clone());
+    }
+
+    Ok(File {
+        resp,
+        path: ArcPath(Arc::new(PathBuf::from(key))),
+    })
+}
+
+/// Options for serving a single file, with [file_with].
+#[derive(Clone, Debug, Default)]
+pub struct FileOptions {
+    precompressed: bool,
+    etag: ETagMode,
+    cache_control: Option<HeaderValue>,
+    cache_control_rules: Vec<(CacheControlRule, HeaderValue)>,
+    read_buffer_size: Option<usize>,
+    read_ahead: usize,
+    cache: Option<FileCache>,
+    // Set by `dir_with`, path rules are relative to it.
+    base: Option<Arc<PathBuf>>,
+    // Set by `dir_with` and `dir_from`, so that the paths of different
+    // storages are different `FileCache` keys.
+    cache_scope: u64,
+}
+
+impl FileOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        FileOptions::default()
+    }
+
+    /// Sets whether precompressed "sidecar" files are served.
+    ///
+    /// When enabled, and the request's `Accept-Encoding` allows it, a
+    /// `<file>.br`, `<file>.zst` or `<file>.gz` file next to the requested
+    /// one is served instead, with the matching `content-encoding`. The
+    /// `content-type` is still the one of the requested file, and responses
+    /// get `vary: accept-encoding`. The default is `false`.
+    pub fn precompressed(mut self, precompressed: bool) -> Self {
+        self.precompressed = precompressed;
+        self
+    }
+
+    /// Sets how the `ETag` of served files is computed.
+    ///
+    /// The default is [ETagMode::Strong].
+    pub fn etag(mut self, mode: ETagMode) -> Self {
+        self.etag = mode;
+        self
+    }
+
+    /// Sets the `Cache-Control` header of successful and `304 Not Modified`
+    /// responses.
+    ///
+    /// By default, no `Cache-Control` header is sent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the provided argument is not a valid `http::HeaderValue`.
+    pub fn cache_control<V>(mut self, value: V) -> Self
+    where
+        HeaderValue: TryFrom<V>,
+    {
+        self.cache_control = Some(cache_control_value(value));
+        self
+    }
+
+    /// Sets the size of the buffer files are read with, in bytes.
+    ///
+    /// Each read produces one chunk of the response body. By default, the
+    /// size is picked from the block size of the file system, with a
+    /// minimum of 8 KiB. Larger buffers mean fewer reads for large files.
+    ///
+    /// Files are always copied through these buffers, there is no zero-copy
+    /// `sendfile` or `splice` path: hyper owns the connection, and only takes
+    /// a response body as chunks of `Bytes`, so the socket can't be handed to
+    /// the kernel once the headers are written. For large files, a larger
+    /// buffer with a [read-ahead](FileOptions::read_ahead) is the closest.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is `0`.
+    pub fn read_buffer_size(mut self, size: usize) -> Self {
+        assert!(size > 0, "read buffer size must be greater than 0");
+        self.read_buffer_size = Some(size);
+        self
+    }
+
+    /// Sets how many chunks of a file are read ahead of the connection
+    /// sending them.
+    ///
+    /// With a read-ahead, files are read on a separate task, so disk reads
+    /// overlap with writes to slow clients, at the cost of holding up to
+    /// that many buffers in memory per response. The default is `0`, files
+    /// are read as the body is sent.
+    pub fn read_ahead(mut self, chunks: usize) -> Self {
+        self.read_ahead = chunks;
+        self
+    }
+
+    /// Sets a cache that served files are kept in.
+    ///
+    /// See [FileCache]. By default, files aren't cached.
+    pub fn cache(mut self, cache: FileCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    fn cache_control_for(&self, path: &Path) -> Option<&HeaderValue> {
+        let path = self
+            .base
+            .as_ref()
+            .and_then(|base| path.strip_prefix(base.as_ref()).ok())
+            .unwrap_or(path);
+
+        self.cache_control_rules
+            .iter()
+            .find(|(rule, _)| rule.matches(path))
+            .map(|(_, value)| value)
+            .or(self.cache_control.as_ref())
+    }
+}
+
+#[derive(Clone, Debug)]
+enum CacheControlRule {
+    Extension(String),
+    Path(PathBuf),
+}
+
+impl CacheControlRule {
+    fn matches(&self, path: &Path) -> bool {
+        match self {
+            CacheControlRule::Extension(ext) => path
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|e| e.eq_ignore_ascii_case(ext))
+                .unwrap_or(false),
+            CacheControlRule::Path(prefix) => path.starts_with(prefix),
+        }
+    }
+}
+
+fn cache_control_value<V>(value: V) -> HeaderValue
+where
+    HeaderValue: TryFrom<V>,
+{
+    match TryFrom::try_from(value) {
+        Ok(value) => value,
+        Err(_) => panic!("illegal Cache-Control value"),
+    }
+}
+
+/// Options for serving a directory, with [dir_with].
+#[derive(Clone, Debug)]
+pub struct DirOptions {
+    file: FileOptions,
+    index_files: Vec<String>,
+    hidden_files: bool,
+    symlinks_outside_base: bool,
+    listing: bool,
+    fallback: Option<PathBuf>,
+}
+
+impl Default for DirOptions {
+    fn default() -> Self {
+        DirOptions {
+            file: FileOptions::default(),
+            index_files: vec!["index.html".to_owned()],
+            hidden_files: false,
+            symlinks_outside_base: true,
+            listing: false,
+            fallback: None,
+        }
+    }
+}
+
+impl DirOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        DirOptions::default()
+    }
+
+    /// Sets whether precompressed "sidecar" files are served.
+    ///
+    /// See [FileOptions::precompressed].
+    pub fn precompressed(mut self, precompressed: bool) -> Self {
+        self.file = self.file.precompressed(precompressed);
+        self
+    }
+
+    /// Sets how the `ETag` of served files is computed.
+    ///
+    /// See [FileOptions::etag].
+    pub fn etag(mut self, mode: ETagMode) -> Self {
+        self.file = self.file.etag(mode);
+        self
+    }
+
+    /// Sets the size of the buffer files are read with, in bytes.
+    ///
+    /// See [FileOptions::read_buffer_size].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is `0`.
+    pub fn read_buffer_size(mut self, size: usize) -> Self {
+        self.file = self.file.read_buffer_size(size);
+        self
+    }
+
+    /// Sets how many chunks of a file are read ahead of the connection
+    /// sending them.
+    ///
+    /// See [FileOptions::read_ahead].
+    pub fn read_ahead(mut self, chunks: usize) -> Self {
+        self.file = self.file.read_ahead(chunks);
+        self
+    }
+
+    /// Sets a cache that served files are kept in.
+    ///
+    /// See [FileOptions::cache].
+    pub fn cache(mut self, cache: FileCache) -> Self {
+        self.file = self.file.cache(cache);
+        self
+    }
+
+    /// Sets the file names looked up, in order, when a directory is requested.
+    ///
+    /// The default is `index.html`.
+    pub fn index_files<I>(mut self, names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.index_files = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether hidden files, and files in hidden directories, are served.
+    ///
+    /// Hidden files are the ones whose name starts with a `.`, such as `.env`
+    /// or `.git/config`. By default they are not, and requests for them are
+    /// rejected as not found.
+    pub fn hidden_files(mut self, allow: bool) -> Self {
+        self.hidden_files = allow;
+        self
+    }
+
+    /// Sets whether symlinks that resolve to a file outside of the base
+    /// directory are followed.
+    ///
+    /// When disabled, requests for them are rejected as not found. The
+    /// default is `true`.
+    pub fn symlinks_outside_base(mut self, allow: bool) -> Self {
+        self.symlinks_outside_base = allow;
+        self
+    }
+
+    /// Sets whether directories without an index file are listed.
+    ///
+    /// The listing is HTML, or JSON if the request prefers
+    /// `application/json`, and holds the name, size and modification time
+    /// of each entry. Requests for a directory that don't end in a `/` are
+    /// redirected to the path with one. Hidden files and symlinks are left
+    /// out of the listing the same way they would not be served.
+    ///
+    /// The default is `false`, such requests are rejected as not found.
+    pub fn listing(mut self, enabled: bool) -> Self {
+        self.listing = enabled;
+        self
+    }
+
+    /// Sets a file, relative to the base directory, served in place of
+    /// missing ones, as single-page applications need.
+    ///
+    /// Only requests that accept `text/html` and whose path has no file
+    /// extension get the fallback, so a missing `/app.js.map` is still
+    /// rejected as not found. The fallback is served with
+    /// `cache-control: no-cache`, and honors conditional requests.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use starterm::fs::DirOptions;
+    ///
+    /// // `GET /settings/profile` serves `dist/index.html`.
+    /// let route = starterm::fs::dir_with("dist", DirOptions::new().fallback("index.html"));
+    /// ```
+    pub fn fallback(mut self, path: impl Into<PathBuf>) -> Self {
+        self.fallback = Some(path.into());
+        self
+    }
+
+    /// Sets the `Cache-Control` header used when no more specific rule
+    /// matches.
+    ///
+    /// See [FileOptions::cache_control].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the provided argument is not a valid `http::HeaderValue`.
+    pub fn cache_control<V>(mut self, value: V) -> Self
+    where
+        HeaderValue: TryFrom<V>,
+    {
+        self.file = self.file.cache_control(value);
+        self
+    }
+
+    /// Sets the `Cache-Control` header of files with the extension `ext`.
+    ///
+    /// Rules are checked in the order they were added, and the first match
+    /// wins.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the provided argument is not a valid `http::HeaderValue`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use starterm::fs::DirOptions;
+    ///
+    /// let options = DirOptions::new()
+    ///     .cache_control_for_path("assets", "public, max-age=31536000, immutable")
+    ///     .cache_control_for_extension("html", "no-cache")
+    ///     .cache_control("public, max-age=3600");
+    /// ```
+    pub fn cache_control_for_extension<V>(mut self, ext: &str, value: V) -> Self
+    where
+        HeaderValue: TryFrom<V>,
+    {
+        let ext = ext.trim_start_matches('.').to_owned();
+        self.file
+            .cache_control_rules
+            .push((CacheControlRule::Extension(ext), cache_control_value(value)));
+        self
+    }
+
+    /// Sets the `Cache-Control` header of files under `prefix`, relative to
+    /// the base directory.
+    ///
+    /// Rules are checked in the order they were added, and the first match
+    /// wins.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the provided argument is not a valid `http::HeaderValue`.
+    pub fn cache_control_for_path<V>(mut self, prefix: impl Into<PathBuf>, value: V) -> Self
+    where
+        HeaderValue: TryFrom<V>,
+    {
+        self.file.cache_control_rules.push((
+            CacheControlRule::Path(prefix.into()),
+            cache_control_value(value),
+        ));
+        self
+    }
+}
+
+/// How the `ETag` of a served file is computed.
+///
+/// The `ETag` is checked against `If-Match`, `If-None-Match` and `If-Range`
+/// request headers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ETagMode {
+    /// A strong `ETag` derived from the inode, size and modification time.
+    #[default]
+    Strong,
+    /// A weak `ETag` derived from the inode, size and modification time.
+    ///
+    /// Weak tags only ever match `If-None-Match`, so conditional range
+    /// requests and `If-Match` will never pass.
+    Weak,
+    /// A strong `ETag` from a SHA-1 hash of the contents.
+    ///
+    /// This reads the whole file on every request.
+    ContentHash,
+    /// No `ETag` is sent.
+    Disabled,
+}
+
+/// A precompressed variant of a file, stored next to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Sidecar {
+    Br,
+    Zstd,
+    Gzip,
+}
+
+impl Sidecar {
+    /// In order of preference, when a client weighs several equally.
+    const ALL: &'static [Sidecar] = &[Sidecar::Br, Sidecar::Zstd, Sidecar::Gzip];
+
+    fn content_encoding(self) -> &'static str {
+        match self {
+            Sidecar::Br => "br",
+            Sidecar::Zstd => "zstd",
+            Sidecar::Gzip => "gzip",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Sidecar::Br => "br",
+            Sidecar::Zstd => "zst",
+            Sidecar::Gzip => "gz",
+        }
+    }
+
+    fn path(self, path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".");
+        name.push(self.extension());
+        PathBuf::from(name)
+    }
 }
 
-fn path_from_tail(
-    base: Arc<PathBuf>,
-) -> impl FilterClone<Extract = One<ArcPath>, Error = Rejection> {
+// Extracts the sidecars worth trying for this request, best first.
+fn accepted_sidecars(
+    precompressed: bool,
+) -> impl Filter<Extract = One<Vec<Sidecar>>, Error = Infallible> + Clone {
+    crate::filter::filter_fn(move |route| {
+        let sidecars = if precompressed {
+            crate::filters::header::rank_accepted(
+                route.headers().get_all(ACCEPT_ENCODING),
+                Sidecar::ALL,
+                Sidecar::content_encoding,
+            )
+        } else {
+            Vec::new()
+        };
+        future::ok((sidecars,))
+    })
+}
+
+// What a request to a directory resolved to, as a path within its storage.
+enum Resolved {
+    File(PathBuf),
+    Listing(PathBuf),
+}
+
+fn path_from_tail<S: Storage>(
+    dir: Arc<ServeDir<S>>,
+) -> impl FilterClone<Extract = One<Resolved>, Error = Rejection> {
     crate::path::tail().and_then(move |tail: crate::path::Tail| {
-        future::ready(sanitize_path(base.as_ref(), tail.as_str())).and_then(|mut buf| async {
-            let is_dir = tokio::fs::metadata(buf.clone())
+        let dir = dir.clone();
+        future::ready(sanitize_path("", tail.as_str())).and_then(move |mut buf| async move {
+            if !dir.options.hidden_files && is_hidden(&buf) {
+                tracing::debug!("dir: rejecting hidden file {:?}", buf);
+                return Err(reject::not_found());
+            }
+
+            let is_dir = dir
+                .storage
+                .metadata(&buf)
                 .await
                 .map(|m| m.is_dir())
                 .unwrap_or(false);
 
             if is_dir {
-                tracing::debug!("dir: appending index.html to directory path");
-                buf.push("index.html");
+                let mut index = None;
+                for name in &dir.options.index_files {
+                    let is_file = dir
+                        .storage
+                        .metadata(&buf.join(name))
+                        .await
+                        .map(|m| !m.is_dir())
+                        .unwrap_or(false);
+                    if is_file {
+                        index = Some(name);
+                        break;
+                    }
+                }
+                if index.is_none() && dir.options.listing {
+                    tracing::trace!("dir: listing {:?}", buf);
+                    return Ok(Resolved::Listing(buf));
+                }
+                match index.or_else(|| dir.options.index_files.first()) {
+                    Some(name) => {
+                        tracing::debug!("dir: appending {} to directory path", name);
+                        buf.push(name);
+                    }
+                    None => return Err(reject::not_found()),
+                }
             }
+
             tracing::trace!("dir: {:?}", buf);
-            Ok(ArcPath(Arc::new(buf)))
+            Ok(Resolved::File(buf))
         })
     })
 }
 
-fn sanitize_path(base: impl AsRef<Path>, tail: &str) -> Result<PathBuf, Rejection> {
+// Whether any component of `path` is a dotfile.
+fn is_hidden(path: &Path) -> bool {
+    path.components()
+        .any(|c| c.as_os_str().to_str().is_some_and(|c| c.starts_with('.')))
+}
+
+// Whether `path`, with all symlinks resolved, is still inside `base`.
+//
+// A path that doesn't exist is considered inside, it will be a 404 anyway.
+async fn is_inside(base: &Path, path: &Path) -> bool {
+    let resolved = match tokio::fs::canonicalize(path).await {
+        Ok(resolved) => resolved,
+        Err(_) => return true,
+    };
+    match tokio::fs::canonicalize(base).await {
+        Ok(base) => resolved.starts_with(base),
+        Err(_) => false,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ListingFormat {
+    Html,
+    Json,
+}
+
+impl ListingFormat {
+    fn media_type(self) -> &'static str {
+        match self {
+            ListingFormat::Html => "text/html",
+            ListingFormat::Json => "application/json",
+        }
+    }
+}
+
+// The parts of a request that directory listings and the fallback need.
+struct DirRequest {
+    path_and_query: Option<http::uri::PathAndQuery>,
+    format: ListingFormat,
+    accepts_html: bool,
+}
+
+impl DirRequest {
+    fn path(&self) -> &str {
+        self.path_and_query
+            .as_ref()
+            .map(|pq| pq.path())
+            .unwrap_or("/")
+    }
+}
+
+fn dir_request() -> impl Filter<Extract = One<DirRequest>, Error = Infallible> + Copy {
+    crate::filter::filter_fn_one(|route| {
+        let accepted = crate::filters::header::rank_accepted(
+            route.headers().get_all(ACCEPT),
+            &[ListingFormat::Html, ListingFormat::Json],
+            ListingFormat::media_type,
+        );
+
+        future::ok(DirRequest {
+            path_and_query: route.uri().path_and_query().cloned(),
+            format: accepted.first().copied().unwrap_or(ListingFormat::Html),
+            accepts_html: accepted.contains(&ListingFormat::Html),
+        })
+    })
+}
+
+struct ListingEntry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+impl<S: Storage> ServeDir<S> {
+    async fn listing_reply(&self, dir: PathBuf, request: DirRequest) -> Result<File, Rejection> {
+        let display = ArcPath(Arc::new(self.base.join(&dir)));
+        let (path, query) = match request.path_and_query {
+            Some(ref pq) => (pq.path(), pq.query()),
+            None => ("/", None),
+        };
+
+        // Relative links in the listing only resolve against a trailing slash.
+        if !path.ends_with('/') {
+            let location = match query {
+                Some(query) => format!("{}/?{}", path, query),
+                None => format!("{}/", path),
+            };
+            let mut resp = Response::new(Body::empty());
+            *resp.status_mut() = StatusCode::MOVED_PERMANENTLY;
+            resp.headers_mut().insert(
+                LOCATION,
+                HeaderValue::from_str(&location).expect("request path is a valid HeaderValue"),
+            );
+            return Ok(File {
+                resp,
+                path: display,
+            });
+        }
+
+        let mut entries = match self.storage.read_dir(&dir).await {
+            Ok(entries) => entries
+                .into_iter()
+                .filter(|(name, _)| self.options.hidden_files || !name.starts_with('.'))
+                .map(|(name, meta)| ListingEntry {
+                    name,
+                    is_dir: meta.is_dir(),
+                    size: meta.size(),
+                    modified: meta.modified(),
+                })
+                .collect::<Vec<_>>(),
+            Err(err) => {
+                tracing::debug!("dir: listing error {:?}: {}", dir, err);
+                return Err(reject::not_found());
+            }
+        };
+        entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+
+        let title = percent_decode_str(path).decode_utf8_lossy();
+        let has_parent = dir.components().next().is_some();
+        let (body, content_type) = match request.format {
+            ListingFormat::Html => (
+                render_html_listing(&title, has_parent, &entries),
+                "text/html; charset=utf-8",
+            ),
+            ListingFormat::Json => (render_json_listing(&entries), "application/json"),
+        };
+
+        let mut resp = Response::new(Body::from(body));
+        resp.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        Ok(File {
+            resp,
+            path: display,
+        })
+    }
+}
+
+// Characters left as is in listing links, like `encodeURIComponent` does.
+const LINK_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~')
+    .remove(b'!')
+    .remove(b'*')
+    .remove(b'\'')
+    .remove(b'(')
+    .remove(b')');
+
+fn render_html_listing(title: &str, has_parent: bool, entries: &[ListingEntry]) -> String {
+    let title = html_escape(title);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Index of {0}</title>\n</head>\n<body>\n<h1>Index of {0}</h1>\n\
+         <table>\n<tr><th>Name</th><th>Size</th><th>Modified</th></tr>\n",
+        title
+    );
+    if has_parent {
+        html.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n");
+    }
+    for entry in entries {
+        let slash = if entry.is_dir { "/" } else { "" };
+        let size = if entry.is_dir {
+            String::new()
+        } else {
+            entry.size.to_string()
+        };
+        let modified = entry
+            .modified
+            .map(httpdate::fmt_http_date)
+            .unwrap_or_default();
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}{}\">{}{}</a></td><td>{}</td><td>{}</td></tr>\n",
+            utf8_percent_encode(&entry.name, LINK_ENCODE_SET),
+            slash,
+            html_escape(&entry.name),
+            slash,
+            size,
+            modified,
+        ));
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+fn render_json_listing(entries: &[ListingEntry]) -> String {
+    let entries = entries
+        .iter()
+        .map(|entry| {
+            let modified = entry
+                .modified
+                .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .map(|dur| dur.as_secs());
+            serde_json::json!({
+                "name": entry.name,
+                "type": if entry.is_dir { "dir" } else { "file" },
+                "size": entry.size,
+                "modified": modified,
+            })
+        })
+        .collect::<Vec<_>>();
+    serde_json::Value::Array(entries).to_string()
+}
+
+fn html_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn sanitize_path(base: impl AsRef<Path>, tail: &str) -> Result<PathBuf, Rejection> {
     let mut buf = PathBuf::from(base.as_ref());
     let p = match percent_decode_str(tail).decode_utf8() {
         Ok(p) => p,
@@ -118,154 +1180,782 @@ fn sanitize_path(base: impl AsRef<Path>, tail: &str) -> Result<PathBuf, Rejectio
     };
     tracing::trace!("dir? base={:?}, route={:?}", base.as_ref(), p);
     for seg in p.split('/') {
-        if seg.starts_with("..") {
-            tracing::warn!("dir: rejecting segment starting with '..'");
-            return Err(reject::not_found());
-        } else if seg.contains('\\') {
-            tracing::warn!("dir: rejecting segment containing backslash (\\)");
+        if let Some(reason) = unsafe_segment(seg) {
+            tracing::warn!("dir: rejecting segment {}", reason);
             return Err(reject::not_found());
-        } else if cfg!(windows) && seg.contains(':') {
-            tracing::warn!("dir: rejecting segment containing colon (:)");
-            return Err(reject::not_found());
-        } else {
-            buf.push(seg);
         }
+        buf.push(seg);
     }
     Ok(buf)
 }
 
-#[derive(Debug)]
-struct Conditionals {
-    if_modified_since: Option<IfModifiedSince>,
-    if_unmodified_since: Option<IfUnmodifiedSince>,
-    if_range: Option<IfRange>,
-    range: Option<Range>,
+// Why `seg` can't be used as a path segment, if it can't.
+pub(crate) fn unsafe_segment(seg: &str) -> Option<&'static str> {
+    if seg.starts_with("..") {
+        Some("starting with '..'")
+    } else if seg.contains('\\') {
+        Some("containing backslash (\\)")
+    } else if cfg!(windows) && seg.contains(':') {
+        Some("containing colon (:)")
+    } else {
+        None
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Conditionals {
+    method: Method,
+    if_match: Option<IfMatch>,
+    if_none_match: Option<IfNoneMatch>,
+    if_modified_since: Option<IfModifiedSince>,
+    if_unmodified_since: Option<IfUnmodifiedSince>,
+    if_range: Option<IfRange>,
+    range: Option<Range>,
+}
+
+enum Cond {
+    NoBody(Response),
+    WithBody(Option<Range>),
+}
+
+impl Conditionals {
+    // Evaluated in the order of RFC 9110, section 13.2.2: the entity tag
+    // checks take precedence over their date based counterparts.
+    fn check(self, last_modified: Option<LastModified>, etag: Option<&ETag>) -> Cond {
+        // `*` matches any current representation, whether or not it has an
+        // `ETag`; the file exists by the time this runs.
+        if let Some(if_match) = self.if_match {
+            let precondition = if_match == IfMatch::any()
+                || etag
+                    .map(|etag| if_match.precondition_passes(etag))
+                    .unwrap_or(false);
+
+            tracing::trace!("if-match? {:?} vs {:?} = {}", if_match, etag, precondition);
+            if !precondition {
+                let mut res = Response::new(Body::empty());
+                *res.status_mut() = StatusCode::PRECONDITION_FAILED;
+                return Cond::NoBody(res);
+            }
+        } else if let Some(since) = self.if_unmodified_since {
+            let precondition = last_modified
+                .map(|time| since.precondition_passes(time.into()))
+                .unwrap_or(false);
+
+            tracing::trace!(
+                "if-unmodified-since? {:?} vs {:?} = {}",
+                since,
+                last_modified,
+                precondition
+            );
+            if !precondition {
+                let mut res = Response::new(Body::empty());
+                *res.status_mut() = StatusCode::PRECONDITION_FAILED;
+                return Cond::NoBody(res);
+            }
+        }
+
+        if let Some(if_none_match) = self.if_none_match {
+            let precondition = if_none_match != IfNoneMatch::any()
+                && etag
+                    .map(|etag| if_none_match.precondition_passes(etag))
+                    .unwrap_or(true);
+
+            tracing::trace!(
+                "if-none-match? {:?} vs {:?} = {}",
+                if_none_match,
+                etag,
+                precondition
+            );
+            if !precondition {
+                let mut res = Response::new(Body::empty());
+                *res.status_mut() = if self.method == Method::GET || self.method == Method::HEAD {
+                    StatusCode::NOT_MODIFIED
+                } else {
+                    StatusCode::PRECONDITION_FAILED
+                };
+                return Cond::NoBody(res);
+            }
+        } else if let Some(since) = self.if_modified_since {
+            tracing::trace!(
+                "if-modified-since? header = {:?}, file = {:?}",
+                since,
+                last_modified
+            );
+            let unmodified = last_modified
+                .map(|time| !since.is_modified(time.into()))
+                // no last_modified means its always modified
+                .unwrap_or(false);
+            if unmodified {
+                let mut res = Response::new(Body::empty());
+                *res.status_mut() = StatusCode::NOT_MODIFIED;
+                return Cond::NoBody(res);
+            }
+        }
+
+        if let Some(if_range) = self.if_range {
+            tracing::trace!(
+                "if-range? {:?} vs {:?}, {:?}",
+                if_range,
+                etag,
+                last_modified
+            );
+            let can_range = !if_range.is_modified(etag, last_modified.as_ref());
+
+            if !can_range {
+                return Cond::WithBody(None);
+            }
+        }
+
+        Cond::WithBody(self.range)
+    }
+}
+
+fn conditionals() -> impl Filter<Extract = One<Conditionals>, Error = Infallible> + Copy {
+    crate::method()
+        .and(crate::header::optional2())
+        .and(crate::header::optional2())
+        .and(crate::header::optional2())
+        .and(crate::header::optional2())
+        .and(crate::header::optional2())
+        .and(crate::header::optional2())
+        .map(
+            |method,
+             if_match,
+             if_none_match,
+             if_modified_since,
+             if_unmodified_since,
+             if_range,
+             range| Conditionals {
+                method,
+                if_match,
+                if_none_match,
+                if_modified_since,
+                if_unmodified_since,
+                if_range,
+                range,
+            },
+        )
+}
+
+/// A file response.
+#[derive(Debug)]
+pub struct File {
+    resp: Response,
+    path: ArcPath,
+}
+
+impl File {
+    /// Extract the `&Path` of the file this `Response` delivers.
+    ///
+    /// # Example
+    ///
+    /// The example below changes the Content-Type response header for every file called `video.mp4`.
+    ///
+    /// ```
+    /// use starterm::{Filter, reply::Reply};
+    ///
+    /// let route = starterm::path("static")
+    ///     .and(starterm::fs::dir("/www/static"))
+    ///     .map(|reply: starterm::filters::fs::File| {
+    ///         if reply.path().ends_with("video.mp4") {
+    ///             starterm::reply::with_header(reply, "Content-Type", "video/mp4").into_response()
+    ///         } else {
+    ///             reply.into_response()
+    ///         }
+    ///     });
+    /// ```
+    pub fn path(&self) -> &Path {
+        self.path.as_ref()
+    }
+}
+
+// Silly wrapper since Arc<PathBuf> doesn't implement AsRef<Path> ;_;
+#[derive(Clone, Debug)]
+struct ArcPath(Arc<PathBuf>);
+
+impl AsRef<Path> for ArcPath {
+    fn as_ref(&self) -> &Path {
+        (*self.0).as_ref()
+    }
+}
+
+impl Reply for File {
+    fn into_response(self) -> Response {
+        self.resp
+    }
+}
+
+/// A backend that files are served from.
+///
+/// [LocalStorage] reads the local file system, and is what [file](fn@file) and [dir]
+/// use. Other implementations can be served with [dir_from], getting the
+/// same conditional request, byte range and MIME type handling.
+///
+/// Paths given to a storage are relative to its root. They are sanitized
+/// and never contain `..` components.
+pub trait Storage: Send + Sync + 'static {
+    /// The files this storage opens.
+    type File: StorageFile;
+
+    /// Returns the metadata of the file or directory at `path`.
+    fn metadata<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, io::Result<StorageMetadata>>;
+
+    /// Opens the file at `path`.
+    ///
+    /// An error of kind [io::ErrorKind::NotFound] rejects the request as not
+    /// found, and should also be returned for a directory.
+    fn open<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, io::Result<Self::File>>;
+
+    /// Returns the names and metadata of the entries of the directory at
+    /// `path`.
+    ///
+    /// This is only used for directory listings. By default listing isn't
+    /// supported, and a listed directory is not found.
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxFuture<'a, io::Result<Vec<(String, StorageMetadata)>>> {
+        let _ = path;
+        Box::pin(future::err(io::ErrorKind::Unsupported.into()))
+    }
+}
+
+/// A file opened by a [Storage].
+pub trait StorageFile: Send + Sync + 'static {
+    /// Returns the metadata of the file, as of when it was opened.
+    fn metadata(&self) -> &StorageMetadata;
+
+    /// Streams the bytes of the file within `range`.
+    ///
+    /// `buf_size` is the size set with `read_buffer_size`, if any. A file may
+    /// be read more than once, such as for several byte ranges.
+    fn read(
+        &self,
+        range: ops::Range<u64>,
+        buf_size: Option<usize>,
+    ) -> BoxStream<'static, io::Result<Bytes>>;
+}
+
+/// The metadata of a file or directory in a [Storage].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StorageMetadata {
+    size: u64,
+    is_dir: bool,
+    modified: Option<SystemTime>,
+    id: u64,
+}
+
+impl StorageMetadata {
+    /// Creates the metadata of a file of `size` bytes.
+    pub fn file(size: u64) -> Self {
+        StorageMetadata {
+            size,
+            is_dir: false,
+            modified: None,
+            id: 0,
+        }
+    }
+
+    /// Creates the metadata of a directory.
+    pub fn dir() -> Self {
+        StorageMetadata {
+            is_dir: true,
+            ..StorageMetadata::file(0)
+        }
+    }
+
+    /// Sets the time of the last modification, sent as `Last-Modified`.
+    pub fn with_modified(mut self, time: SystemTime) -> Self {
+        self.modified = Some(time);
+        self
+    }
+
+    /// Sets an identifier of the file, such as an inode number.
+    ///
+    /// Generated ETags combine it with the size and modification time.
+    pub fn with_id(mut self, id: u64) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Returns the size in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns whether this is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    /// Returns the time of the last modification, if known.
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+}
+
+impl From<&Metadata> for StorageMetadata {
+    fn from(meta: &Metadata) -> Self {
+        let mut storage = if meta.is_dir() {
+            StorageMetadata::dir()
+        } else {
+            StorageMetadata::file(meta.len())
+        };
+        if let Ok(time) = meta.modified() {
+            storage = storage.with_modified(time);
+        }
+        #[cfg(unix)]
+        {
+            storage = storage.with_id(std::os::unix::fs::MetadataExt::ino(meta));
+        }
+        storage
+    }
+}
+
+/// A [Storage] of the files under a directory of the local file system.
+///
+/// # Example
+///
+/// ```
+/// use starterm::fs::LocalStorage;
+///
+/// let storage = LocalStorage::new("/www/static").symlinks_outside_root(false);
+/// ```
+#[derive(Clone, Debug)]
+pub struct LocalStorage {
+    root: PathBuf,
+    symlinks_outside_root: bool,
+}
+
+impl LocalStorage {
+    /// Creates a storage of the files under `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocalStorage {
+            root: root.into(),
+            symlinks_outside_root: true,
+        }
+    }
+
+    /// Sets whether symlinks pointing outside of the root are followed.
+    ///
+    /// Defaults to `true`. When `false`, they are not found.
+    pub fn symlinks_outside_root(mut self, allow: bool) -> Self {
+        self.symlinks_outside_root = allow;
+        self
+    }
+
+    async fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        let path = self.root.join(path);
+        if !self.symlinks_outside_root && !is_inside(&self.root, &path).await {
+            tracing::warn!("fs: rejecting symlink outside of root {:?}", path);
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok(path)
+    }
+}
+
+impl Storage for LocalStorage {
+    type File = LocalFile;
+
+    fn metadata<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, io::Result<StorageMetadata>> {
+        Box::pin(async move {
+            let path = self.resolve(path).await?;
+            let meta = tokio::fs::metadata(path).await?;
+            Ok(StorageMetadata::from(&meta))
+        })
+    }
+
+    fn open<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, io::Result<LocalFile>> {
+        Box::pin(async move {
+            let path = self.resolve(path).await?;
+            let file = TkFile::open(path).await?;
+            let meta = file.metadata().await?;
+            if meta.is_dir() {
+                return Err(io::ErrorKind::NotFound.into());
+            }
+            Ok(LocalFile {
+                buf_size: optimal_buf_size(&meta),
+                meta: StorageMetadata::from(&meta),
+                file: file.into_std().await,
+            })
+        })
+    }
+
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxFuture<'a, io::Result<Vec<(String, StorageMetadata)>>> {
+        Box::pin(async move {
+            let mut entries = Vec::new();
+            let mut read_dir = tokio::fs::read_dir(self.resolve(path).await?).await?;
+            while let Some(entry) = read_dir.next_entry().await? {
+                // Names that aren't UTF-8 can't be requested by their listed name.
+                let name = match entry.file_name().into_string() {
+                    Ok(name) => name,
+                    Err(_) => continue,
+                };
+                // Follows symlinks, to list what would be served.
+                if let Ok(meta) = self.metadata(&path.join(&name)).await {
+                    entries.push((name, meta));
+                }
+            }
+            Ok(entries)
+        })
+    }
+}
+
+/// A file opened by [LocalStorage].
+#[derive(Debug)]
+pub struct LocalFile {
+    file: std::fs::File,
+    meta: StorageMetadata,
+    buf_size: usize,
+}
+
+impl StorageFile for LocalFile {
+    fn metadata(&self) -> &StorageMetadata {
+        &self.meta
+    }
+
+    fn read(
+        &self,
+        range: ops::Range<u64>,
+        buf_size: Option<usize>,
+    ) -> BoxStream<'static, io::Result<Bytes>> {
+        // The clones share a cursor, each read seeks to its own start.
+        match self.file.try_clone() {
+            Ok(file) => file_stream(
+                TkFile::from_std(file),
+                buf_size.unwrap_or(self.buf_size),
+                (range.start, range.end),
+            )
+            .boxed(),
+            Err(err) => stream::once(future::err(err)).boxed(),
+        }
+    }
+}
+
+/// An in-memory LRU cache of served files.
+///
+/// Cached files are served from memory instead of being opened and read
+/// again, with the same conditional request and byte range handling. An
+/// entry is revalidated against the size and modification time of the file,
+/// at most once per [ttl](FileCache::ttl). When the cache is over one of its
+/// limits, the least recently used files are evicted.
+///
+/// Clones share the same entries and counters, so a clone can be kept to
+/// read [hits](FileCache::hits) and [misses](FileCache::misses). A cache can
+/// be used by several [dir_with] or [dir_from_with] filters, their files are
+/// kept apart even when they have the same path.
+///
+/// # Example
+///
+/// ```
+/// use starterm::fs::{DirOptions, FileCache};
+///
+/// let cache = FileCache::new().max_bytes(16 * 1024 * 1024).max_entries(512);
+/// let route = starterm::fs::dir_with("/www/static", DirOptions::new().cache(cache.clone()));
+///
+/// // Later, for metrics:
+/// let (hits, misses) = (cache.hits(), cache.misses());
+/// ```
+#[derive(Clone)]
+pub struct FileCache {
+    max_bytes: u64,
+    max_entries: usize,
+    max_file_size: u64,
+    ttl: Duration,
+    shared: Arc<CacheShared>,
+}
+
+#[derive(Default)]
+struct CacheShared {
+    state: Mutex<CacheState>,
+    hits: AtomicU64,
+    misses: AtomicU64,
 }
 
-enum Cond {
-    NoBody(Response),
-    WithBody(Option<Range>),
+// The scope of the storage a file comes from, and the file's path. Files
+// served with `file_with` share the scope `0`.
+type CacheKey = (u64, PathBuf);
+
+fn next_cache_scope() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    NEXT.fetch_add(1, Ordering::Relaxed)
 }
 
-impl Conditionals {
-    fn check(self, last_modified: Option<LastModified>) -> Cond {
-        if let Some(since) = self.if_unmodified_since {
-            let precondition = last_modified
-                .map(|time| since.precondition_passes(time.into()))
-                .unwrap_or(false);
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, CacheEntry>,
+    bytes: u64,
+    // Incremented on every use, the entry with the lowest is evicted first.
+    clock: u64,
+}
 
-            tracing::trace!(
-                "if-unmodified-since? {:?} vs {:?} = {}",
-                since,
-                last_modified,
-                precondition
-            );
-            if !precondition {
-                let mut res = Response::new(Body::empty());
-                *res.status_mut() = StatusCode::PRECONDITION_FAILED;
-                return Cond::NoBody(res);
-            }
+struct CacheEntry {
+    contents: Bytes,
+    meta: StorageMetadata,
+    validated: Instant,
+    used: u64,
+}
+
+impl Default for FileCache {
+    fn default() -> Self {
+        FileCache {
+            max_bytes: 64 * 1024 * 1024,
+            max_entries: 1024,
+            max_file_size: 1024 * 1024,
+            ttl: Duration::from_secs(0),
+            shared: Arc::default(),
         }
+    }
+}
 
-        if let Some(since) = self.if_modified_since {
-            tracing::trace!(
-                "if-modified-since? header = {:?}, file = {:?}",
-                since,
-                last_modified
-            );
-            let unmodified = last_modified
-                .map(|time| !since.is_modified(time.into()))
-                // no last_modified means its always modified
-                .unwrap_or(false);
-            if unmodified {
-                let mut res = Response::new(Body::empty());
-                *res.status_mut() = StatusCode::NOT_MODIFIED;
-                return Cond::NoBody(res);
+impl fmt::Debug for FileCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileCache")
+            .field("max_bytes", &self.max_bytes)
+            .field("max_entries", &self.max_entries)
+            .field("max_file_size", &self.max_file_size)
+            .field("ttl", &self.ttl)
+            .field("entries", &self.entries())
+            .field("hits", &self.hits())
+            .field("misses", &self.misses())
+            .finish()
+    }
+}
+
+impl FileCache {
+    /// Creates an empty cache with the default limits.
+    pub fn new() -> Self {
+        FileCache::default()
+    }
+
+    /// Sets the total size of the cached files, in bytes.
+    ///
+    /// The default is 64 MiB.
+    pub fn max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = bytes;
+        self
+    }
+
+    /// Sets the number of cached files.
+    ///
+    /// The default is `1024`.
+    pub fn max_entries(mut self, entries: usize) -> Self {
+        self.max_entries = entries;
+        self
+    }
+
+    /// Sets the size of the largest file that is cached, in bytes.
+    ///
+    /// Larger files are always streamed from storage. The default is 1 MiB.
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = bytes;
+        self
+    }
+
+    /// Sets how long a cached file is served without checking it for
+    /// changes.
+    ///
+    /// After that, the file's metadata is read again, and the entry is
+    /// dropped if its size or modification time changed. The default is `0`,
+    /// files are checked on every request, which still saves opening and
+    /// reading them.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Returns how many files were served from the cache.
+    pub fn hits(&self) -> u64 {
+        self.shared.hits.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many files were served from storage, because they weren't
+    /// cached, had changed or were too large.
+    pub fn misses(&self) -> u64 {
+        self.shared.misses.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of cached files.
+    pub fn entries(&self) -> usize {
+        self.state().entries.len()
+    }
+
+    /// Returns the total size of the cached files, in bytes.
+    pub fn bytes(&self) -> u64 {
+        self.state().bytes
+    }
+
+    fn state(&self) -> MutexGuard<'_, CacheState> {
+        self.shared.state.lock().unwrap()
+    }
+
+    async fn open<S: Storage>(
+        &self,
+        storage: &S,
+        path: &Path,
+        key: &CacheKey,
+    ) -> io::Result<Opened<S::File>> {
+        let cached = self.state().get(key);
+        if let Some((contents, meta, validated)) = cached {
+            let fresh = validated.elapsed() < self.ttl;
+            if fresh || storage.metadata(path).await.ok().as_ref() == Some(&meta) {
+                if !fresh {
+                    self.state().revalidate(key);
+                }
+                self.shared.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(Opened::Cached(CachedFile { contents, meta }));
             }
+            tracing::trace!("file cache: {:?} changed", key.1);
+            self.state().remove(key);
         }
 
-        if let Some(if_range) = self.if_range {
-            tracing::trace!("if-range? {:?} vs {:?}", if_range, last_modified);
-            let can_range = !if_range.is_modified(None, last_modified.as_ref());
+        let file = storage.open(path).await?;
+        self.shared.misses.fetch_add(1, Ordering::Relaxed);
 
-            if !can_range {
-                return Cond::WithBody(None);
+        let meta = file.metadata().clone();
+        let size = meta.size();
+        if size > self.max_file_size || size > self.max_bytes {
+            return Ok(Opened::Storage(file));
+        }
+
+        let mut contents = BytesMut::with_capacity(size as usize);
+        let mut stream = file.read(0..size, None);
+        while let Some(chunk) = stream.next().await {
+            match chunk {
+                Ok(chunk) => contents.extend_from_slice(&chunk),
+                Err(err) => {
+                    tracing::debug!("file cache: read error {:?}: {}", key.1, err);
+                    return Ok(Opened::Storage(file));
+                }
             }
         }
+        // The file changed while it was read, don't keep a torn copy.
+        if contents.len() as u64 != size {
+            return Ok(Opened::Storage(file));
+        }
 
-        Cond::WithBody(self.range)
+        let contents = contents.freeze();
+        let mut state = self.state();
+        state.insert(key.clone(), contents.clone(), meta.clone());
+        state.evict(self.max_bytes, self.max_entries);
+        Ok(Opened::Cached(CachedFile { contents, meta }))
     }
 }
 
-fn conditionals() -> impl Filter<Extract = One<Conditionals>, Error = Infallible> + Copy {
-    crate::header::optional2()
-        .and(crate::header::optional2())
-        .and(crate::header::optional2())
-        .and(crate::header::optional2())
-        .map(
-            |if_modified_since, if_unmodified_since, if_range, range| Conditionals {
-                if_modified_since,
-                if_unmodified_since,
-                if_range,
-                range,
-            },
-        )
-}
+impl CacheState {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
 
-/// A file response.
-#[derive(Debug)]
-pub struct File {
-    resp: Response,
-    path: ArcPath,
-}
+    fn get(&mut self, key: &CacheKey) -> Option<(Bytes, StorageMetadata, Instant)> {
+        let used = self.tick();
+        let entry = self.entries.get_mut(key)?;
+        entry.used = used;
+        Some((entry.contents.clone(), entry.meta.clone(), entry.validated))
+    }
 
-impl File {
-    /// Extract the `&Path` of the file this `Response` delivers.
-    ///
-    /// # Example
-    ///
-    /// The example below changes the Content-Type response header for every file called `video.mp4`.
-    ///
-    /// ```
-    /// use starterm::{Filter, reply::Reply};
-    ///
-    /// let route = starterm::path("static")
-    ///     .and(starterm::fs::dir("/www/static"))
-    ///     .map(|reply: starterm::filters::fs::File| {
-    ///         if reply.path().ends_with("video.mp4") {
-    ///             starterm::reply::with_header(reply, "Content-Type", "video/mp4").into_response()
-    ///         } else {
-    ///             reply.into_response()
-    ///         }
-    ///     });
-    /// ```
-    pub fn path(&self) -> &Path {
-        self.path.as_ref()
+    fn revalidate(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.validated = Instant::now();
+        }
     }
-}
 
-// Silly wrapper since Arc<PathBuf> doesn't implement AsRef<Path> ;_;
-#[derive(Clone, Debug)]
-struct ArcPath(Arc<PathBuf>);
+    fn insert(&mut self, key: CacheKey, contents: Bytes, meta: StorageMetadata) {
+        let used = self.tick();
+        self.bytes += contents.len() as u64;
+        let entry = CacheEntry {
+            contents,
+            meta,
+            validated: Instant::now(),
+            used,
+        };
+        if let Some(old) = self.entries.insert(key, entry) {
+            self.bytes -= old.contents.len() as u64;
+        }
+    }
 
-impl AsRef<Path> for ArcPath {
-    fn as_ref(&self) -> &Path {
-        (*self.0).as_ref()
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(old) = self.entries.remove(key) {
+            self.bytes -= old.contents.len() as u64;
+        }
+    }
+
+    fn evict(&mut self, max_bytes: u64, max_entries: usize) {
+        while self.bytes > max_bytes || self.entries.len() > max_entries {
+            let lru = match self.entries.iter().min_by_key(|(_, entry)| entry.used) {
+                Some((key, _)) => key.clone(),
+                None => break,
+            };
+            tracing::trace!("file cache: evicting {:?}", lru.1);
+            self.remove(&lru);
+        }
     }
 }
 
-impl Reply for File {
-    fn into_response(self) -> Response {
-        self.resp
+// A file either from its storage, or from a `FileCache`.
+enum Opened<F> {
+    Storage(F),
+    Cached(CachedFile),
+}
+
+struct CachedFile {
+    contents: Bytes,
+    meta: StorageMetadata,
+}
+
+impl<F: StorageFile> StorageFile for Opened<F> {
+    fn metadata(&self) -> &StorageMetadata {
+        match self {
+            Opened::Storage(file) => file.metadata(),
+            Opened::Cached(file) => &file.meta,
+        }
+    }
+
+    fn read(
+        &self,
+        range: ops::Range<u64>,
+        buf_size: Option<usize>,
+    ) -> BoxStream<'static, io::Result<Bytes>> {
+        match self {
+            Opened::Storage(file) => file.read(range, buf_size),
+            Opened::Cached(file) => {
+                let chunk = file
+                    .contents
+                    .slice(range.start as usize..range.end as usize);
+                stream::once(future::ok(chunk)).boxed()
+            }
+        }
     }
 }
 
-fn file_reply(
+async fn file_reply<S: Storage>(
+    storage: &S,
+    storage_path: &Path,
     path: ArcPath,
     conditionals: Conditionals,
-) -> impl Future<Output = Result<File, Rejection>> + Send {
-    TkFile::open(path.clone()).then(move |res| match res {
-        Ok(f) => Either::Left(file_conditional(f, path, conditionals)),
+    sidecars: Vec<Sidecar>,
+    options: &FileOptions,
+) -> Result<File, Rejection> {
+    for sidecar in sidecars {
+        let sidecar_path = sidecar.path(storage_path);
+        let key = sidecar.path(path.as_ref());
+        match open_file(storage, &sidecar_path, &key, options).await {
+            Ok(f) => {
+                tracing::trace!("file: serving sidecar {:?}", sidecar_path);
+                return file_conditional(f, path, conditionals, Some(sidecar), options).await;
+            }
+            Err(err) => {
+                tracing::trace!("file: no sidecar {:?}: {}", sidecar_path, err);
+            }
+        }
+    }
+
+    match open_file(storage, storage_path, path.as_ref(), options).await {
+        Ok(f) => file_conditional(f, path, conditionals, None, options).await,
         Err(err) => {
             let rej = match err.kind() {
                 io::ErrorKind::NotFound => {
@@ -285,121 +1975,303 @@ fn file_reply(
                     reject::known(FileOpenError { _p: () })
                 }
             };
-            Either::Right(future::err(rej))
+            Err(rej)
         }
-    })
+    }
 }
 
-async fn file_metadata(f: TkFile) -> Result<(TkFile, Metadata), Rejection> {
-    match f.metadata().await {
-        Ok(meta) => Ok((f, meta)),
-        Err(err) => {
-            tracing::debug!("file metadata error: {}", err);
-            Err(reject::not_found())
+// Opens a file, through the cache if there is one. The cache is keyed by
+// `key`, the path of the served `File`, within the storage's scope.
+async fn open_file<S: Storage>(
+    storage: &S,
+    path: &Path,
+    key: &Path,
+    options: &FileOptions,
+) -> io::Result<Opened<S::File>> {
+    match options.cache {
+        Some(ref cache) => {
+            let key = (options.cache_scope, key.to_owned());
+            cache.open(storage, path, &key).await
         }
+        None => storage.open(path).await.map(Opened::Storage),
     }
 }
 
-fn file_conditional(
-    f: TkFile,
+async fn file_conditional<F: StorageFile>(
+    file: F,
     path: ArcPath,
     conditionals: Conditionals,
-) -> impl Future<Output = Result<File, Rejection>> + Send {
-    file_metadata(f).map_ok(move |(file, meta)| {
-        let mut len = meta.len();
-        let modified = meta.modified().ok().map(LastModified::from);
-
-        let resp = match conditionals.check(modified) {
-            Cond::NoBody(resp) => resp,
-            Cond::WithBody(range) => {
-                bytes_range(range, len)
-                    .map(|(start, end)| {
-                        let sub_len = end - start;
-                        let buf_size = optimal_buf_size(&meta);
-                        let stream = file_stream(file, buf_size, (start, end));
-                        let body = Body::wrap_stream(stream);
-
-                        let mut resp = Response::new(body);
-
-                        if sub_len != len {
-                            *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
-                            resp.headers_mut().typed_insert(
-                                ContentRange::bytes(start..end, len).expect("valid ContentRange"),
-                            );
-
-                            len = sub_len;
-                        }
-
-                        let mime = mime_guess::from_path(path.as_ref()).first_or_octet_stream();
-
-                        resp.headers_mut().typed_insert(ContentLength(len));
-                        resp.headers_mut().typed_insert(ContentType::from(mime));
-                        resp.headers_mut().typed_insert(AcceptRanges::bytes());
-
-                        if let Some(last_modified) = modified {
-                            resp.headers_mut().typed_insert(last_modified);
-                        }
-
-                        resp
-                    })
-                    .unwrap_or_else(|BadRange| {
-                        // bad byte range
-                        let mut resp = Response::new(Body::empty());
-                        *resp.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
-                        resp.headers_mut()
-                            .typed_insert(ContentRange::unsatisfied_bytes(len));
-                        resp
+    sidecar: Option<Sidecar>,
+    options: &FileOptions,
+) -> Result<File, Rejection> {
+    let meta = file.metadata();
+    let len = meta.size();
+    let modified = meta.modified().map(LastModified::from);
+    let etag = match options.etag {
+        ETagMode::Disabled => None,
+        ETagMode::Strong => Some(metadata_etag(meta, false)),
+        ETagMode::Weak => Some(metadata_etag(meta, true)),
+        ETagMode::ContentHash => Some(content_etag(&file, options.read_buffer_size).await?),
+    };
+
+    let mut resp = match conditionals.check(modified, etag.as_ref()) {
+        Cond::NoBody(resp) => resp,
+        Cond::WithBody(range) => match bytes_ranges(range, len) {
+            Ok(ranges) => {
+                let mime = mime_guess::from_path(path.as_ref()).first_or_octet_stream();
+
+                // The parts of a multipart body may share a cursor, so they
+                // are read one after the other instead of ahead.
+                let chunks = if ranges.len() == 1 {
+                    options.read_ahead
+                } else {
+                    0
+                };
+                let parts = ranges
+                    .iter()
+                    .map(|&(start, end)| {
+                        read_ahead(file.read(start..end, options.read_buffer_size), chunks)
                     })
+                    .collect();
+                let mut resp = ranges_response(&ranges, len, &mime, parts);
+
+                resp.headers_mut().typed_insert(AcceptRanges::bytes());
+
+                if let Some(last_modified) = modified {
+                    resp.headers_mut().typed_insert(last_modified);
+                }
+
+                if let Some(sidecar) = sidecar {
+                    resp.headers_mut().insert(
+                        CONTENT_ENCODING,
+                        HeaderValue::from_static(sidecar.content_encoding()),
+                    );
+                }
+
+                resp
             }
-        };
+            Err(BadRange) => unsatisfiable_range(len),
+        },
+    };
 
-        File { resp, path }
-    })
+    if resp.status().is_success() || resp.status() == StatusCode::NOT_MODIFIED {
+        if let Some(value) = options.cache_control_for(path.as_ref()) {
+            resp.headers_mut().insert(CACHE_CONTROL, value.clone());
+        }
+    }
+
+    // A `304 Not Modified` has to carry the validator a `200` would have.
+    if let Some(etag) = etag {
+        if resp.status() != StatusCode::PRECONDITION_FAILED {
+            resp.headers_mut().typed_insert(etag);
+        }
+    }
+
+    if options.precompressed {
+        resp.headers_mut()
+            .insert(VARY, HeaderValue::from_static("accept-encoding"));
+    }
+
+    Ok(File { resp, path })
+}
+
+fn metadata_etag(meta: &StorageMetadata, weak: bool) -> ETag {
+    let mtime = meta
+        .modified()
+        .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|dur| dur.as_nanos())
+        .unwrap_or(0);
+
+    let prefix = if weak { "W/" } else { "" };
+    format!("{}\"{:x}-{:x}-{:x}\"", prefix, meta.id, meta.size(), mtime)
+        .parse()
+        .expect("valid ETag")
+}
+
+async fn content_etag<F: StorageFile>(
+    file: &F,
+    buf_size: Option<usize>,
+) -> Result<ETag, Rejection> {
+    let mut hasher = Sha1::new();
+    let mut stream = file.read(0..file.metadata().size(), buf_size);
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            Ok(chunk) => hasher.update(&chunk),
+            Err(err) => {
+                tracing::error!("file read error while hashing: {}", err);
+                return Err(reject::known(FileOpenError { _p: () }));
+            }
+        }
+    }
+
+    Ok(hash_etag(hasher))
+}
+
+fn hash_etag(hasher: Sha1) -> ETag {
+    let tag = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    format!("\"{}\"", tag).parse().expect("valid ETag")
 }
 
 struct BadRange;
 
-fn bytes_range(range: Option<Range>, max_len: u64) -> Result<(u64, u64), BadRange> {
+// More ranges than this are answered with the whole file instead, so
+// that a request can't make us build a body of many tiny slices.
+const MAX_RANGES: usize = 16;
+
+fn bytes_ranges(range: Option<Range>, max_len: u64) -> Result<Vec<(u64, u64)>, BadRange> {
     use std::ops::Bound;
 
     let range = if let Some(range) = range {
         range
     } else {
-        return Ok((0, max_len));
+        return Ok(vec![(0, max_len)]);
     };
 
-    let ret = range
-        .iter()
-        .map(|(start, end)| {
-            let start = match start {
-                Bound::Unbounded => 0,
-                Bound::Included(s) => s,
-                Bound::Excluded(s) => s + 1,
-            };
+    let mut requested = 0;
+    let mut ranges = Vec::new();
+    for (start, end) in range.iter() {
+        requested += 1;
 
-            let end = match end {
-                Bound::Unbounded => max_len,
-                Bound::Included(s) => {
-                    // For the special case where s == the file size
-                    if s == max_len {
-                        s
-                    } else {
-                        s + 1
-                    }
-                }
-                Bound::Excluded(s) => s,
-            };
+        let start = match start {
+            Bound::Unbounded => 0,
+            Bound::Included(s) => s,
+            Bound::Excluded(s) => s + 1,
+        };
 
-            if start < end && end <= max_len {
-                Ok((start, end))
-            } else {
-                tracing::trace!("unsatisfiable byte range: {}-{}/{}", start, end, max_len);
-                Err(BadRange)
+        let end = match end {
+            Bound::Unbounded => max_len,
+            Bound::Included(s) => {
+                // For the special case where s == the file size
+                if s == max_len {
+                    s
+                } else {
+                    s + 1
+                }
             }
-        })
-        .next()
-        .unwrap_or(Ok((0, max_len)));
-    ret
+            Bound::Excluded(s) => s,
+        };
+
+        if start < end && end <= max_len {
+            ranges.push((start, end));
+        } else {
+            tracing::trace!("unsatisfiable byte range: {}-{}/{}", start, end, max_len);
+        }
+    }
+
+    if requested == 0 {
+        return Ok(vec![(0, max_len)]);
+    }
+    if ranges.is_empty() {
+        return Err(BadRange);
+    }
+
+    // Coalesce overlapping and adjacent ranges.
+    ranges.sort_unstable();
+    let mut coalesced: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match coalesced.last_mut() {
+            Some(last) if start <= last.1 => last.1 = cmp::max(last.1, end),
+            _ => coalesced.push((start, end)),
+        }
+    }
+
+    if coalesced.len() > MAX_RANGES {
+        tracing::debug!(
+            "too many byte ranges ({}), serving the whole file",
+            coalesced.len()
+        );
+        return Ok(vec![(0, max_len)]);
+    }
+
+    Ok(coalesced)
+}
+
+// Builds the response for `ranges` of a body that is `len` bytes long, where
+// `parts` stream the bytes of each range. More than one range makes a
+// `multipart/byteranges` body, with one part per range.
+fn ranges_response<S>(ranges: &[(u64, u64)], len: u64, mime: &mime::Mime, parts: Vec<S>) -> Response
+where
+    S: Stream<Item = Result<Bytes, io::Error>> + Send + 'static,
+{
+    debug_assert_eq!(ranges.len(), parts.len());
+
+    if let [(start, end)] = ranges[..] {
+        let sub_len = end - start;
+        let body = stream::iter(parts).flatten();
+        let mut resp = Response::new(Body::wrap_stream(body));
+
+        if sub_len != len {
+            *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
+            resp.headers_mut()
+                .typed_insert(ContentRange::bytes(start..end, len).expect("valid ContentRange"));
+        }
+
+        resp.headers_mut().typed_insert(ContentLength(sub_len));
+        resp.headers_mut()
+            .typed_insert(ContentType::from(mime.clone()));
+        return resp;
+    }
+
+    let boundary = byteranges_boundary();
+
+    let mut content_length = 0;
+    let mut body = Vec::with_capacity(parts.len());
+    for (i, (&(start, end), part)) in ranges.iter().zip(parts).enumerate() {
+        let head = format!(
+            "{}--{}\r\ncontent-type: {}\r\ncontent-range: bytes {}-{}/{}\r\n\r\n",
+            if i == 0 { "" } else { "\r\n" },
+            boundary,
+            mime,
+            start,
+            end - 1,
+            len
+        );
+        content_length += head.len() as u64 + (end - start);
+        body.push(stream::once(future::ok(Bytes::from(head))).chain(part));
+    }
+
+    let tail = format!("\r\n--{}--\r\n", boundary);
+    content_length += tail.len() as u64;
+    let body = stream::iter(body)
+        .flatten()
+        .chain(stream::once(future::ok(Bytes::from(tail))));
+
+    let mut resp = Response::new(Body::wrap_stream(body));
+    *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
+    resp.headers_mut()
+        .typed_insert(ContentLength(content_length));
+    resp.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_str(&format!("multipart/byteranges; boundary={}", boundary))
+            .expect("valid boundary"),
+    );
+    resp
+}
+
+fn unsatisfiable_range(len: u64) -> Response {
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+    resp.headers_mut()
+        .typed_insert(ContentRange::unsatisfied_bytes(len));
+    resp
+}
+
+fn byteranges_boundary() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|dur| dur.subsec_nanos())
+        .unwrap_or(0);
+    format!(
+        "{:08x}{:016x}",
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
 }
 
 fn file_stream(
@@ -410,9 +2282,7 @@ fn file_stream(
     use std::io::SeekFrom;
 
     let seek = async move {
-        if start != 0 {
-            file.seek(SeekFrom::Start(start)).await?;
-        }
+        file.seek(SeekFrom::Start(start)).await?;
         Ok(file)
     };
 
@@ -458,6 +2328,31 @@ fn file_stream(
         .flatten()
 }
 
+// Polls `stream` on its own task, buffering up to `chunks` items ahead of
+// the consumer.
+fn read_ahead<S>(stream: S, chunks: usize) -> impl Stream<Item = S::Item> + Send
+where
+    S: Stream + Send + 'static,
+    S::Item: Send + 'static,
+{
+    if chunks == 0 {
+        return Either::Left(stream);
+    }
+
+    // The channel holds `buffer + 1` items, one more for the single sender.
+    let (mut tx, rx) = futures_channel::mpsc::channel(chunks - 1);
+    tokio::spawn(async move {
+        futures_util::pin_mut!(stream);
+        while let Some(item) = stream.next().await {
+            if tx.send(item).await.is_err() {
+                tracing::trace!("file read ahead: body dropped");
+                break;
+            }
+        }
+    });
+    Either::Right(rx)
+}
+
 fn reserve_at_least(buf: &mut BytesMut, cap: usize) {
     if buf.capacity() - buf.len() < cap {
         buf.reserve(cap);
@@ -535,4 +2430,36 @@ mod tests {
         assert_eq!(buf.len(), 0);
         assert_eq!(buf.capacity(), cap);
     }
+
+    #[test]
+    fn test_bytes_ranges() {
+        fn ranges(header: &str) -> Result<Vec<(u64, u64)>, super::BadRange> {
+            let value = http::HeaderValue::from_str(header).unwrap();
+            let range = headers::Header::decode(&mut std::iter::once(&value)).unwrap();
+            super::bytes_ranges(Some(range), 1000)
+        }
+
+        assert_eq!(ranges("bytes=0-9").ok(), Some(vec![(0, 10)]));
+        assert_eq!(
+            ranges("bytes=50-59, 0-9").ok(),
+            Some(vec![(0, 10), (50, 60)])
+        );
+
+        // overlapping and adjacent ranges are coalesced
+        assert_eq!(ranges("bytes=0-9, 5-19, 20-29").ok(), Some(vec![(0, 30)]));
+
+        // unsatisfiable ranges are dropped, unless none is left
+        assert_eq!(ranges("bytes=0-9, 2000-").ok(), Some(vec![(0, 10)]));
+        assert!(ranges("bytes=2000-").is_err());
+
+        // too many ranges are served as the whole file
+        let many = (0..20)
+            .map(|i| format!("{}-{}", i * 10, i * 10 + 1))
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(
+            ranges(&format!("bytes={}", many)).ok(),
+            Some(vec![(0, 1000)])
+        );
+    }
 }
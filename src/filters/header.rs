@@ -104,6 +104,104 @@ where
     filter_fn_one(move |route| future::ready(Ok(route.headers().typed_get())))
 }
 
+/// Ranks the `supported` values by the quality the header values of an
+/// `Accept`-like request header, such as `Accept-Encoding`, give them.
+///
+/// For media types, as in `Accept`, the `*/*` and `type/*` ranges match too,
+/// and the quality of a value is that of the most specific match. Values with
+/// a quality of `0`, or not accepted at all, are left out. Values of equal
+/// quality keep their order in `supported`.
+pub(crate) fn rank_accepted<'a, I, T>(
+    accept: I,
+    supported: &[T],
+    name_of: impl Fn(T) -> &'static str,
+) -> Vec<T>
+where
+    I: IntoIterator<Item = &'a HeaderValue>,
+    T: Copy,
+{
+    // The quality of each value, with the specificity of the match it's from.
+    let mut qualities: Vec<Option<(u8, u16)>> = vec![None; supported.len()];
+
+    let values = accept.into_iter().filter_map(|value| value.to_str().ok());
+    for item in values.flat_map(|value| value.split(',')) {
+        let mut params = item.split(';');
+        let name = params.next().unwrap_or_default().trim();
+        if name.is_empty() {
+            continue;
+        }
+        let quality = match parse_quality(params) {
+            Some(q) => q,
+            None => continue,
+        };
+
+        let specificity = if name == "*" || name == "*/*" {
+            0
+        } else if name.ends_with("/*") {
+            1
+        } else {
+            2
+        };
+        for (idx, &t) in supported.iter().enumerate() {
+            let supported_name = name_of(t);
+            let matches = match specificity {
+                0 => true,
+                // `type/*` matches `type/subtype`, the `/` included.
+                1 => supported_name
+                    .get(..name.len() - 1)
+                    .is_some_and(|prefix| prefix.eq_ignore_ascii_case(&name[..name.len() - 1])),
+                _ => name.eq_ignore_ascii_case(supported_name),
+            };
+            if matches && qualities[idx].is_none_or(|(s, _)| s <= specificity) {
+                qualities[idx] = Some((specificity, quality));
+            }
+        }
+    }
+
+    let mut ranked = supported
+        .iter()
+        .zip(qualities)
+        .filter_map(|(&t, quality)| match quality {
+            Some((_, q)) if q > 0 => Some((t, q)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    // A stable sort, so ties keep their order of preference.
+    ranked.sort_by(|(_, a), (_, b)| b.cmp(a));
+    ranked.into_iter().map(|(t, _)| t).collect()
+}
+
+/// Parses the `q` parameter of an `Accept-*` item into thousandths.
+///
+/// A missing `q` means `1`, a malformed one discards the whole item.
+fn parse_quality<'a>(params: impl Iterator<Item = &'a str>) -> Option<u16> {
+    for param in params {
+        let mut pair = param.splitn(2, '=');
+        let name = pair.next().unwrap_or_default().trim();
+        if !name.eq_ignore_ascii_case("q") {
+            continue;
+        }
+        let value = pair.next()?.trim();
+        let mut parts = value.splitn(2, '.');
+        let int = parts.next()?;
+        let frac = parts.next().unwrap_or_default();
+        if frac.len() > 3 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let thousandths = frac
+            .bytes()
+            .chain(std::iter::repeat(b'0'))
+            .take(3)
+            .fold(0u16, |acc, b| acc * 10 + u16::from(b - b'0'));
+        return match int {
+            "0" => Some(thousandths),
+            "1" if thousandths == 0 => Some(1000),
+            _ => None,
+        };
+    }
+    Some(1000)
+}
+
 /* TODO
 pub fn exact2<T>(header: T) -> impl FilterClone<Extract=(), Error=Rejection>
 where
@@ -1,12 +1,13 @@
 //! Logger Filters
 
 use std::fmt;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::time::{Duration, Instant};
 
 use http::{header, StatusCode};
 
 use crate::filter::{Filter, WrapSealed};
+use crate::filters::addr::TrustedProxies;
 use crate::reject::IsReject;
 use crate::reply::Reply;
 use crate::route::Route;
@@ -114,6 +115,35 @@ impl Info<'_> {
         self.route.remote_addr()
     }
 
+    /// View the IP address of the client, as reported by the `trusted`
+    /// proxies.
+    ///
+    /// See [`addr::client_ip`](crate::addr::client_ip).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use starterm::Filter;
+    /// use starterm::addr::TrustedProxies;
+    ///
+    /// let trusted = TrustedProxies::new(vec!["10.0.0.0/8".parse().unwrap()]);
+    /// let log = starterm::log::custom(move |info| {
+    ///     eprintln!(
+    ///         "{:?} {} {} {}",
+    ///         info.client_ip(&trusted),
+    ///         info.method(),
+    ///         info.path(),
+    ///         info.status(),
+    ///     );
+    /// });
+    /// let route = starterm::any()
+    ///     .map(starterm::reply)
+    ///     .with(log);
+    /// ```
+    pub fn client_ip(&self, trusted: &TrustedProxies) -> Option<IpAddr> {
+        trusted.client_ip(self.route.remote_addr(), self.route.headers())
+    }
+
     /// View the `http::Method` of the request.
     pub fn method(&self) -> &http::Method {
         self.route.method()
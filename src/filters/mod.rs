@@ -6,8 +6,13 @@
 pub mod addr;
 pub mod any;
 pub mod body;
-#[cfg(any(feature = "compression-brotli", feature = "compression-gzip"))]
+#[cfg(any(
+    feature = "compression-brotli",
+    feature = "compression-gzip",
+    feature = "compression-zstd"
+))]
 pub mod compression;
+pub mod conn;
 pub mod cookie;
 pub mod cors;
 pub mod ext;
@@ -5,16 +5,25 @@
 use std::error::Error as StdError;
 use std::fmt::{Display, Formatter};
 use std::future::Future;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::SystemTime;
 use std::{fmt, io};
 
 use bytes::{Buf, Bytes};
-use futures_util::{future, Stream};
+use futures_util::{future, Stream, StreamExt, TryFutureExt};
 use headers::ContentType;
 use hyper::Body;
 use mime::Mime;
-use multer::{Field as PartInner, Multipart as FormDataInner};
+use multer::{Constraints, Field as PartInner, Multipart as FormDataInner, SizeLimit};
+use serde::de::DeserializeOwned;
+use sha1::{Digest, Sha1};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
 
 use crate::filter::{Filter, FilterBase, Internal};
 use crate::reject::{self, Rejection};
@@ -22,12 +31,27 @@ use crate::reject::{self, Rejection};
 // If not otherwise configured, default to 2MB.
 const DEFAULT_FORM_DATA_MAX_LENGTH: u64 = 1024 * 1024 * 2;
 
+// Defaults of `typed()`, for a text field and for a file kept in memory.
+const DEFAULT_FIELD_MAX_LENGTH: u64 = 64 * 1024;
+const DEFAULT_SPILL_THRESHOLD: u64 = 256 * 1024;
+
 /// A [`Filter`](crate::Filter) to extract a `multipart/form-data` body from a request.
 ///
 /// Create with the `starterm::multipart::form()` function.
 #[derive(Debug, Clone)]
 pub struct FormOptions {
     max_length: Option<u64>,
+    limits: Arc<FormLimits>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct FormLimits {
+    max_field_length: Option<u64>,
+    allowed_fields: Option<Vec<String>>,
+    // Checked by `FormData` itself, multer doesn't support them.
+    max_parts: Option<usize>,
+    allowed_content_types: Option<Vec<String>>,
+    max_headers_length: Option<usize>,
 }
 
 /// A `Stream` of multipart/form-data `Part`s.
@@ -35,6 +59,8 @@ pub struct FormOptions {
 /// Extracted with a `starterm::multipart::form` filter.
 pub struct FormData {
     inner: FormDataInner<'static>,
+    limits: Arc<FormLimits>,
+    parts: usize,
 }
 
 /// A single "part" of a multipart/form-data body.
@@ -44,6 +70,67 @@ pub struct Part {
     part: PartInner<'static>,
 }
 
+/// Options for saving a `Part` to a file, with [`Part::save_to_with`] or
+/// [`Part::save_to_temp_with`].
+#[derive(Debug, Clone, Default)]
+pub struct SaveOptions {
+    max_size: Option<u64>,
+    fsync: bool,
+    sha1: bool,
+}
+
+/// A `Part` saved to a file.
+#[derive(Debug, Clone)]
+pub struct SavedPart {
+    path: PathBuf,
+    size: u64,
+    sha1: Option<String>,
+}
+
+/// A [`Filter`](crate::Filter) to extract a `multipart/form-data` body into a
+/// [`TypedForm`].
+///
+/// Create with the `starterm::multipart::typed()` function.
+pub struct TypedFormOptions<T> {
+    form: FormOptions,
+    limits: TypedLimits,
+    _marker: PhantomData<fn() -> T>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TypedLimits {
+    max_field_length: u64,
+    max_file_length: Option<u64>,
+    spill_threshold: u64,
+}
+
+/// A `multipart/form-data` body, with its text fields deserialized into a `T`.
+///
+/// Extracted with a `starterm::multipart::typed` filter.
+pub struct TypedForm<T> {
+    fields: T,
+    files: Vec<FormFile>,
+}
+
+/// A file part of a [`TypedForm`], that is a part with a filename.
+///
+/// Small files are kept in memory, larger ones are written to a temporary
+/// file, only readable and writable by the current user on Unix. The
+/// temporary file is removed in the background when the `FormFile` is
+/// dropped, unless it was persisted with [`FormFile::persist_to`].
+pub struct FormFile {
+    name: String,
+    filename: Option<String>,
+    content_type: Option<String>,
+    size: u64,
+    contents: FileContents,
+}
+
+enum FileContents {
+    Memory(Bytes),
+    Temp(RemoveOnDrop),
+}
+
 /// Create a [`Filter`](crate::Filter) to extract a `multipart/form-data` body from a request.
 ///
 /// The extracted `FormData` type is a `Stream` of `Part`s, and each `Part`
@@ -51,6 +138,46 @@ pub struct Part {
 pub fn form() -> FormOptions {
     FormOptions {
         max_length: Some(DEFAULT_FORM_DATA_MAX_LENGTH),
+        limits: Arc::default(),
+    }
+}
+
+/// Create a [`Filter`](crate::Filter) to extract a `multipart/form-data` body
+/// into a [`TypedForm`].
+///
+/// The text parts are deserialized into a `T`, like a
+/// `application/x-www-form-urlencoded` body is by
+/// [`body::form`](crate::body::form). The file parts, those with a filename,
+/// are collected as [`FormFile`]s. A missing, duplicated or invalid field
+/// rejects the request with `400 Bad Request`, and a part over a limit with
+/// a [`MultipartLimitError`].
+///
+/// # Example
+///
+/// ```
+/// use serde_derive::Deserialize;
+/// use starterm::Filter;
+/// use starterm::multipart::TypedForm;
+///
+/// #[derive(Deserialize)]
+/// struct Upload {
+///     title: String,
+/// }
+///
+/// let route = starterm::multipart::typed::<Upload>()
+///     .map(|form: TypedForm<Upload>| {
+///         format!("{}: {} files", form.fields().title, form.files().len())
+///     });
+/// ```
+pub fn typed<T: DeserializeOwned + Send + 'static>() -> TypedFormOptions<T> {
+    TypedFormOptions {
+        form: form(),
+        limits: TypedLimits {
+            max_field_length: DEFAULT_FIELD_MAX_LENGTH,
+            max_file_length: None,
+            spill_threshold: DEFAULT_SPILL_THRESHOLD,
+        },
+        _marker: PhantomData,
     }
 }
 
@@ -65,6 +192,118 @@ impl FormOptions {
         self.max_length = max.into();
         self
     }
+
+    /// Set the maximum byte length of the data of each part.
+    ///
+    /// `max_field_length(None)` means that only the length of the whole body
+    /// is checked. Defaults to `None`.
+    pub fn max_field_length(mut self, max: impl Into<Option<u64>>) -> Self {
+        Arc::make_mut(&mut self.limits).max_field_length = max.into();
+        self
+    }
+
+    /// Set the maximum number of parts in this body.
+    ///
+    /// `max_parts(None)` means that the number of parts is not checked.
+    /// Defaults to `None`.
+    pub fn max_parts(mut self, max: impl Into<Option<usize>>) -> Self {
+        Arc::make_mut(&mut self.limits).max_parts = max.into();
+        self
+    }
+
+    /// Set the names of the parts allowed in this body.
+    ///
+    /// By default, any name is allowed.
+    pub fn allowed_fields<I>(mut self, names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Arc::make_mut(&mut self.limits).allowed_fields =
+            Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the content-types of the parts allowed in this body.
+    ///
+    /// A type can end with a `*` subtype, such as `image/*`. A part without
+    /// a content-type is `text/plain`. By default, any content-type is
+    /// allowed.
+    pub fn allowed_content_types<I>(mut self, types: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let types = types
+            .into_iter()
+            .map(|ty| ty.into().to_ascii_lowercase())
+            .collect();
+        Arc::make_mut(&mut self.limits).allowed_content_types = Some(types);
+        self
+    }
+
+    /// Set the maximum byte length of the headers of each part.
+    ///
+    /// The headers are checked once a part is parsed, so a part over the
+    /// limit is rejected with `413 Payload Too Large`, but it is still
+    /// buffered: what is buffered is only bounded by
+    /// [`max_length`](FormOptions::max_length). `max_part_headers_length(None)`
+    /// means that the length of the headers is not checked. Defaults to
+    /// `None`.
+    pub fn max_part_headers_length(mut self, max: impl Into<Option<usize>>) -> Self {
+        Arc::make_mut(&mut self.limits).max_headers_length = max.into();
+        self
+    }
+}
+
+// ===== impl FormLimits =====
+
+impl FormLimits {
+    fn constraints(&self) -> Constraints {
+        let mut constraints = Constraints::new();
+        if let Some(max) = self.max_field_length {
+            constraints = constraints.size_limit(SizeLimit::new().per_field(max));
+        }
+        if let Some(ref names) = self.allowed_fields {
+            constraints = constraints.allowed_fields(names.clone());
+        }
+        constraints
+    }
+
+    // Checks the limits multer doesn't, for the `count`th part.
+    fn check(&self, part: &PartInner<'static>, count: usize) -> Result<(), MultipartLimitError> {
+        if self.max_parts.is_some_and(|max| count > max) {
+            return Err(MultipartLimitError::new(LimitKind::Parts));
+        }
+
+        if let Some(max) = self.max_headers_length {
+            // Each header is `name: value\r\n`.
+            let length = part
+                .headers()
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value.len() + 4)
+                .sum::<usize>();
+            if length > max {
+                return Err(MultipartLimitError::new(LimitKind::Headers));
+            }
+        }
+
+        if let Some(ref allowed) = self.allowed_content_types {
+            let essence = part
+                .content_type()
+                .map(|mime| mime.essence_str().to_ascii_lowercase())
+                .unwrap_or_else(|| "text/plain".to_owned());
+            let is_allowed = allowed.iter().any(|ty| match ty.strip_suffix("/*") {
+                Some(prefix) => prefix == "*" || essence.split('/').next() == Some(prefix),
+                None => *ty == essence,
+            });
+            if !is_allowed {
+                return Err(MultipartLimitError::new(LimitKind::ContentType));
+            }
+        }
+
+        Ok(())
+    }
 }
 
 type FormFut = Pin<Box<dyn Future<Output = Result<(FormData,), Rejection>> + Send>>;
@@ -84,12 +323,15 @@ impl FilterBase for FormOptions {
             future::ready(mime)
         });
 
+        let limits = self.limits.clone();
         let filt = boundary
             .and(super::body::body())
-            .map(|boundary: String, body| {
+            .map(move |boundary: String, body| {
                 let body = BodyIoError(body);
                 FormData {
-                    inner: FormDataInner::new(body, &boundary),
+                    inner: FormDataInner::with_constraints(body, &boundary, limits.constraints()),
+                    limits: limits.clone(),
+                    parts: 0,
                 }
             });
 
@@ -105,6 +347,172 @@ impl FilterBase for FormOptions {
     }
 }
 
+// ===== impl TypedFormOptions =====
+
+impl<T> TypedFormOptions<T> {
+    /// Set the maximum byte length allowed for this body.
+    ///
+    /// See [`FormOptions::max_length`].
+    pub fn max_length(mut self, max: impl Into<Option<u64>>) -> Self {
+        self.form = self.form.max_length(max);
+        self
+    }
+
+    /// Set the options of the underlying form, such as its limits.
+    ///
+    /// See [`FormOptions`].
+    pub fn form(mut self, form: FormOptions) -> Self {
+        self.form = form;
+        self
+    }
+
+    /// Set the maximum byte length of a text field.
+    ///
+    /// Defaults to 64KB.
+    pub fn max_field_length(mut self, max: u64) -> Self {
+        self.limits.max_field_length = max;
+        self
+    }
+
+    /// Set the maximum byte length of a file.
+    ///
+    /// `max_file_length(None)` means that only the length of the whole body
+    /// is checked. Defaults to `None`.
+    pub fn max_file_length(mut self, max: impl Into<Option<u64>>) -> Self {
+        self.limits.max_file_length = max.into();
+        self
+    }
+
+    /// Set the byte length above which a file is written to a temporary file
+    /// instead of being kept in memory.
+    ///
+    /// Defaults to 256KB.
+    pub fn spill_threshold(mut self, threshold: u64) -> Self {
+        self.limits.spill_threshold = threshold;
+        self
+    }
+}
+
+impl<T> Clone for TypedFormOptions<T> {
+    fn clone(&self) -> Self {
+        TypedFormOptions {
+            form: self.form.clone(),
+            limits: self.limits,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for TypedFormOptions<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypedFormOptions")
+            .field("form", &self.form)
+            .field("limits", &self.limits)
+            .finish()
+    }
+}
+
+type TypedFormFut<T> = Pin<Box<dyn Future<Output = Result<(TypedForm<T>,), Rejection>> + Send>>;
+
+impl<T: DeserializeOwned + Send + 'static> FilterBase for TypedFormOptions<T> {
+    type Extract = (TypedForm<T>,);
+    type Error = Rejection;
+    type Future = TypedFormFut<T>;
+
+    fn filter(&self, _: Internal) -> Self::Future {
+        let limits = self.limits;
+        Box::pin(
+            self.form
+                .filter(Internal)
+                .and_then(move |(form,)| collect_typed(form, limits)),
+        )
+    }
+}
+
+async fn collect_typed<T: DeserializeOwned>(
+    mut form: FormData,
+    limits: TypedLimits,
+) -> Result<(TypedForm<T>,), Rejection> {
+    let mut fields = Vec::new();
+    let mut files = Vec::new();
+
+    while let Some(part) = form.next().await {
+        let part = part?;
+        if part.filename().is_some() {
+            files.push(FormFile::collect(part, limits).await?);
+        } else {
+            let name = part.name().to_owned();
+            let value = read_field(part, limits.max_field_length).await?;
+            fields.push((name, value));
+        }
+    }
+
+    // Goes through the urlencoded format, so fields deserialize the same way
+    // as with `body::form()`.
+    let fields = serde_urlencoded::to_string(&fields)
+        .map_err(deserialize_error)
+        .and_then(|encoded| serde_urlencoded::from_str(&encoded).map_err(deserialize_error))?;
+    Ok((TypedForm { fields, files },))
+}
+
+async fn read_field(mut part: Part, max_length: u64) -> Result<String, Rejection> {
+    let mut value = Vec::new();
+    while let Some(data) = future::poll_fn(|cx| part.poll_next(cx)).await {
+        let data = data?;
+        if (value.len() + data.len()) as u64 > max_length {
+            tracing::debug!("multipart: field {:?} over its length limit", part.name());
+            return Err(reject::known(MultipartLimitError::new(
+                LimitKind::PartLength,
+            )));
+        }
+        value.extend_from_slice(&data);
+    }
+    String::from_utf8(value).map_err(deserialize_error)
+}
+
+/// Convert an error of a [`FormData`] or [`Part`] stream into a `Rejection`.
+///
+/// This is the same as the `?` operator in a filter returning a `Rejection`,
+/// see [`MultipartError`].
+///
+/// # Example
+///
+/// ```
+/// use futures_util::TryStreamExt;
+/// use starterm::Filter;
+/// use starterm::multipart::{self, FormData};
+///
+/// let route = multipart::form()
+///     .max_parts(10)
+///     .and_then(|form: FormData| async move {
+///         let parts: Vec<_> = form.try_collect().await.map_err(multipart::into_rejection)?;
+///         Ok::<_, starterm::Rejection>(format!("{} parts", parts.len()))
+///     });
+/// ```
+pub fn into_rejection(err: MultipartError) -> Rejection {
+    err.into()
+}
+
+// Limit violations detected by multer get our own error.
+fn multer_error(err: multer::Error) -> MultipartError {
+    match err {
+        multer::Error::FieldSizeExceeded { .. } => {
+            MultipartLimitError::new(LimitKind::PartLength).into()
+        }
+        multer::Error::UnknownField { .. } => MultipartLimitError::new(LimitKind::Field).into(),
+        err => MultipartError::body(err),
+    }
+}
+
+fn deserialize_error(err: impl Into<Box<dyn StdError + Send + Sync>>) -> Rejection {
+    reject::known(crate::body::BodyDeserializeError { cause: err.into() })
+}
+
+fn write_error(err: io::Error) -> Rejection {
+    tracing::error!("multipart: failed to write file: {}", err);
+    reject::known(MultipartWriteError { _p: () })
+}
+
 // ===== impl FormData =====
 
 impl fmt::Debug for FormData {
@@ -114,20 +522,23 @@ impl fmt::Debug for FormData {
 }
 
 impl Stream for FormData {
-    type Item = Result<Part, crate::Error>;
+    type Item = Result<Part, MultipartError>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         match self.inner.poll_next_field(cx) {
             Poll::Pending => Poll::Pending,
             Poll::Ready(Ok(Some(part))) => {
-                if part.name().is_some() || part.file_name().is_some() {
+                self.parts += 1;
+                if let Err(err) = self.limits.check(&part, self.parts) {
+                    Poll::Ready(Some(Err(err.into())))
+                } else if part.name().is_some() || part.file_name().is_some() {
                     Poll::Ready(Some(Ok(Part { part })))
                 } else {
-                    Poll::Ready(Some(Err(crate::Error::new(MultipartFieldMissingName))))
+                    Poll::Ready(Some(Err(MultipartError::body(MultipartFieldMissingName))))
                 }
             }
             Poll::Ready(Ok(None)) => Poll::Ready(None),
-            Poll::Ready(Err(err)) => Poll::Ready(Some(Err(crate::Error::new(err)))),
+            Poll::Ready(Err(err)) => Poll::Ready(Some(Err(multer_error(err)))),
         }
     }
 }
@@ -147,6 +558,15 @@ impl Part {
         self.part.file_name()
     }
 
+    /// Get the filename of this part, made safe to use as a file name.
+    ///
+    /// Only the last component of the filename is kept, as some clients send
+    /// a full path. Returns `None` if there is no filename, or if it would be
+    /// rejected as a path segment by [`fs::dir`](crate::fs::dir).
+    pub fn sanitized_filename(&self) -> Option<&str> {
+        sanitize_filename(self.filename()?)
+    }
+
     /// Get the content-type of this part, if present.
     pub fn content_type(&self) -> Option<&str> {
         let content_type = self.part.content_type();
@@ -154,21 +574,150 @@ impl Part {
     }
 
     /// Asynchronously get some of the data for this `Part`.
-    pub async fn data(&mut self) -> Option<Result<impl Buf, crate::Error>> {
+    pub async fn data(&mut self) -> Option<Result<impl Buf, MultipartError>> {
         future::poll_fn(|cx| self.poll_next(cx)).await
     }
 
     /// Convert this `Part` into a `Stream` of `Buf`s.
-    pub fn stream(self) -> impl Stream<Item = Result<impl Buf, crate::Error>> {
+    pub fn stream(self) -> impl Stream<Item = Result<impl Buf, MultipartError>> {
         PartStream(self)
     }
 
-    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, crate::Error>>> {
+    /// Save the data of this `Part` to a file at `path`.
+    ///
+    /// The file is created, or replaced if it exists. See [`Part::save_to_with`].
+    pub async fn save_to(self, path: impl AsRef<Path>) -> Result<SavedPart, MultipartError> {
+        self.save_to_with(path, SaveOptions::default()).await
+    }
+
+    /// Save the data of this `Part` to a file at `path`, configured by
+    /// `options`.
+    ///
+    /// The data is streamed to a hidden file in the directory of `path` as
+    /// it's received, which is renamed to `path` once the whole part is
+    /// saved. If saving fails, or the returned future is dropped, such as
+    /// when the client disconnects, the partially written file is removed,
+    /// and a file already at `path` is left as it was.
+    pub async fn save_to_with(
+        self,
+        path: impl AsRef<Path>,
+        options: SaveOptions,
+    ) -> Result<SavedPart, MultipartError> {
+        let path = path.as_ref().to_owned();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let name = format!(".{}", temp_file_name());
+        let (file, partial) = create_file_in(dir, &name, false)
+            .await
+            .map_err(MultipartError::io)?;
+        self.save(file, partial, Some(path), options).await
+    }
+
+    /// Save the data of this `Part` to a new file in the temporary directory.
+    ///
+    /// The file isn't removed once saved, it's up to the caller to move or
+    /// remove it. On Unix, it's only readable and writable by the current
+    /// user. See [`Part::save_to_with`].
+    pub async fn save_to_temp(self) -> Result<SavedPart, MultipartError> {
+        self.save_to_temp_with(SaveOptions::default()).await
+    }
+
+    /// Save the data of this `Part` to a new file in the temporary directory,
+    /// configured by `options`.
+    ///
+    /// See [`Part::save_to_temp`].
+    pub async fn save_to_temp_with(
+        self,
+        options: SaveOptions,
+    ) -> Result<SavedPart, MultipartError> {
+        let (file, path) = create_temp_file().await.map_err(MultipartError::io)?;
+        self.save(file, path, None, options).await
+    }
+
+    // Writes the data to `file` at `path`, then renames it to `target`, if
+    // any. `path` is removed if either fails.
+    async fn save(
+        mut self,
+        mut file: tokio::fs::File,
+        path: PathBuf,
+        target: Option<PathBuf>,
+        options: SaveOptions,
+    ) -> Result<SavedPart, MultipartError> {
+        let mut partial = RemoveOnDrop(Some(path));
+        let written = self.write(&mut file, &options).await;
+        drop(file);
+        let (size, sha1) = match written {
+            Ok(written) => written,
+            Err(err) => {
+                partial.remove().await;
+                return Err(err);
+            }
+        };
+
+        let path = match target {
+            Some(target) => {
+                let path = partial.0.as_ref().expect("path taken once");
+                if let Err(err) = tokio::fs::rename(path, &target).await {
+                    partial.remove().await;
+                    return Err(MultipartError::io(err));
+                }
+                partial.0 = None;
+                target
+            }
+            None => partial.0.take().expect("path taken once"),
+        };
+        Ok(SavedPart { path, size, sha1 })
+    }
+
+    async fn write(
+        &mut self,
+        file: &mut tokio::fs::File,
+        options: &SaveOptions,
+    ) -> Result<(u64, Option<String>), MultipartError> {
+        let mut hasher = if options.sha1 {
+            Some(Sha1::new())
+        } else {
+            None
+        };
+        let mut size = 0;
+
+        while let Some(data) = future::poll_fn(|cx| self.poll_next(cx)).await {
+            let data = data?;
+            size += data.len() as u64;
+            if let Some(max_size) = options.max_size {
+                if size > max_size {
+                    return Err(MultipartLimitError::new(LimitKind::PartLength).into());
+                }
+            }
+            if let Some(ref mut hasher) = hasher {
+                hasher.update(&data);
+            }
+            file.write_all(&data).await.map_err(MultipartError::io)?;
+        }
+
+        file.flush().await.map_err(MultipartError::io)?;
+        if options.fsync {
+            file.sync_all().await.map_err(MultipartError::io)?;
+        }
+
+        let sha1 = hasher.map(|hasher| {
+            hasher
+                .finalize()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect()
+        });
+        Ok((size, sha1))
+    }
+
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, MultipartError>>> {
         match Pin::new(&mut self.part).poll_next(cx) {
             Poll::Pending => Poll::Pending,
             Poll::Ready(Some(Ok(bytes))) => Poll::Ready(Some(Ok(bytes))),
             Poll::Ready(None) => Poll::Ready(None),
-            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(crate::Error::new(err)))),
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(multer_error(err)))),
         }
     }
 }
@@ -190,10 +739,324 @@ impl fmt::Debug for Part {
     }
 }
 
+// Removes the file at its path when dropped, unless the path was taken.
+//
+// Within a runtime, the removal runs on its blocking thread pool, so the file
+// may still exist for a moment after the drop.
+struct RemoveOnDrop(Option<PathBuf>);
+
+impl RemoveOnDrop {
+    // Removes the file before returning, instead of once dropped.
+    async fn remove(mut self) {
+        if let Some(path) = self.0.take() {
+            tracing::debug!("multipart: removing {:?}", path);
+            let _ = tokio::fs::remove_file(path).await;
+        }
+    }
+}
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        if let Some(path) = self.0.take() {
+            tracing::debug!("multipart: removing {:?}", path);
+            let remove = move || {
+                let _ = std::fs::remove_file(path);
+            };
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    handle.spawn_blocking(remove);
+                }
+                Err(_) => remove(),
+            }
+        }
+    }
+}
+
+fn sanitize_filename(filename: &str) -> Option<&str> {
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or(filename);
+    let unsafe_name = name.is_empty()
+        || name == "."
+        || name.contains('\0')
+        || crate::filters::fs::unsafe_segment(name).is_some();
+    if unsafe_name {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+// Creates a new file in the temporary directory. On Unix, only the current
+// user may read or write it.
+async fn create_temp_file() -> io::Result<(tokio::fs::File, PathBuf)> {
+    create_file_in(&std::env::temp_dir(), &temp_file_name(), true).await
+}
+
+// Creates a new file named `name` in `dir`, failing if it exists.
+async fn create_file_in(
+    dir: &Path,
+    name: &str,
+    owner_only: bool,
+) -> io::Result<(tokio::fs::File, PathBuf)> {
+    let path = dir.join(name);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    if owner_only {
+        options.mode(0o600);
+    }
+    #[cfg(not(unix))]
+    let _ = owner_only;
+    let file = options.open(&path).await?;
+    Ok((file, path))
+}
+
+fn temp_file_name() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|dur| dur.subsec_nanos())
+        .unwrap_or(0);
+    format!(
+        "starterm-upload-{}-{:x}-{:x}",
+        std::process::id(),
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+// ===== impl SaveOptions =====
+
+impl SaveOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        SaveOptions::default()
+    }
+
+    /// Set the maximum size of the saved part, in bytes.
+    ///
+    /// A larger part fails to save with a [`MultipartLimitError`]. Defaults
+    /// to `None`, no maximum besides the one of the whole form.
+    pub fn max_size(mut self, max: impl Into<Option<u64>>) -> Self {
+        self.max_size = max.into();
+        self
+    }
+
+    /// Set whether the file is synced to disk before the save completes.
+    ///
+    /// Defaults to `false`.
+    pub fn fsync(mut self, fsync: bool) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    /// Set whether the SHA-1 hash of the data is computed while saving.
+    ///
+    /// Defaults to `false`. See [`SavedPart::sha1`].
+    pub fn sha1(mut self, sha1: bool) -> Self {
+        self.sha1 = sha1;
+        self
+    }
+}
+
+// ===== impl SavedPart =====
+
+impl SavedPart {
+    /// Get the path of the file the part was saved to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the size of the saved data, in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Get the SHA-1 hash of the saved data, in lowercase hex, if enabled
+    /// with [`SaveOptions::sha1`].
+    pub fn sha1(&self) -> Option<&str> {
+        self.sha1.as_deref()
+    }
+}
+
+// ===== impl TypedForm =====
+
+impl<T> TypedForm<T> {
+    /// Get the deserialized text fields.
+    pub fn fields(&self) -> &T {
+        &self.fields
+    }
+
+    /// Get the files, in the order they were received.
+    pub fn files(&self) -> &[FormFile] {
+        &self.files
+    }
+
+    /// Get the first file of the part named `name`, if present.
+    pub fn file(&self, name: &str) -> Option<&FormFile> {
+        self.files.iter().find(|file| file.name == name)
+    }
+
+    /// Take the first file of the part named `name`, if present.
+    pub fn take_file(&mut self, name: &str) -> Option<FormFile> {
+        let index = self.files.iter().position(|file| file.name == name)?;
+        Some(self.files.remove(index))
+    }
+
+    /// Convert this form into its fields and files.
+    pub fn into_parts(self) -> (T, Vec<FormFile>) {
+        (self.fields, self.files)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for TypedForm<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypedForm")
+            .field("fields", &self.fields)
+            .field("files", &self.files)
+            .finish()
+    }
+}
+
+// ===== impl FormFile =====
+
+impl FormFile {
+    async fn collect(mut part: Part, limits: TypedLimits) -> Result<FormFile, Rejection> {
+        let mut buf = Vec::new();
+        let mut spilled = None;
+        let mut size = 0;
+
+        while let Some(data) = future::poll_fn(|cx| part.poll_next(cx)).await {
+            let data = data?;
+            size += data.len() as u64;
+            if limits.max_file_length.is_some_and(|max| size > max) {
+                tracing::debug!("multipart: file {:?} over its length limit", part.name());
+                return Err(reject::known(MultipartLimitError::new(
+                    LimitKind::PartLength,
+                )));
+            }
+
+            match spilled {
+                Some((ref mut file, _)) => write_all(file, &data).await?,
+                None if size > limits.spill_threshold => {
+                    let (mut file, path) = create_temp_file().await.map_err(write_error)?;
+                    let guard = RemoveOnDrop(Some(path));
+                    write_all(&mut file, &buf).await?;
+                    write_all(&mut file, &data).await?;
+                    buf = Vec::new();
+                    spilled = Some((file, guard));
+                }
+                None => buf.extend_from_slice(&data),
+            }
+        }
+
+        let contents = match spilled {
+            Some((mut file, guard)) => {
+                file.flush().await.map_err(write_error)?;
+                FileContents::Temp(guard)
+            }
+            None => FileContents::Memory(Bytes::from(buf)),
+        };
+        Ok(FormFile {
+            name: part.name().to_owned(),
+            filename: part.filename().map(str::to_owned),
+            content_type: part.content_type().map(str::to_owned),
+            size,
+            contents,
+        })
+    }
+
+    /// Get the name of this file's part.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the filename of this file, if present.
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    /// Get the filename of this file, made safe to use as a file name.
+    ///
+    /// See [`Part::sanitized_filename`].
+    pub fn sanitized_filename(&self) -> Option<&str> {
+        sanitize_filename(self.filename.as_deref()?)
+    }
+
+    /// Get the content-type of this file, if present.
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// Get the length of this file, in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Get the contents of this file, if it was kept in memory.
+    pub fn bytes(&self) -> Option<&Bytes> {
+        match self.contents {
+            FileContents::Memory(ref bytes) => Some(bytes),
+            FileContents::Temp(_) => None,
+        }
+    }
+
+    /// Get the path of the temporary file, if this file was written to one.
+    pub fn temp_path(&self) -> Option<&Path> {
+        match self.contents {
+            FileContents::Memory(_) => None,
+            FileContents::Temp(ref guard) => guard.0.as_deref(),
+        }
+    }
+
+    /// Write this file to `path`, moving the temporary file if there is one.
+    pub async fn persist_to(self, path: impl AsRef<Path>) -> Result<(), MultipartError> {
+        let path = path.as_ref();
+        match self.contents {
+            FileContents::Memory(bytes) => tokio::fs::write(path, bytes)
+                .await
+                .map_err(MultipartError::io),
+            FileContents::Temp(mut guard) => {
+                let temp = guard.0.take().expect("temporary file path");
+                // A rename can't cross file systems, copy instead.
+                if tokio::fs::rename(&temp, path).await.is_err() {
+                    guard.0 = Some(temp.clone());
+                    tokio::fs::copy(&temp, path)
+                        .await
+                        .map_err(MultipartError::io)?;
+                    guard.remove().await;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Debug for FormFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut builder = f.debug_struct("FormFile");
+        builder.field("name", &self.name);
+
+        if let Some(ref filename) = self.filename {
+            builder.field("filename", filename);
+        }
+
+        if let Some(ref content_type) = self.content_type {
+            builder.field("content_type", content_type);
+        }
+
+        builder.field("size", &self.size).finish()
+    }
+}
+
+async fn write_all(file: &mut tokio::fs::File, data: &[u8]) -> Result<(), Rejection> {
+    file.write_all(data).await.map_err(write_error)
+}
+
 struct PartStream(Part);
 
 impl Stream for PartStream {
-    type Item = Result<Bytes, crate::Error>;
+    type Item = Result<Bytes, MultipartError>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         self.0.poll_next(cx)
@@ -210,9 +1073,7 @@ impl Stream for BodyIoError {
             Poll::Pending => Poll::Pending,
             Poll::Ready(Some(Ok(bytes))) => Poll::Ready(Some(Ok(bytes))),
             Poll::Ready(None) => Poll::Ready(None),
-            Poll::Ready(Some(Err(err))) => {
-                Poll::Ready(Some(Err(io::Error::new(io::ErrorKind::Other, err))))
-            }
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(io::Error::other(err)))),
         }
     }
 }
@@ -228,3 +1089,163 @@ impl Display for MultipartFieldMissingName {
 }
 
 impl StdError for MultipartFieldMissingName {}
+
+unit_error! {
+    pub(crate) MultipartWriteError: "Multipart file could not be written"
+}
+
+/// An error reading a `multipart/form-data` body, yielded by [`FormData`]
+/// and [`Part`].
+///
+/// It converts into a `Rejection`, so it can be returned with `?` from a
+/// filter: a violated limit of the [`FormOptions`] rejects the request with
+/// its [`MultipartLimitError`], a malformed body with `400 Bad Request`, and
+/// a part that couldn't be saved with `500 Internal Server Error`.
+///
+/// # Example
+///
+/// ```
+/// use futures_util::TryStreamExt;
+/// use starterm::Filter;
+/// use starterm::multipart::FormData;
+///
+/// let route = starterm::multipart::form()
+///     .max_parts(10)
+///     .and_then(|mut form: FormData| async move {
+///         let mut names = Vec::new();
+///         while let Some(part) = form.try_next().await? {
+///             names.push(part.name().to_owned());
+///         }
+///         Ok::<_, starterm::Rejection>(names.join(", "))
+///     });
+/// ```
+pub struct MultipartError {
+    kind: ErrorKind,
+}
+
+enum ErrorKind {
+    Limit(MultipartLimitError),
+    Body(crate::Error),
+    Io(io::Error),
+}
+
+impl MultipartError {
+    fn body(err: impl Into<Box<dyn StdError + Send + Sync>>) -> Self {
+        MultipartError {
+            kind: ErrorKind::Body(crate::Error::new(err)),
+        }
+    }
+
+    fn io(err: io::Error) -> Self {
+        MultipartError {
+            kind: ErrorKind::Io(err),
+        }
+    }
+
+    /// Returns the violated limit, if this error is one.
+    pub fn limit(&self) -> Option<&MultipartLimitError> {
+        match self.kind {
+            ErrorKind::Limit(ref limit) => Some(limit),
+            _ => None,
+        }
+    }
+}
+
+impl From<MultipartLimitError> for MultipartError {
+    fn from(limit: MultipartLimitError) -> Self {
+        MultipartError {
+            kind: ErrorKind::Limit(limit),
+        }
+    }
+}
+
+impl From<MultipartError> for Rejection {
+    fn from(err: MultipartError) -> Self {
+        match err.kind {
+            ErrorKind::Limit(limit) => reject::known(limit),
+            ErrorKind::Body(err) => deserialize_error(err),
+            ErrorKind::Io(err) => write_error(err),
+        }
+    }
+}
+
+impl fmt::Debug for MultipartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ErrorKind::Limit(ref err) => fmt::Debug::fmt(err, f),
+            ErrorKind::Body(ref err) => fmt::Debug::fmt(err, f),
+            ErrorKind::Io(ref err) => fmt::Debug::fmt(err, f),
+        }
+    }
+}
+
+impl Display for MultipartError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ErrorKind::Limit(ref err) => Display::fmt(err, f),
+            ErrorKind::Body(ref err) => Display::fmt(err, f),
+            ErrorKind::Io(ref err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl StdError for MultipartError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self.kind {
+            ErrorKind::Limit(ref err) => Some(err),
+            ErrorKind::Body(ref err) => err.source(),
+            ErrorKind::Io(ref err) => Some(err),
+        }
+    }
+}
+
+/// An error used in rejections when a multipart body is over one of the
+/// limits of its [`FormOptions`].
+///
+/// A body over a length or count limit is rejected with
+/// `413 Payload Too Large`, one with a field that isn't allowed with
+/// `400 Bad Request`, and one with a content-type that isn't allowed with
+/// `415 Unsupported Media Type`.
+#[derive(Debug)]
+pub struct MultipartLimitError {
+    kind: LimitKind,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum LimitKind {
+    PartLength,
+    Parts,
+    Headers,
+    Field,
+    ContentType,
+}
+
+impl MultipartLimitError {
+    fn new(kind: LimitKind) -> Self {
+        MultipartLimitError { kind }
+    }
+
+    pub(crate) fn status(&self) -> http::StatusCode {
+        match self.kind {
+            LimitKind::PartLength | LimitKind::Parts | LimitKind::Headers => {
+                http::StatusCode::PAYLOAD_TOO_LARGE
+            }
+            LimitKind::Field => http::StatusCode::BAD_REQUEST,
+            LimitKind::ContentType => http::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        }
+    }
+}
+
+impl Display for MultipartLimitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self.kind {
+            LimitKind::PartLength => "Multipart part is over the length limit",
+            LimitKind::Parts => "Multipart body has too many parts",
+            LimitKind::Headers => "Multipart part headers are over the length limit",
+            LimitKind::Field => "Multipart field is not allowed",
+            LimitKind::ContentType => "Multipart part content-type is not allowed",
+        })
+    }
+}
+
+impl StdError for MultipartLimitError {}
@@ -10,11 +10,12 @@
 //! [`Spans`]: https://docs.rs/tracing/latest/tracing/#spans
 use tracing::Span;
 
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 
 use http::header;
 
 use crate::filter::{Filter, WrapSealed};
+use crate::filters::addr::TrustedProxies;
 use crate::reject::IsReject;
 use crate::reply::Reply;
 use crate::route::Route;
@@ -162,6 +163,29 @@ impl Info<'_> {
         self.route.remote_addr()
     }
 
+    /// View the IP address of the client, as reported by the `trusted`
+    /// proxies.
+    ///
+    /// See [`addr::client_ip`](crate::addr::client_ip).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use starterm::Filter;
+    /// use starterm::addr::TrustedProxies;
+    ///
+    /// let trusted = TrustedProxies::new(vec!["10.0.0.0/8".parse().unwrap()]);
+    /// let route = starterm::any()
+    ///     .map(starterm::reply)
+    ///     .with(starterm::trace(move |info| {
+    ///         let client = info.client_ip(&trusted);
+    ///         tracing::info_span!("request", client = ?client, path = %info.path())
+    ///     }));
+    /// ```
+    pub fn client_ip(&self, trusted: &TrustedProxies) -> Option<IpAddr> {
+        trusted.client_ip(self.route.remote_addr(), self.route.headers())
+    }
+
     /// View the `http::Method` of the request.
     pub fn method(&self) -> &http::Method {
         self.route.method()
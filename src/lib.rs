@@ -90,23 +90,31 @@ mod error;
 mod filter;
 pub mod filters;
 mod generic;
+mod proxy_protocol;
 pub mod redirect;
 pub mod reject;
 pub mod reply;
 mod route;
 mod server;
 mod service;
+mod tcp;
 pub mod test;
 #[cfg(feature = "tls")]
 mod tls;
 mod transport;
+#[cfg(unix)]
+mod unix;
 
 pub use self::error::Error;
 pub use self::filter::Filter;
 // This otherwise shows a big dump of re-exports in the doc homepage,
 // with zero context, so just hide it from the docs. Doc examples
 // on each can show that a convenient import exists.
-#[cfg(feature = "compression")]
+#[cfg(any(
+    feature = "compression-brotli",
+    feature = "compression-gzip",
+    feature = "compression-zstd"
+))]
 #[doc(hidden)]
 pub use self::filters::compression;
 #[cfg(feature = "multipart")]
@@ -121,6 +129,7 @@ pub use self::filters::{
     // any() function
     any::any,
     body,
+    conn,
     cookie,
     // cookie() function
     cookie::cookie,
@@ -162,8 +171,11 @@ pub use self::reject::{reject, Rejection};
 pub use self::reply::{reply, Reply};
 #[cfg(feature = "tls")]
 pub use self::server::TlsServer;
-pub use self::server::{serve, Server};
+pub use self::server::{serve, BindError, Server, ServerConfig};
 pub use self::service::service;
+#[cfg(feature = "tls")]
+pub use self::tls::TlsConfigError;
+pub use self::transport::Transport;
 #[doc(hidden)]
 pub use http;
 #[doc(hidden)]
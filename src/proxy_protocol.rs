@@ -0,0 +1,319 @@
+//! Parsing of the PROXY protocol header, versions 1 and 2.
+//!
+//! See <https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt>.
+
+use std::convert::TryFrom;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use bytes::{Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+// Including the CRLF.
+const V1_MAX_LEN: usize = 107;
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+const V2_HEADER_LEN: usize = 16;
+
+/// The addresses sent by the proxy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ProxyAddrs {
+    pub(crate) source: SocketAddr,
+    pub(crate) destination: SocketAddr,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Parsed {
+    Incomplete,
+    // `None` for a header without addresses, such as a health check of the
+    // proxy itself, where the connection's own addresses are kept.
+    Complete(Option<ProxyAddrs>, usize),
+}
+
+/// Reads a PROXY header from `io`.
+///
+/// Returns the addresses of the header, and any bytes read past it, which
+/// belong to the connection.
+pub(crate) async fn read_header<T: AsyncRead + Unpin>(
+    io: &mut T,
+) -> io::Result<(Option<ProxyAddrs>, Bytes)> {
+    let mut buf = BytesMut::with_capacity(V1_MAX_LEN);
+    loop {
+        if let Parsed::Complete(addrs, len) = parse(&buf)? {
+            let rest = buf.split_off(len).freeze();
+            return Ok((addrs, rest));
+        }
+        if io.read_buf(&mut buf).await? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed before the PROXY header",
+            ));
+        }
+    }
+}
+
+fn parse(buf: &[u8]) -> io::Result<Parsed> {
+    if is_prefix(buf, V1_PREFIX) {
+        parse_v1(buf)
+    } else if is_prefix(buf, V2_SIGNATURE) {
+        parse_v2(buf)
+    } else {
+        Err(invalid("missing PROXY header"))
+    }
+}
+
+// Whether `buf` and `expected` agree on their common length.
+fn is_prefix(buf: &[u8], expected: &[u8]) -> bool {
+    let len = buf.len().min(expected.len());
+    buf[..len] == expected[..len]
+}
+
+fn parse_v1(buf: &[u8]) -> io::Result<Parsed> {
+    let end = match buf.windows(2).position(|w| w == b"\r\n") {
+        Some(end) => end,
+        None if buf.len() < V1_MAX_LEN => return Ok(Parsed::Incomplete),
+        None => return Err(invalid("PROXY v1 header is too long")),
+    };
+    if end + 2 > V1_MAX_LEN {
+        return Err(invalid("PROXY v1 header is too long"));
+    }
+    let line = std::str::from_utf8(&buf[V1_PREFIX.len()..end])
+        .map_err(|_| invalid("PROXY v1 header is not ASCII"))?;
+    let mut parts = line.split(' ');
+    let addrs = match parts.next() {
+        // The rest of the line is to be ignored.
+        Some("UNKNOWN") => None,
+        Some(proto @ "TCP4") | Some(proto @ "TCP6") => {
+            let src = parse_v1_ip(parts.next(), proto)?;
+            let dst = parse_v1_ip(parts.next(), proto)?;
+            let src_port = parse_v1_port(parts.next())?;
+            let dst_port = parse_v1_port(parts.next())?;
+            if parts.next().is_some() {
+                return Err(invalid("PROXY v1 header has trailing fields"));
+            }
+            Some(ProxyAddrs {
+                source: SocketAddr::new(src, src_port),
+                destination: SocketAddr::new(dst, dst_port),
+            })
+        }
+        _ => return Err(invalid("PROXY v1 header has an unknown protocol")),
+    };
+    Ok(Parsed::Complete(addrs, end + 2))
+}
+
+fn parse_v1_ip(field: Option<&str>, proto: &str) -> io::Result<IpAddr> {
+    let ip = match (field, proto) {
+        (Some(field), "TCP4") => field.parse::<Ipv4Addr>().ok().map(IpAddr::V4),
+        (Some(field), _) => field.parse::<Ipv6Addr>().ok().map(IpAddr::V6),
+        (None, _) => None,
+    };
+    ip.ok_or_else(|| invalid("PROXY v1 header has an invalid address"))
+}
+
+fn parse_v1_port(field: Option<&str>) -> io::Result<u16> {
+    field
+        // Ports are plain decimal numbers, without a sign or leading zeros.
+        .filter(|f| !f.is_empty() && f.bytes().all(|b| b.is_ascii_digit()))
+        .filter(|f| *f == "0" || !f.starts_with('0'))
+        .and_then(|f| f.parse().ok())
+        .ok_or_else(|| invalid("PROXY v1 header has an invalid port"))
+}
+
+fn parse_v2(buf: &[u8]) -> io::Result<Parsed> {
+    if buf.len() < V2_HEADER_LEN {
+        return Ok(Parsed::Incomplete);
+    }
+    let version = buf[12] >> 4;
+    let command = buf[12] & 0x0f;
+    let family = buf[13] >> 4;
+    let transport = buf[13] & 0x0f;
+    let len = usize::from(u16::from_be_bytes([buf[14], buf[15]]));
+    if version != 2 {
+        return Err(invalid("PROXY v2 header has an unknown version"));
+    }
+    if buf.len() < V2_HEADER_LEN + len {
+        return Ok(Parsed::Incomplete);
+    }
+    let block = &buf[V2_HEADER_LEN..V2_HEADER_LEN + len];
+    let addrs = match command {
+        // LOCAL, sent by the proxy for its own connections.
+        0x0 => None,
+        // PROXY, only from a TCP connection, unless it's all unspecified.
+        0x1 if transport != 0x1 && buf[13] != 0x00 => {
+            return Err(invalid("PROXY v2 header has a transport other than TCP"));
+        }
+        0x1 => match family {
+            0x1 => {
+                if block.len() < 12 {
+                    return Err(invalid("PROXY v2 header is too short"));
+                }
+                let src = Ipv4Addr::from(<[u8; 4]>::try_from(&block[0..4]).unwrap());
+                let dst = Ipv4Addr::from(<[u8; 4]>::try_from(&block[4..8]).unwrap());
+                Some(ProxyAddrs {
+                    source: SocketAddr::new(src.into(), port(&block[8..10])),
+                    destination: SocketAddr::new(dst.into(), port(&block[10..12])),
+                })
+            }
+            0x2 => {
+                if block.len() < 36 {
+                    return Err(invalid("PROXY v2 header is too short"));
+                }
+                let src = Ipv6Addr::from(<[u8; 16]>::try_from(&block[0..16]).unwrap());
+                let dst = Ipv6Addr::from(<[u8; 16]>::try_from(&block[16..32]).unwrap());
+                Some(ProxyAddrs {
+                    source: SocketAddr::new(src.into(), port(&block[32..34])),
+                    destination: SocketAddr::new(dst.into(), port(&block[34..36])),
+                })
+            }
+            // Unspecified, or Unix sockets, which don't have socket
+            // addresses to report.
+            _ => None,
+        },
+        _ => return Err(invalid("PROXY v2 header has an unknown command")),
+    };
+    // TLVs after the addresses are skipped.
+    Ok(Parsed::Complete(addrs, V2_HEADER_LEN + len))
+}
+
+fn port(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addrs(source: &str, destination: &str) -> Option<ProxyAddrs> {
+        Some(ProxyAddrs {
+            source: source.parse().unwrap(),
+            destination: destination.parse().unwrap(),
+        })
+    }
+
+    #[test]
+    fn v1() {
+        let header = b"PROXY TCP4 192.0.2.1 198.51.100.2 56324 443\r\nGET /";
+        assert_eq!(
+            parse(header).unwrap(),
+            Parsed::Complete(addrs("192.0.2.1:56324", "198.51.100.2:443"), 45)
+        );
+
+        let header = b"PROXY TCP6 2001:db8::1 2001:db8::2 56324 443\r\n";
+        assert_eq!(
+            parse(header).unwrap(),
+            Parsed::Complete(addrs("[2001:db8::1]:56324", "[2001:db8::2]:443"), 46)
+        );
+
+        let header = b"PROXY UNKNOWN ffff::1 ffff::2 1 2\r\n";
+        assert_eq!(parse(header).unwrap(), Parsed::Complete(None, 35));
+
+        assert_eq!(parse(b"PRO").unwrap(), Parsed::Incomplete);
+        assert_eq!(parse(b"PROXY TCP4 192.0.2.1").unwrap(), Parsed::Incomplete);
+    }
+
+    #[test]
+    fn v1_invalid() {
+        let headers: &[&[u8]] = &[
+            b"GET / HTTP/1.1\r\n",
+            b"PROXY TCP4 2001:db8::1 198.51.100.2 56324 443\r\n",
+            b"PROXY TCP4 192.0.2.1 198.51.100.2 56324\r\n",
+            b"PROXY TCP4 192.0.2.1 198.51.100.2 56324 443 1\r\n",
+            b"PROXY TCP4 192.0.2.1 198.51.100.2 056324 443\r\n",
+            b"PROXY TCP4 192.0.2.1 198.51.100.2 65536 443\r\n",
+            b"PROXY UDP4 192.0.2.1 198.51.100.2 56324 443\r\n",
+        ];
+        for header in headers {
+            assert!(
+                parse(header).is_err(),
+                "{:?}",
+                String::from_utf8_lossy(header)
+            );
+        }
+
+        let mut long = b"PROXY UNKNOWN ".to_vec();
+        long.resize(V1_MAX_LEN, b'a');
+        assert!(parse(&long).is_err());
+    }
+
+    #[test]
+    fn v2() {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x21, 0x11, 0, 15]);
+        header.extend_from_slice(&[192, 0, 2, 1, 198, 51, 100, 2]);
+        header.extend_from_slice(&56324u16.to_be_bytes());
+        header.extend_from_slice(&443u16.to_be_bytes());
+        // A TLV, which is skipped.
+        header.extend_from_slice(&[0x04, 0, 0]);
+
+        for len in 0..header.len() {
+            assert_eq!(parse(&header[..len]).unwrap(), Parsed::Incomplete);
+        }
+        assert_eq!(
+            parse(&header).unwrap(),
+            Parsed::Complete(addrs("192.0.2.1:56324", "198.51.100.2:443"), 31)
+        );
+
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x21, 0x21, 0, 36]);
+        header.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        header.extend_from_slice(&"2001:db8::2".parse::<Ipv6Addr>().unwrap().octets());
+        header.extend_from_slice(&56324u16.to_be_bytes());
+        header.extend_from_slice(&443u16.to_be_bytes());
+        assert_eq!(
+            parse(&header).unwrap(),
+            Parsed::Complete(addrs("[2001:db8::1]:56324", "[2001:db8::2]:443"), 52)
+        );
+
+        // LOCAL
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x20, 0x00, 0, 0]);
+        assert_eq!(parse(&header).unwrap(), Parsed::Complete(None, 16));
+
+        // Unspecified family and transport.
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x21, 0x00, 0, 0]);
+        assert_eq!(parse(&header).unwrap(), Parsed::Complete(None, 16));
+    }
+
+    #[test]
+    fn v2_invalid() {
+        // Wrong version.
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x11, 0x11, 0, 12]);
+        assert!(parse(&header).is_err());
+
+        // Unknown command.
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x22, 0x11, 0, 12]);
+        header.extend_from_slice(&[0; 12]);
+        assert!(parse(&header).is_err());
+
+        // UDP, on a TCP listener.
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x21, 0x12, 0, 12]);
+        header.extend_from_slice(&[0; 12]);
+        assert!(parse(&header).is_err());
+
+        // Addresses longer than the block.
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x21, 0x11, 0, 4]);
+        header.extend_from_slice(&[0; 4]);
+        assert!(parse(&header).is_err());
+    }
+
+    #[tokio::test]
+    async fn read_header_keeps_rest() {
+        let mut io: &[u8] = b"PROXY TCP4 192.0.2.1 198.51.100.2 56324 443\r\nGET / HTTP/1.1\r\n";
+        let (addrs, rest) = read_header(&mut io).await.unwrap();
+        assert_eq!(addrs.unwrap().source, "192.0.2.1:56324".parse().unwrap());
+        assert_eq!(&rest[..], b"GET / HTTP/1.1\r\n");
+
+        let mut io: &[u8] = b"PROXY TCP4 192.0.2.1";
+        let err = read_header(&mut io).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -409,7 +409,12 @@ enum_known! {
     #[cfg(feature = "websocket")]
     MissingConnectionUpgrade(crate::ws::MissingConnectionUpgrade),
     MissingExtension(crate::ext::MissingExtension),
+    MissingConnInfo(crate::conn::MissingConnInfo),
     BodyConsumedMultipleTimes(crate::body::BodyConsumedMultipleTimes),
+    #[cfg(feature = "multipart")]
+    MultipartWriteError(crate::multipart::MultipartWriteError),
+    #[cfg(feature = "multipart")]
+    MultipartLimitError(crate::multipart::MultipartLimitError),
 }
 
 impl Rejection {
@@ -544,7 +549,12 @@ impl Rejections {
                 Known::FilePermissionError(_) | Known::CorsForbidden(_) => StatusCode::FORBIDDEN,
                 Known::FileOpenError(_)
                 | Known::MissingExtension(_)
+                | Known::MissingConnInfo(_)
                 | Known::BodyConsumedMultipleTimes(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                #[cfg(feature = "multipart")]
+                Known::MultipartWriteError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                #[cfg(feature = "multipart")]
+                Known::MultipartLimitError(ref err) => err.status(),
             },
             Rejections::Custom(..) => StatusCode::INTERNAL_SERVER_ERROR,
             Rejections::Combined(..) => self.preferred().status(),
@@ -34,14 +34,25 @@
 //! ```
 
 use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
 use std::convert::TryFrom;
+use std::error::Error as StdError;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use crate::generic::{Either, One};
-use http::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use bytes::Bytes;
+use futures_util::stream::{self, BoxStream};
+use futures_util::{future, Stream, StreamExt, TryStreamExt};
+use http::header::{
+    HeaderMap, HeaderName, HeaderValue, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE,
+};
 use http::StatusCode;
 use hyper::Body;
 use serde::Serialize;
 
+type BoxError = Box<dyn StdError + Send + Sync>;
+
 // This re-export just looks weird in docs...
 pub(crate) use self::sealed::Reply_;
 use self::sealed::{BoxedReply, Internal};
@@ -181,6 +192,321 @@ where
     }
 }
 
+/// Reply with a `multipart/mixed` body, made of the parts added with
+/// [`Multipart::part`].
+///
+/// The body is streamed one part after another, so parts created from a
+/// [stream](MultipartPart::stream) are never buffered in memory. A random
+/// boundary is generated, unless one is set with [`Multipart::boundary`].
+///
+/// # Example
+///
+/// ```
+/// use starterm::Filter;
+/// use starterm::reply::MultipartPart;
+///
+/// // POST /batch returns two JSON documents and an attachment.
+/// let route = starterm::path("batch")
+///     .map(|| {
+///         starterm::reply::multipart()
+///             .part(MultipartPart::json(&[1, 3, 7]))
+///             .part(MultipartPart::json(&"done"))
+///             .part(
+///                 MultipartPart::new(vec![0u8, 1, 2, 3])
+///                     .content_type("application/octet-stream")
+///                     .filename("report.bin"),
+///             )
+///     });
+/// ```
+pub fn multipart() -> Multipart {
+    Multipart {
+        subtype: "mixed",
+        boundary: None,
+        parts: Vec::new(),
+    }
+}
+
+/// A multipart reply.
+///
+/// Returned by `starterm::reply::multipart`.
+#[allow(missing_debug_implementations)]
+pub struct Multipart {
+    subtype: &'static str,
+    boundary: Option<String>,
+    parts: Vec<MultipartPart>,
+}
+
+impl Multipart {
+    /// Sends a `multipart/form-data` body instead of `multipart/mixed`.
+    ///
+    /// Every part should then have a [name](MultipartPart::name).
+    pub fn form_data(mut self) -> Self {
+        self.subtype = "form-data";
+        self
+    }
+
+    /// Sets the boundary between the parts.
+    ///
+    /// It must be 1 to 70 characters long, of letters, digits and
+    /// `'()+_,-./:=?`, and must not appear in the body of any part. An invalid
+    /// boundary makes the reply a `500 Internal Server Error`.
+    pub fn boundary(mut self, boundary: impl Into<String>) -> Self {
+        self.boundary = Some(boundary.into());
+        self
+    }
+
+    /// Adds a part after the ones already added.
+    pub fn part(mut self, part: MultipartPart) -> Self {
+        self.parts.push(part);
+        self
+    }
+
+    // Returns the `content-type` and the body, and its length when no part is
+    // a stream.
+    pub(crate) fn into_body(self) -> Result<(HeaderValue, Option<u64>, Body), ()> {
+        let boundary = self.boundary.unwrap_or_else(multipart_boundary);
+        if !is_valid_boundary(&boundary) {
+            tracing::error!("reply::multipart invalid boundary: {:?}", boundary);
+            return Err(());
+        }
+        if self.parts.iter().any(|part| part.invalid) {
+            return Err(());
+        }
+        let content_type = HeaderValue::from_str(&format!(
+            "multipart/{}; boundary={}",
+            self.subtype, boundary
+        ))
+        .map_err(|_| ())?;
+
+        let mut content_length = Some(0);
+        let mut body = Vec::with_capacity(self.parts.len());
+        for part in self.parts {
+            let head = part.head(&boundary);
+            content_length = match (content_length, &part.body) {
+                (Some(len), PartBody::Bytes(bytes)) => {
+                    Some(len + (head.len() + bytes.len() + 2) as u64)
+                }
+                _ => None,
+            };
+            let contents = match part.body {
+                PartBody::Bytes(bytes) => chunk(bytes).boxed(),
+                PartBody::Stream(stream) => stream,
+            };
+            body.push(
+                chunk(Bytes::from(head))
+                    .chain(contents)
+                    .chain(chunk(Bytes::from_static(b"\r\n"))),
+            );
+        }
+
+        let tail = format!("--{}--\r\n", boundary);
+        let content_length = content_length.map(|len| len + tail.len() as u64);
+        let body = stream::iter(body).flatten().chain(chunk(Bytes::from(tail)));
+        Ok((content_type, content_length, Body::wrap_stream(body)))
+    }
+}
+
+impl Reply for Multipart {
+    fn into_response(self) -> Response {
+        match self.into_body() {
+            Ok((content_type, content_length, body)) => {
+                let mut res = Response::new(body);
+                res.headers_mut().insert(CONTENT_TYPE, content_type);
+                if let Some(len) = content_length {
+                    res.headers_mut()
+                        .insert(CONTENT_LENGTH, HeaderValue::from(len));
+                }
+                res
+            }
+            Err(()) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
+
+/// A part of a [`Multipart`] reply.
+///
+/// A part without a `content-type` header is `text/plain` to the client.
+#[allow(missing_debug_implementations)]
+pub struct MultipartPart {
+    headers: HeaderMap,
+    name: Option<String>,
+    filename: Option<String>,
+    body: PartBody,
+    // Set when a header or the JSON body couldn't be created, the error was
+    // logged then, and the whole reply is a 500.
+    invalid: bool,
+}
+
+enum PartBody {
+    Bytes(Bytes),
+    Stream(BoxStream<'static, Result<Bytes, BoxError>>),
+}
+
+impl MultipartPart {
+    /// Creates a part with the given body.
+    pub fn new(body: impl Into<Bytes>) -> Self {
+        MultipartPart::with_body(PartBody::Bytes(body.into()))
+    }
+
+    /// Creates a part with a body streamed from `stream`.
+    ///
+    /// If the stream yields an error, the response body is cut short.
+    pub fn stream<S, B, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<B, E>> + Send + 'static,
+        B: Into<Bytes> + 'static,
+        E: Into<BoxError> + 'static,
+    {
+        let stream = stream.map_ok(Into::into).map_err(Into::into).boxed();
+        MultipartPart::with_body(PartBody::Stream(stream))
+    }
+
+    /// Creates a part with the value encoded as JSON, and `content-type` set
+    /// to `application/json`.
+    ///
+    /// If the value fails to be serialized, the error is logged at the
+    /// `error` level, and the reply will be an empty
+    /// `500 Internal Server Error` response.
+    pub fn json<T: Serialize>(val: &T) -> Self {
+        match serde_json::to_vec(val) {
+            Ok(body) => MultipartPart::new(body).content_type("application/json"),
+            Err(err) => {
+                tracing::error!("reply::multipart json error: {}", err);
+                let mut part = MultipartPart::new(Bytes::new());
+                part.invalid = true;
+                part
+            }
+        }
+    }
+
+    fn with_body(body: PartBody) -> Self {
+        MultipartPart {
+            headers: HeaderMap::new(),
+            name: None,
+            filename: None,
+            body,
+            invalid: false,
+        }
+    }
+
+    /// Sets the `content-type` of this part.
+    pub fn content_type(self, content_type: &str) -> Self {
+        self.header(CONTENT_TYPE, content_type)
+    }
+
+    /// Adds a header to this part.
+    ///
+    /// An invalid name or value is logged at the `error` level, and the reply
+    /// will be an empty `500 Internal Server Error` response.
+    pub fn header<K, V>(mut self, name: K, value: V) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: Into<http::Error>,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+    {
+        let name = match <HeaderName as TryFrom<K>>::try_from(name) {
+            Ok(name) => name,
+            Err(err) => {
+                tracing::error!("reply::multipart header name error: {}", err.into());
+                self.invalid = true;
+                return self;
+            }
+        };
+        match <HeaderValue as TryFrom<V>>::try_from(value) {
+            Ok(value) => {
+                self.headers.append(name, value);
+            }
+            Err(err) => {
+                tracing::error!("reply::multipart header value error: {}", err.into());
+                self.invalid = true;
+            }
+        }
+        self
+    }
+
+    /// Sets the name of this part, for a `multipart/form-data` reply.
+    ///
+    /// It's sent in a `content-disposition: form-data` header, unless the
+    /// part has a `content-disposition` header already.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the filename of this part.
+    ///
+    /// It's sent in the `content-disposition` header, which is `attachment`
+    /// for a part without a [name](MultipartPart::name).
+    pub fn filename(mut self, filename: impl Into<String>) -> Self {
+        self.filename = Some(filename.into());
+        self
+    }
+
+    // The boundary and headers before the body.
+    fn head(&self, boundary: &str) -> Vec<u8> {
+        let mut head = format!("--{}\r\n", boundary).into_bytes();
+        if !self.headers.contains_key(CONTENT_DISPOSITION) {
+            let disposition = match (&self.name, &self.filename) {
+                (Some(name), _) => Some(format!("form-data; name=\"{}\"", quote(name))),
+                (None, Some(_)) => Some("attachment".to_owned()),
+                (None, None) => None,
+            };
+            if let Some(mut disposition) = disposition {
+                if let Some(ref filename) = self.filename {
+                    disposition.push_str(&format!("; filename=\"{}\"", quote(filename)));
+                }
+                head.extend_from_slice(b"content-disposition: ");
+                head.extend_from_slice(disposition.as_bytes());
+                head.extend_from_slice(b"\r\n");
+            }
+        }
+        for (name, value) in &self.headers {
+            head.extend_from_slice(name.as_str().as_bytes());
+            head.extend_from_slice(b": ");
+            head.extend_from_slice(value.as_bytes());
+            head.extend_from_slice(b"\r\n");
+        }
+        head.extend_from_slice(b"\r\n");
+        head
+    }
+}
+
+fn chunk(bytes: Bytes) -> BoxStream<'static, Result<Bytes, BoxError>> {
+    stream::once(future::ok(bytes)).boxed()
+}
+
+// Escapes a `content-disposition` parameter the way browsers do for form
+// submissions.
+fn quote(param: &str) -> String {
+    param
+        .replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+// RFC 2046 allows spaces too, but they would need the boundary quoted.
+fn is_valid_boundary(boundary: &str) -> bool {
+    (1..=70).contains(&boundary.len())
+        && boundary
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"'()+_,-./:=?".contains(&b))
+}
+
+// The boundary has to be unlikely to appear in the parts, including ones a
+// client controls, so it's derived from the randomly seeded std hasher.
+fn multipart_boundary() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let state = RandomState::new();
+    let hash = || {
+        let mut hasher = state.build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        hasher.finish()
+    };
+    format!("{:016x}{:016x}", hash(), hash())
+}
+
 /// Types that can be converted into a `Response`.
 ///
 /// This trait is implemented for the following:
@@ -5,6 +5,7 @@ use std::net::SocketAddr;
 
 use hyper::Body;
 
+use crate::transport::ConnInfo;
 use crate::Request;
 
 scoped_thread_local!(static ROUTE: RefCell<Route>);
@@ -30,7 +31,7 @@ where
 #[derive(Debug)]
 pub(crate) struct Route {
     body: BodyState,
-    remote_addr: Option<SocketAddr>,
+    conn: ConnInfo,
     req: Request,
     segments_index: usize,
 }
@@ -42,7 +43,7 @@ enum BodyState {
 }
 
 impl Route {
-    pub(crate) fn new(req: Request, remote_addr: Option<SocketAddr>) -> RefCell<Route> {
+    pub(crate) fn new(req: Request, conn: ConnInfo) -> RefCell<Route> {
         let segments_index = if req.uri().path().starts_with('/') {
             // Skip the beginning slash.
             1
@@ -52,7 +53,7 @@ impl Route {
 
         RefCell::new(Route {
             body: BodyState::Ready,
-            remote_addr,
+            conn,
             req,
             segments_index,
         })
@@ -66,6 +67,15 @@ impl Route {
         self.req.headers()
     }
 
+    #[cfg(any(
+        feature = "compression-brotli",
+        feature = "compression-gzip",
+        feature = "compression-zstd"
+    ))]
+    pub(crate) fn headers_mut(&mut self) -> &mut http::HeaderMap {
+        self.req.headers_mut()
+    }
+
     pub(crate) fn version(&self) -> http::Version {
         self.req.version()
     }
@@ -123,7 +133,11 @@ impl Route {
     }
 
     pub(crate) fn remote_addr(&self) -> Option<SocketAddr> {
-        self.remote_addr
+        self.conn.remote_addr
+    }
+
+    pub(crate) fn conn_extensions(&self) -> &http::Extensions {
+        &self.conn.extensions
     }
 
     pub(crate) fn take_body(&mut self) -> Option<Body> {
@@ -136,4 +150,14 @@ impl Route {
             BodyState::Taken => None,
         }
     }
+
+    #[cfg(any(
+        feature = "compression-brotli",
+        feature = "compression-gzip",
+        feature = "compression-zstd"
+    ))]
+    pub(crate) fn put_body(&mut self, body: Body) {
+        *self.req.body_mut() = body;
+        self.body = BodyState::Ready;
+    }
 }
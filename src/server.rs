@@ -2,22 +2,32 @@
 use crate::tls::TlsConfigBuilder;
 use std::convert::Infallible;
 use std::error::Error as StdError;
+use std::fmt;
 use std::future::Future;
+use std::io;
 use std::net::SocketAddr;
-#[cfg(feature = "tls")]
+#[cfg(any(feature = "tls", unix))]
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
 use futures_util::{future, FutureExt, TryFuture, TryStream, TryStreamExt};
 use hyper::server::conn::AddrIncoming;
+use hyper::server::Builder as HyperBuilder;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::Server as HyperServer;
 use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpSocket;
 use tracing::Instrument;
 
 use crate::filter::Filter;
+use crate::filters::addr::Cidr;
 use crate::reject::IsReject;
 use crate::reply::Reply;
-use crate::transport::Transport;
+use crate::tcp::{ProxyOptions, ProxyTrust, TcpIncoming};
+#[cfg(feature = "tls")]
+use crate::tls::TlsConfigError;
+use crate::transport::{ConnInfo, Transport};
 
 /// Create a `Server` with the provided `Filter`.
 pub fn serve<F>(filter: F) -> Server<F>
@@ -28,6 +38,7 @@ where
 {
     Server {
         pipeline: false,
+        config: ServerConfig::default(),
         filter,
     }
 }
@@ -36,6 +47,7 @@ where
 #[derive(Debug)]
 pub struct Server<F> {
     pipeline: bool,
+    config: ServerConfig,
     filter: F,
 }
 
@@ -55,18 +67,17 @@ macro_rules! into_service {
         let inner = crate::service($into);
         make_service_fn(move |transport| {
             let inner = inner.clone();
-            let remote_addr = Transport::remote_addr(transport);
+            let conn = ConnInfo::from_transport(transport);
             future::ok::<_, Infallible>(service_fn(move |req| {
-                inner.call_with_addr(req, remote_addr)
+                inner.call_with_conn(req, conn.clone())
             }))
         })
     }};
 }
 
 macro_rules! addr_incoming {
-    ($addr:expr) => {{
-        let mut incoming = AddrIncoming::bind($addr)?;
-        incoming.set_nodelay(true);
+    ($config:expr, $addr:expr) => {{
+        let incoming = $config.incoming($addr)?;
         let addr = incoming.local_addr();
         (addr, incoming)
     }};
@@ -75,21 +86,42 @@ macro_rules! addr_incoming {
 macro_rules! bind_inner {
     ($this:ident, $addr:expr) => {{
         let service = into_service!($this.filter);
-        let (addr, incoming) = addr_incoming!($addr);
-        let srv = HyperServer::builder(incoming)
+        let (addr, incoming) = addr_incoming!($this.config, $addr);
+        let srv = $this
+            .config
+            .builder(incoming)
             .http1_pipeline_flush($this.pipeline)
             .serve(service);
-        Ok::<_, hyper::Error>((addr, srv))
+        Ok::<_, BindError>((addr, srv))
     }};
 
     (tls: $this:ident, $addr:expr) => {{
         let service = into_service!($this.server.filter);
-        let (addr, incoming) = addr_incoming!($addr);
+        let (addr, incoming) = addr_incoming!($this.server.config, $addr);
         let tls = $this.tls.build()?;
-        let srv = HyperServer::builder(crate::tls::TlsAcceptor::new(tls, incoming))
+        let srv = $this
+            .server
+            .config
+            .builder(crate::tls::TlsAcceptor::new(
+                tls,
+                incoming,
+                $this.server.config.tls_handshake_timeout,
+                $this.server.config.max_pending_handshakes,
+            ))
             .http1_pipeline_flush($this.server.pipeline)
             .serve(service);
-        Ok::<_, Box<dyn std::error::Error + Send + Sync>>((addr, srv))
+        Ok::<_, BindError>((addr, srv))
+    }};
+
+    (unix: $this:ident, $path:expr) => {{
+        let service = into_service!($this.filter);
+        let incoming = crate::unix::UnixIncoming::bind($path, $this.config.unix_socket_mode)?;
+        let srv = $this
+            .config
+            .builder(incoming)
+            .http1_pipeline_flush($this.pipeline)
+            .serve(service);
+        Ok::<_, BindError>(srv)
     }};
 }
 
@@ -107,6 +139,13 @@ macro_rules! bind {
             panic!("error binding to {}: {}", addr, e);
         })
     }};
+
+    (unix: $this:ident, $path:expr) => {{
+        let path = $path;
+        (|path| bind_inner!(unix: $this, path))(path).unwrap_or_else(|e| {
+            panic!("error binding to {}: {}", path.display(), e);
+        })
+    }};
 }
 
 macro_rules! try_bind {
@@ -117,6 +156,10 @@ macro_rules! try_bind {
     (tls: $this:ident, $addr:expr) => {{
         (|addr| bind_inner!(tls: $this, addr))($addr)
     }};
+
+    (unix: $this:ident, $path:expr) => {{
+        (|path| bind_inner!(unix: $this, path))($path)
+    }};
 }
 
 // ===== impl Server =====
@@ -140,6 +183,45 @@ where
         fut.instrument(span).await;
     }
 
+    /// Run this `Server` forever on the current thread, unless binding to
+    /// the provided address fails.
+    ///
+    /// This is the same as [`Server::run`], without the panic.
+    pub async fn try_run(self, addr: impl Into<SocketAddr>) -> Result<(), BindError> {
+        let addr = addr.into();
+        let (addr, srv) = try_bind!(self, &addr)?;
+        let fut = srv.map(|result| {
+            if let Err(err) = result {
+                tracing::error!("server error: {}", err)
+            }
+        });
+        let span = tracing::info_span!("Server::run", ?addr);
+        tracing::info!(parent: &span, "listening on http://{}", addr);
+
+        fut.instrument(span).await;
+        Ok(())
+    }
+
+    /// Run this `Server` forever on the current thread, listening on a Unix
+    /// domain socket at `path`.
+    ///
+    /// See [`Server::bind_unix`].
+    ///
+    /// *This function is only available on Unix platforms.*
+    ///
+    /// # Panics
+    ///
+    /// Panics if we are unable to bind to the provided path.
+    #[cfg(unix)]
+    pub async fn run_unix(self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let span = tracing::info_span!("Server::run_unix", path = %path.display());
+        let fut = self.bind_unix(path);
+        tracing::info!(parent: &span, "listening on unix:{}", path.display());
+
+        fut.instrument(span).await;
+    }
+
     /// Run this `Server` forever on the current thread with a specific stream
     /// of incoming connections.
     ///
@@ -227,7 +309,7 @@ where
     /// Tried to bind a possibly ephemeral socket address.
     ///
     /// Returns a `Result` which fails in case we are unable to bind with the
-    /// underlying error.
+    /// underlying error, a [`BindError`].
     ///
     /// Returns the bound address and a `Future` that can be executed on
     /// the current runtime.
@@ -236,7 +318,7 @@ where
         addr: impl Into<SocketAddr>,
     ) -> Result<(SocketAddr, impl Future<Output = ()> + 'static), crate::Error> {
         let addr = addr.into();
-        let (addr, srv) = try_bind!(self, &addr).map_err(crate::Error::new)?;
+        let (addr, srv) = try_bind!(self, &addr)?;
         let srv = srv.map(|result| {
             if let Err(err) = result {
                 tracing::error!("server error: {}", err)
@@ -246,6 +328,109 @@ where
         Ok((addr, srv))
     }
 
+    /// Bind to a Unix domain socket at `path`, returning a `Future` that can
+    /// be executed on the current runtime.
+    ///
+    /// A socket file left at `path` by a server that is no longer running is
+    /// replaced, and the socket file is removed when the `Future` is dropped.
+    /// Its permissions can be set with [`ServerConfig::unix_socket_mode`].
+    ///
+    /// Filters can read the peer credentials with
+    /// [`addr::unix_peer`](crate::addr::unix_peer).
+    ///
+    /// *This function is only available on Unix platforms.*
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use starterm::Filter;
+    ///
+    /// # async fn run() {
+    /// let routes = starterm::any().map(|| "Hello, World!");
+    ///
+    /// starterm::serve(routes).bind_unix("/tmp/starterm.sock").await;
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if we are unable to bind to the provided path.
+    #[cfg(unix)]
+    pub fn bind_unix(self, path: impl AsRef<Path>) -> impl Future<Output = ()> + 'static {
+        let srv = bind!(unix: self, path.as_ref());
+        srv.map(|result| {
+            if let Err(err) = result {
+                tracing::error!("server error: {}", err)
+            }
+        })
+    }
+
+    /// Tries to bind to a Unix domain socket at `path`.
+    ///
+    /// Returns a `Result` which fails in case we are unable to bind, or a
+    /// running server already listens at `path`.
+    ///
+    /// See [`Server::bind_unix`].
+    ///
+    /// *This function is only available on Unix platforms.*
+    #[cfg(unix)]
+    pub fn try_bind_unix(
+        self,
+        path: impl AsRef<Path>,
+    ) -> Result<impl Future<Output = ()> + 'static, BindError> {
+        let srv = try_bind!(unix: self, path.as_ref())?;
+        Ok(srv.map(|result| {
+            if let Err(err) = result {
+                tracing::error!("server error: {}", err)
+            }
+        }))
+    }
+
+    /// Bind to a Unix domain socket at `path`, with a graceful shutdown
+    /// signal.
+    ///
+    /// See [`Server::bind_unix`] and [`Server::bind_with_graceful_shutdown`].
+    ///
+    /// *This function is only available on Unix platforms.*
+    ///
+    /// # Panics
+    ///
+    /// Panics if we are unable to bind to the provided path.
+    #[cfg(unix)]
+    pub fn bind_unix_with_graceful_shutdown(
+        self,
+        path: impl AsRef<Path>,
+        signal: impl Future<Output = ()> + Send + 'static,
+    ) -> impl Future<Output = ()> + 'static {
+        let srv = bind!(unix: self, path.as_ref());
+        srv.with_graceful_shutdown(signal).map(|result| {
+            if let Err(err) = result {
+                tracing::error!("server error: {}", err)
+            }
+        })
+    }
+
+    /// Tries to bind to a Unix domain socket at `path`, with a graceful
+    /// shutdown signal.
+    ///
+    /// See [`Server::try_bind_unix`] and
+    /// [`Server::bind_with_graceful_shutdown`].
+    ///
+    /// *This function is only available on Unix platforms.*
+    #[cfg(unix)]
+    pub fn try_bind_unix_with_graceful_shutdown(
+        self,
+        path: impl AsRef<Path>,
+        signal: impl Future<Output = ()> + Send + 'static,
+    ) -> Result<impl Future<Output = ()> + 'static, BindError> {
+        let srv = try_bind!(unix: self, path.as_ref())?;
+        Ok(srv.with_graceful_shutdown(signal).map(|result| {
+            if let Err(err) = result {
+                tracing::error!("server error: {}", err)
+            }
+        }))
+    }
+
     /// Create a server with graceful shutdown signal.
     ///
     /// When the signal completes, the server will start the graceful shutdown
@@ -307,7 +492,7 @@ where
         signal: impl Future<Output = ()> + Send + 'static,
     ) -> Result<(SocketAddr, impl Future<Output = ()> + 'static), crate::Error> {
         let addr = addr.into();
-        let (addr, srv) = try_bind!(self, &addr).map_err(crate::Error::new)?;
+        let (addr, srv) = try_bind!(self, &addr)?;
         let srv = srv.with_graceful_shutdown(signal).map(|result| {
             if let Err(err) = result {
                 tracing::error!("server error: {}", err)
@@ -353,24 +538,90 @@ where
         I::Error: Into<Box<dyn StdError + Send + Sync>>,
     {
         let incoming = incoming.map_ok(crate::transport::LiftIo);
+        self.serve_incoming2_with_graceful_shutdown(incoming, signal)
+            .instrument(tracing::info_span!(
+                "Server::serve_incoming_with_graceful_shutdown"
+            ))
+    }
+
+    /// Run this `Server` forever on the current thread with a specific stream
+    /// of incoming connections, which provide information about themselves
+    /// with [`Transport`].
+    ///
+    /// Filters can read the information with [`conn::info`](crate::conn::info).
+    pub async fn run_transport<I>(self, incoming: I)
+    where
+        I: TryStream + Send,
+        I::Ok: Transport + Send + 'static + Unpin,
+        I::Error: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        self.run_incoming2(incoming)
+            .instrument(tracing::info_span!("Server::run_transport"))
+            .await;
+    }
+
+    /// Setup this `Server` with a specific stream of incoming connections,
+    /// which provide information about themselves with [`Transport`].
+    ///
+    /// Filters can read the information with [`conn::info`](crate::conn::info).
+    ///
+    /// Returns a `Future` that can be executed on the current runtime.
+    pub fn serve_transport<I>(self, incoming: I) -> impl Future<Output = ()>
+    where
+        I: TryStream + Send,
+        I::Ok: Transport + Send + 'static + Unpin,
+        I::Error: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        self.serve_incoming2(incoming)
+            .instrument(tracing::info_span!("Server::serve_transport"))
+    }
+
+    /// Setup this `Server` with a specific stream of incoming connections,
+    /// which provide information about themselves with [`Transport`], and a
+    /// signal to initiate graceful shutdown.
+    ///
+    /// When the signal completes, the server will start the graceful shutdown
+    /// process.
+    ///
+    /// Returns a `Future` that can be executed on the current runtime.
+    pub fn serve_transport_with_graceful_shutdown<I>(
+        self,
+        incoming: I,
+        signal: impl Future<Output = ()> + Send + 'static,
+    ) -> impl Future<Output = ()>
+    where
+        I: TryStream + Send,
+        I::Ok: Transport + Send + 'static + Unpin,
+        I::Error: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        self.serve_incoming2_with_graceful_shutdown(incoming, signal)
+            .instrument(tracing::info_span!(
+                "Server::serve_transport_with_graceful_shutdown"
+            ))
+    }
+
+    async fn serve_incoming2_with_graceful_shutdown<I>(
+        self,
+        incoming: I,
+        signal: impl Future<Output = ()> + Send + 'static,
+    ) where
+        I: TryStream + Send,
+        I::Ok: Transport + Send + 'static + Unpin,
+        I::Error: Into<Box<dyn StdError + Send + Sync>>,
+    {
         let service = into_service!(self.filter);
-        let pipeline = self.pipeline;
-
-        async move {
-            let srv =
-                HyperServer::builder(hyper::server::accept::from_stream(incoming.into_stream()))
-                    .http1_pipeline_flush(pipeline)
-                    .serve(service)
-                    .with_graceful_shutdown(signal)
-                    .await;
-
-            if let Err(err) = srv {
-                tracing::error!("server error: {}", err);
-            }
+
+        let srv = self
+            .config
+            .builder(hyper::server::accept::from_stream(incoming.into_stream()))
+            .http1_pipeline_flush(self.pipeline)
+            .serve(service)
+            .with_graceful_shutdown(signal)
+            .await;
+
+        if let Err(err) = srv {
+            tracing::error!("server error: {}", err);
         }
-        .instrument(tracing::info_span!(
-            "Server::serve_incoming_with_graceful_shutdown"
-        ))
     }
 
     async fn serve_incoming2<I>(self, incoming: I)
@@ -381,7 +632,9 @@ where
     {
         let service = into_service!(self.filter);
 
-        let srv = HyperServer::builder(hyper::server::accept::from_stream(incoming.into_stream()))
+        let srv = self
+            .config
+            .builder(hyper::server::accept::from_stream(incoming.into_stream()))
             .http1_pipeline_flush(self.pipeline)
             .serve(service)
             .await;
@@ -391,6 +644,32 @@ where
         }
     }
 
+    /// Configure the HTTP and TCP options of this `Server`.
+    ///
+    /// The TCP options only apply when this server binds its own socket, not
+    /// with `serve_incoming` or `run_incoming`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use starterm::{Filter, ServerConfig};
+    ///
+    /// let routes = starterm::any().map(|| "Hello, World!");
+    ///
+    /// let server = starterm::serve(routes).config(
+    ///     ServerConfig::new()
+    ///         .http1_header_read_timeout(Duration::from_secs(10))
+    ///         .http2_max_concurrent_streams(250)
+    ///         .tcp_keepalive(Duration::from_secs(60))
+    ///         .tcp_backlog(4096),
+    /// );
+    /// ```
+    pub fn config(mut self, config: ServerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
     // Generally shouldn't be used, as it can slow down non-pipelined responses.
     //
     // It's only real use is to make silly pipeline benchmarks look better.
@@ -498,6 +777,16 @@ where
         self.with_tls(|tls| tls.ocsp_resp(resp.as_ref()))
     }
 
+    /// Configure the HTTP and TCP options of this `TlsServer`.
+    ///
+    /// See [`Server::config`].
+    ///
+    /// *This function requires the `"tls"` feature.*
+    pub fn config(mut self, config: ServerConfig) -> Self {
+        self.server.config = config;
+        self
+    }
+
     fn with_tls<Func>(self, func: Func) -> Self
     where
         Func: FnOnce(TlsConfigBuilder) -> TlsConfigBuilder,
@@ -520,6 +809,27 @@ where
         fut.instrument(span).await;
     }
 
+    /// Run this `TlsServer` forever on the current thread, unless binding to
+    /// the provided address or the TLS configuration fails.
+    ///
+    /// This is the same as [`TlsServer::run`], without the panic.
+    ///
+    /// *This function requires the `"tls"` feature.*
+    pub async fn try_run(self, addr: impl Into<SocketAddr>) -> Result<(), BindError> {
+        let addr = addr.into();
+        let (addr, srv) = try_bind!(tls: self, &addr)?;
+        let fut = srv.map(|result| {
+            if let Err(err) = result {
+                tracing::error!("server error: {}", err)
+            }
+        });
+        let span = tracing::info_span!("TlsServer::run", %addr);
+        tracing::info!(parent: &span, "listening on https://{}", addr);
+
+        fut.instrument(span).await;
+        Ok(())
+    }
+
     /// Bind to a socket address, returning a `Future` that can be
     /// executed on a runtime.
     ///
@@ -527,12 +837,38 @@ where
     ///
     /// # Panics
     ///
-    /// Panics if we are unable to bind to the provided address.
+    /// Panics if we are unable to bind to the provided address, or the TLS
+    /// configuration is invalid.
     pub async fn bind(self, addr: impl Into<SocketAddr>) {
         let (_, fut) = self.bind_ephemeral(addr);
         fut.await;
     }
 
+    /// Bind to a socket address, returning a `Future` that can be
+    /// executed on any runtime.
+    ///
+    /// In case we are unable to bind to the specified address, or the TLS
+    /// configuration is invalid, resolves to an error and logs the reason.
+    ///
+    /// *This function requires the `"tls"` feature.*
+    pub async fn try_bind(self, addr: impl Into<SocketAddr>) {
+        let addr = addr.into();
+        let srv = match try_bind!(tls: self, &addr) {
+            Ok((_, srv)) => srv,
+            Err(err) => {
+                tracing::error!("error binding to {}: {}", addr, err);
+                return;
+            }
+        };
+
+        srv.map(|result| {
+            if let Err(err) = result {
+                tracing::error!("server error: {}", err)
+            }
+        })
+        .await;
+    }
+
     /// Bind to a possibly ephemeral socket address.
     ///
     /// Returns the bound address and a `Future` that can be executed on
@@ -542,7 +878,8 @@ where
     ///
     /// # Panics
     ///
-    /// Panics if we are unable to bind to the provided address.
+    /// Panics if we are unable to bind to the provided address, or the TLS
+    /// configuration is invalid.
     pub fn bind_ephemeral(
         self,
         addr: impl Into<SocketAddr>,
@@ -557,6 +894,30 @@ where
         (addr, srv)
     }
 
+    /// Tries to bind a possibly ephemeral socket address.
+    ///
+    /// Returns a `Result` which fails in case we are unable to bind to the
+    /// address, or the TLS configuration is invalid.
+    ///
+    /// Returns the bound address and a `Future` that can be executed on
+    /// the current runtime.
+    ///
+    /// *This function requires the `"tls"` feature.*
+    pub fn try_bind_ephemeral(
+        self,
+        addr: impl Into<SocketAddr>,
+    ) -> Result<(SocketAddr, impl Future<Output = ()> + 'static), crate::Error> {
+        let addr = addr.into();
+        let (addr, srv) = try_bind!(tls: self, &addr)?;
+        let srv = srv.map(|result| {
+            if let Err(err) = result {
+                tracing::error!("server error: {}", err)
+            }
+        });
+
+        Ok((addr, srv))
+    }
+
     /// Create a server with graceful shutdown signal.
     ///
     /// When the signal completes, the server will start the graceful shutdown
@@ -566,7 +927,8 @@ where
     ///
     /// # Panics
     ///
-    /// Panics if we are unable to bind to the provided address.
+    /// Panics if we are unable to bind to the provided address, or the TLS
+    /// configuration is invalid.
     pub fn bind_with_graceful_shutdown(
         self,
         addr: impl Into<SocketAddr> + 'static,
@@ -594,7 +956,7 @@ where
         signal: impl Future<Output = ()> + Send + 'static,
     ) -> Result<(SocketAddr, impl Future<Output = ()> + 'static), crate::Error> {
         let addr = addr.into();
-        let (addr, srv) = try_bind!(tls: self, &addr).map_err(crate::Error::new)?;
+        let (addr, srv) = try_bind!(tls: self, &addr)?;
         let srv = srv.with_graceful_shutdown(signal).map(|result| {
             if let Err(err) = result {
                 tracing::error!("server error: {}", err)
@@ -616,3 +978,506 @@ where
             .finish()
     }
 }
+
+// ===== impl BindError =====
+
+/// An error binding a [`Server`], or a `TlsServer`.
+///
+/// Returned by the `try_run` methods, and the `try_bind_unix` variants.
+/// `try_bind_ephemeral` and `try_bind_with_graceful_shutdown` return a
+/// [`crate::Error`], whose [`source`](StdError::source) is the `BindError`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BindError {
+    /// The address is already in use.
+    AddrInUse(io::Error),
+    /// Binding the address isn't permitted, such as a privileged port
+    /// without the privileges.
+    PermissionDenied(io::Error),
+    /// Any other error binding the address.
+    Io(io::Error),
+    /// The TLS configuration is invalid.
+    ///
+    /// *This variant requires the `"tls"` feature.*
+    #[cfg(feature = "tls")]
+    Tls(TlsConfigError),
+}
+
+impl fmt::Display for BindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindError::AddrInUse(err) | BindError::PermissionDenied(err) | BindError::Io(err) => {
+                err.fmt(f)
+            }
+            #[cfg(feature = "tls")]
+            BindError::Tls(err) => write!(f, "invalid TLS config: {}", err),
+        }
+    }
+}
+
+impl StdError for BindError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            BindError::AddrInUse(err) | BindError::PermissionDenied(err) | BindError::Io(err) => {
+                Some(err)
+            }
+            #[cfg(feature = "tls")]
+            BindError::Tls(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for BindError {
+    fn from(err: io::Error) -> BindError {
+        match err.kind() {
+            io::ErrorKind::AddrInUse => BindError::AddrInUse(err),
+            io::ErrorKind::PermissionDenied => BindError::PermissionDenied(err),
+            _ => BindError::Io(err),
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+impl From<TlsConfigError> for BindError {
+    fn from(err: TlsConfigError) -> BindError {
+        BindError::Tls(err)
+    }
+}
+
+impl From<BindError> for crate::Error {
+    fn from(err: BindError) -> crate::Error {
+        crate::Error::new(err)
+    }
+}
+
+// ===== impl ServerConfig =====
+
+// hyper asserts that `http1_max_buf_size` is at least this.
+const MIN_HTTP1_MAX_BUF_SIZE: usize = 8192;
+
+// The backlog of `std::net::TcpListener::bind`, which hyper binds with.
+const DEFAULT_TCP_BACKLOG: u32 = 128;
+
+// A client that doesn't finish its handshake, or a proxy that doesn't send
+// its header, in time is dropped, so that idle connections can't hold up the
+// others.
+#[cfg(feature = "tls")]
+const DEFAULT_TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_PROXY_PROTOCOL_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_MAX_PENDING_HANDSHAKES: usize = 1024;
+
+/// HTTP and TCP options of a [`Server`].
+///
+/// Every option left unset keeps the default of hyper, or of
+/// `std::net::TcpListener` and the operating system for the TCP ones.
+/// `TCP_NODELAY` is enabled unless turned off.
+///
+/// See [`Server::config`].
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    http1_keep_alive: Option<bool>,
+    http1_header_read_timeout: Option<Duration>,
+    http1_max_buf_size: Option<usize>,
+    http1_only: bool,
+    http2_only: bool,
+    http2_initial_stream_window_size: Option<u32>,
+    http2_initial_connection_window_size: Option<u32>,
+    http2_adaptive_window: Option<bool>,
+    http2_max_frame_size: Option<u32>,
+    http2_max_concurrent_streams: Option<u32>,
+    http2_keep_alive_interval: Option<Duration>,
+    http2_keep_alive_timeout: Option<Duration>,
+    tcp_nodelay: bool,
+    tcp_keepalive: Option<Duration>,
+    tcp_keepalive_interval: Option<Duration>,
+    tcp_keepalive_retries: Option<u32>,
+    tcp_reuse_port: bool,
+    tcp_backlog: Option<u32>,
+    #[cfg(feature = "tls")]
+    tls_handshake_timeout: Option<Duration>,
+    max_pending_handshakes: usize,
+    proxy_protocol: bool,
+    // `None` trusts every peer.
+    proxy_protocol_trusted: Option<Vec<Cidr>>,
+    proxy_protocol_timeout: Option<Duration>,
+    #[cfg(unix)]
+    unix_socket_mode: Option<u32>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            http1_keep_alive: None,
+            http1_header_read_timeout: None,
+            http1_max_buf_size: None,
+            http1_only: false,
+            http2_only: false,
+            http2_initial_stream_window_size: None,
+            http2_initial_connection_window_size: None,
+            http2_adaptive_window: None,
+            http2_max_frame_size: None,
+            http2_max_concurrent_streams: None,
+            http2_keep_alive_interval: None,
+            http2_keep_alive_timeout: None,
+            tcp_nodelay: true,
+            tcp_keepalive: None,
+            tcp_keepalive_interval: None,
+            tcp_keepalive_retries: None,
+            tcp_reuse_port: false,
+            tcp_backlog: None,
+            #[cfg(feature = "tls")]
+            tls_handshake_timeout: Some(DEFAULT_TLS_HANDSHAKE_TIMEOUT),
+            max_pending_handshakes: DEFAULT_MAX_PENDING_HANDSHAKES,
+            proxy_protocol: false,
+            proxy_protocol_trusted: None,
+            proxy_protocol_timeout: Some(DEFAULT_PROXY_PROTOCOL_TIMEOUT),
+            #[cfg(unix)]
+            unix_socket_mode: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Creates a config with the default options.
+    pub fn new() -> Self {
+        ServerConfig::default()
+    }
+
+    /// Sets whether HTTP/1 connections are kept alive between requests.
+    ///
+    /// The default is `true`.
+    pub fn http1_keep_alive(mut self, enabled: bool) -> Self {
+        self.http1_keep_alive = Some(enabled);
+        self
+    }
+
+    /// Sets how long a client has to send the headers of an HTTP/1 request,
+    /// before the connection is closed.
+    ///
+    /// There is no timeout by default.
+    pub fn http1_header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.http1_header_read_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum size of the HTTP/1 read and write buffers, which also
+    /// limits the size of the request headers.
+    ///
+    /// The default is about 400 KiB. A size less than 8 KiB, the minimum
+    /// of hyper, is raised to 8 KiB.
+    pub fn http1_max_buf_size(mut self, size: usize) -> Self {
+        self.http1_max_buf_size = Some(size.max(MIN_HTTP1_MAX_BUF_SIZE));
+        self
+    }
+
+    /// Only accepts HTTP/1 connections.
+    pub fn http1_only(mut self) -> Self {
+        self.http1_only = true;
+        self.http2_only = false;
+        self
+    }
+
+    /// Only accepts HTTP/2 connections.
+    ///
+    /// Without TLS, clients have to use HTTP/2 with prior knowledge (h2c).
+    pub fn http2_only(mut self) -> Self {
+        self.http2_only = true;
+        self.http1_only = false;
+        self
+    }
+
+    /// Sets the HTTP/2 initial window size of each stream, in bytes.
+    ///
+    /// The default is 1 MiB.
+    pub fn http2_initial_stream_window_size(mut self, size: u32) -> Self {
+        self.http2_initial_stream_window_size = Some(size);
+        self
+    }
+
+    /// Sets the HTTP/2 initial window size of each connection, in bytes.
+    ///
+    /// The default is 1 MiB.
+    pub fn http2_initial_connection_window_size(mut self, size: u32) -> Self {
+        self.http2_initial_connection_window_size = Some(size);
+        self
+    }
+
+    /// Sets whether the HTTP/2 window sizes adapt to the bandwidth-delay
+    /// product, instead of the initial window sizes.
+    ///
+    /// The default is `false`.
+    pub fn http2_adaptive_window(mut self, enabled: bool) -> Self {
+        self.http2_adaptive_window = Some(enabled);
+        self
+    }
+
+    /// Sets the largest HTTP/2 frame a client may send, in bytes.
+    ///
+    /// The default is 16 KiB.
+    pub fn http2_max_frame_size(mut self, size: u32) -> Self {
+        self.http2_max_frame_size = Some(size);
+        self
+    }
+
+    /// Sets how many HTTP/2 streams a client may have open at once, on each
+    /// connection.
+    ///
+    /// There is no limit by default.
+    pub fn http2_max_concurrent_streams(mut self, max: u32) -> Self {
+        self.http2_max_concurrent_streams = Some(max);
+        self
+    }
+
+    /// Sends an HTTP/2 ping at this interval, to keep connections alive.
+    ///
+    /// Pings are off by default.
+    pub fn http2_keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.http2_keep_alive_interval = Some(interval);
+        self
+    }
+
+    /// Sets how long to wait for a ping to be acknowledged, before closing
+    /// the connection.
+    ///
+    /// Only applies with an [interval](ServerConfig::http2_keep_alive_interval).
+    /// The default is 20 seconds.
+    pub fn http2_keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.http2_keep_alive_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets `TCP_NODELAY` on accepted connections.
+    ///
+    /// The default is `true`.
+    pub fn tcp_nodelay(mut self, enabled: bool) -> Self {
+        self.tcp_nodelay = enabled;
+        self
+    }
+
+    /// Enables TCP keepalive on accepted connections, with probes sent after
+    /// they are idle for `idle`.
+    pub fn tcp_keepalive(mut self, idle: Duration) -> Self {
+        self.tcp_keepalive = Some(idle);
+        self
+    }
+
+    /// Sets the interval between TCP keepalive probes.
+    ///
+    /// Only applies with [`tcp_keepalive`](ServerConfig::tcp_keepalive).
+    pub fn tcp_keepalive_interval(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive_interval = Some(interval);
+        self
+    }
+
+    /// Sets how many TCP keepalive probes are sent before a connection is
+    /// dropped.
+    ///
+    /// Only applies with [`tcp_keepalive`](ServerConfig::tcp_keepalive).
+    pub fn tcp_keepalive_retries(mut self, retries: u32) -> Self {
+        self.tcp_keepalive_retries = Some(retries);
+        self
+    }
+
+    /// Sets `SO_REUSEPORT` on the listening socket, so that several servers
+    /// can bind the same address.
+    ///
+    /// Binding fails on platforms without `SO_REUSEPORT`.
+    pub fn tcp_reuse_port(mut self, enabled: bool) -> Self {
+        self.tcp_reuse_port = enabled;
+        self
+    }
+
+    /// Sets how many connections may wait to be accepted.
+    ///
+    /// The default is `128`, like `std::net::TcpListener::bind`. The
+    /// operating system may cap it lower.
+    pub fn tcp_backlog(mut self, backlog: u32) -> Self {
+        self.tcp_backlog = Some(backlog);
+        self
+    }
+
+    /// Sets how long a client has to finish its TLS handshake, before the
+    /// connection is closed.
+    ///
+    /// `tls_handshake_timeout(None)` means that there is no timeout, so an
+    /// idle client holds one of the
+    /// [pending handshakes](ServerConfig::max_pending_handshakes) until it
+    /// disconnects. The default is 10 seconds.
+    ///
+    /// *This function requires the `"tls"` feature.*
+    #[cfg(feature = "tls")]
+    pub fn tls_handshake_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.tls_handshake_timeout = timeout.into();
+        self
+    }
+
+    /// Sets how many TLS handshakes, and how many
+    /// [PROXY headers](ServerConfig::proxy_protocol), may be read at once.
+    ///
+    /// Handshakes and headers are read before a connection is handed to
+    /// hyper, concurrently, so that a slow client doesn't hold up the others.
+    /// Past this many, new connections wait to be accepted, in the
+    /// [backlog](ServerConfig::tcp_backlog). The default is `1024`, and `0`
+    /// is raised to `1`.
+    pub fn max_pending_handshakes(mut self, max: usize) -> Self {
+        self.max_pending_handshakes = max.max(1);
+        self
+    }
+
+    /// Requires a PROXY protocol header, version 1 or 2, at the start of
+    /// every TCP connection.
+    ///
+    /// This is for servers behind a load balancer that passes the address of
+    /// the client this way. The addresses of the header are then those of
+    /// [`addr::remote`](crate::addr::remote) and of the `log` filter. With
+    /// TLS, the header comes before the handshake.
+    ///
+    /// Connections with a missing or malformed header are closed. Anyone who
+    /// can connect directly could send a fake header, see
+    /// [`proxy_protocol_trusted`](ServerConfig::proxy_protocol_trusted).
+    pub fn proxy_protocol(mut self, enabled: bool) -> Self {
+        self.proxy_protocol = enabled;
+        self
+    }
+
+    /// Enables the [PROXY protocol](ServerConfig::proxy_protocol), and only
+    /// accepts connections from proxies with an address in `cidrs`.
+    ///
+    /// Connections from any other address are closed, so an empty list
+    /// closes every connection.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use starterm::ServerConfig;
+    ///
+    /// let config = ServerConfig::new()
+    ///     .proxy_protocol_trusted(vec!["10.0.0.0/8".parse().unwrap()]);
+    /// ```
+    pub fn proxy_protocol_trusted(mut self, cidrs: impl IntoIterator<Item = Cidr>) -> Self {
+        self.proxy_protocol = true;
+        self.proxy_protocol_trusted = Some(cidrs.into_iter().collect());
+        self
+    }
+
+    /// Sets how long a proxy has to send the
+    /// [PROXY header](ServerConfig::proxy_protocol), before the connection is
+    /// closed.
+    ///
+    /// `proxy_protocol_timeout(None)` means that there is no timeout, so an
+    /// idle connection holds one of the
+    /// [pending handshakes](ServerConfig::max_pending_handshakes) until it
+    /// is closed. The default is 10 seconds.
+    pub fn proxy_protocol_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.proxy_protocol_timeout = timeout.into();
+        self
+    }
+
+    /// Sets the permissions of the socket file created by
+    /// [`Server::bind_unix`], such as `0o660`.
+    ///
+    /// They are set before the socket is reachable at its path. By default,
+    /// they depend on the umask of the process.
+    ///
+    /// *This function is only available on Unix platforms.*
+    #[cfg(unix)]
+    pub fn unix_socket_mode(mut self, mode: u32) -> Self {
+        self.unix_socket_mode = Some(mode);
+        self
+    }
+
+    fn builder<I>(&self, incoming: I) -> HyperBuilder<I> {
+        // `None` keeps the defaults of hyper for these.
+        let mut builder = HyperServer::builder(incoming)
+            .http2_initial_stream_window_size(self.http2_initial_stream_window_size)
+            .http2_initial_connection_window_size(self.http2_initial_connection_window_size)
+            .http2_max_frame_size(self.http2_max_frame_size)
+            .http2_keep_alive_interval(self.http2_keep_alive_interval);
+        // Passing `false` to either would reset the other.
+        if self.http1_only {
+            builder = builder.http1_only(true);
+        }
+        if self.http2_only {
+            builder = builder.http2_only(true);
+        }
+        if let Some(enabled) = self.http1_keep_alive {
+            builder = builder.http1_keepalive(enabled);
+        }
+        if let Some(timeout) = self.http1_header_read_timeout {
+            builder = builder.http1_header_read_timeout(timeout);
+        }
+        if let Some(size) = self.http1_max_buf_size {
+            builder = builder.http1_max_buf_size(size);
+        }
+        if let Some(enabled) = self.http2_adaptive_window {
+            builder = builder.http2_adaptive_window(enabled);
+        }
+        if let Some(max) = self.http2_max_concurrent_streams {
+            builder = builder.http2_max_concurrent_streams(max);
+        }
+        if let Some(timeout) = self.http2_keep_alive_timeout {
+            builder = builder.http2_keep_alive_timeout(timeout);
+        }
+        builder
+    }
+
+    fn incoming(&self, addr: &SocketAddr) -> io::Result<TcpIncoming> {
+        let socket = match addr {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        // Like `std::net::TcpListener::bind`, to rebind while old connections
+        // are in TIME_WAIT.
+        #[cfg(unix)]
+        socket.set_reuseaddr(true)?;
+        if self.tcp_reuse_port {
+            set_reuse_port(&socket)?;
+        }
+        socket.bind(*addr)?;
+        let listener = socket.listen(self.tcp_backlog.unwrap_or(DEFAULT_TCP_BACKLOG))?;
+
+        let mut incoming = AddrIncoming::from_listener(listener).map_err(io::Error::other)?;
+        incoming
+            .set_nodelay(self.tcp_nodelay)
+            .set_keepalive(self.tcp_keepalive)
+            .set_keepalive_interval(self.tcp_keepalive_interval)
+            .set_keepalive_retries(self.tcp_keepalive_retries);
+        let proxy = if self.proxy_protocol {
+            let trust = match self.proxy_protocol_trusted {
+                Some(ref cidrs) => ProxyTrust::Only(Arc::from(&cidrs[..])),
+                None => ProxyTrust::Any,
+            };
+            Some(ProxyOptions {
+                trust,
+                timeout: self.proxy_protocol_timeout,
+                max_pending: self.max_pending_handshakes,
+            })
+        } else {
+            None
+        };
+        Ok(TcpIncoming::new(incoming, proxy))
+    }
+}
+
+#[cfg(all(
+    unix,
+    not(target_os = "solaris"),
+    not(target_os = "illumos"),
+    not(target_os = "cygwin"),
+))]
+fn set_reuse_port(socket: &TcpSocket) -> io::Result<()> {
+    socket.set_reuseport(true)
+}
+
+#[cfg(not(all(
+    unix,
+    not(target_os = "solaris"),
+    not(target_os = "illumos"),
+    not(target_os = "cygwin"),
+)))]
+fn set_reuse_port(_: &TcpSocket) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "SO_REUSEPORT is not supported on this platform",
+    ))
+}
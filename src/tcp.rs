@@ -0,0 +1,227 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::{Buf, Bytes};
+use futures_util::future::BoxFuture;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use hyper::server::accept::Accept;
+use hyper::server::conn::{AddrIncoming, AddrStream};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::filters::addr::Cidr;
+use crate::proxy_protocol::{self, ProxyAddrs};
+use crate::transport::Transport;
+
+/// Accepts TCP connections, reading their PROXY header first when the
+/// protocol is enabled, up to `max_pending` headers at once.
+pub(crate) struct TcpIncoming {
+    incoming: AddrIncoming,
+    // `None` if the PROXY protocol is disabled.
+    proxy: Option<ProxyOptions>,
+    headers: FuturesUnordered<BoxFuture<'static, Option<TcpConn>>>,
+}
+
+/// How PROXY headers are read.
+pub(crate) struct ProxyOptions {
+    pub(crate) trust: ProxyTrust,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) max_pending: usize,
+}
+
+impl TcpIncoming {
+    pub(crate) fn new(incoming: AddrIncoming, proxy: Option<ProxyOptions>) -> TcpIncoming {
+        TcpIncoming {
+            incoming,
+            proxy,
+            headers: FuturesUnordered::new(),
+        }
+    }
+
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.incoming.local_addr()
+    }
+}
+
+/// The peers a PROXY header is accepted from.
+#[derive(Clone)]
+pub(crate) enum ProxyTrust {
+    Any,
+    Only(Arc<[Cidr]>),
+}
+
+impl ProxyTrust {
+    fn contains(&self, ip: IpAddr) -> bool {
+        match self {
+            ProxyTrust::Any => true,
+            ProxyTrust::Only(cidrs) => cidrs.iter().any(|cidr| cidr.contains(ip)),
+        }
+    }
+}
+
+async fn read_header(
+    stream: AddrStream,
+    trust: ProxyTrust,
+    timeout: Option<Duration>,
+) -> Option<TcpConn> {
+    let mut conn = TcpConn::new(stream);
+    let peer = conn.remote_addr;
+    if !trust.contains(peer.ip()) {
+        tracing::debug!("closing connection from untrusted proxy {}", peer);
+        return None;
+    }
+    let header = proxy_protocol::read_header(&mut conn.io);
+    let result = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, header).await,
+        None => Ok(header.await),
+    };
+    match result {
+        Ok(Ok((addrs, rest))) => {
+            if let Some(ProxyAddrs {
+                source,
+                destination,
+            }) = addrs
+            {
+                conn.remote_addr = source;
+                conn.local_addr = destination;
+            }
+            conn.rest = rest;
+            Some(conn)
+        }
+        Ok(Err(err)) => {
+            tracing::debug!("invalid PROXY header from {}: {}", peer, err);
+            None
+        }
+        Err(_) => {
+            tracing::debug!("PROXY header from {} timed out", peer);
+            None
+        }
+    }
+}
+
+impl Accept for TcpIncoming {
+    type Conn = TcpConn;
+    type Error = io::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let pin = self.get_mut();
+        let proxy = match pin.proxy {
+            Some(ref proxy) => proxy,
+            None => {
+                let conn = futures_util::ready!(Pin::new(&mut pin.incoming).poll_accept(cx));
+                return Poll::Ready(conn.map(|conn| conn.map(TcpConn::new)));
+            }
+        };
+
+        let mut closed = false;
+        loop {
+            // Past `max_pending`, connections wait in the backlog, and this is
+            // woken by the headers instead.
+            while !closed && pin.headers.len() < proxy.max_pending {
+                match Pin::new(&mut pin.incoming).poll_accept(cx) {
+                    Poll::Ready(Some(Ok(stream))) => {
+                        let header = read_header(stream, proxy.trust.clone(), proxy.timeout);
+                        pin.headers.push(Box::pin(header));
+                    }
+                    Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                    Poll::Ready(None) => closed = true,
+                    Poll::Pending => break,
+                }
+            }
+
+            match pin.headers.poll_next_unpin(cx) {
+                Poll::Ready(Some(Some(conn))) => return Poll::Ready(Some(Ok(conn))),
+                // A closed connection makes room for another one.
+                Poll::Ready(Some(None)) => continue,
+                Poll::Ready(None) if closed => return Poll::Ready(None),
+                // Woken by `incoming` for new connections.
+                Poll::Ready(None) | Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// A connection accepted by `TcpIncoming`.
+///
+/// With the PROXY protocol, its addresses are those sent by the proxy.
+pub(crate) struct TcpConn {
+    io: AddrStream,
+    // Bytes read past the PROXY header.
+    rest: Bytes,
+    remote_addr: SocketAddr,
+    local_addr: SocketAddr,
+}
+
+impl TcpConn {
+    fn new(io: AddrStream) -> TcpConn {
+        TcpConn {
+            remote_addr: io.remote_addr(),
+            local_addr: io.local_addr(),
+            io,
+            rest: Bytes::new(),
+        }
+    }
+}
+
+impl Transport for TcpConn {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        Some(self.remote_addr)
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        Some(self.local_addr)
+    }
+}
+
+impl AsyncRead for TcpConn {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let pin = self.get_mut();
+        if !pin.rest.is_empty() {
+            let len = pin.rest.len().min(buf.remaining());
+            buf.put_slice(&pin.rest[..len]);
+            pin.rest.advance(len);
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut pin.io).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TcpConn {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().io).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().io).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+    }
+}
@@ -63,20 +63,22 @@
 //!
 //! ```
 //! # use starterm::Filter;
-//! #[test]
-//! fn test_math() {
+//! #[tokio::test]
+//! async fn test_math() {
 //! #    let math = || starterm::any().map(starterm::reply);
 //!     let filter = math();
 //!
 //!     let res = starterm::test::request()
 //!         .path("/1/2")
-//!         .reply(&filter);
+//!         .reply(&filter)
+//!         .await;
 //!     assert_eq!(res.status(), 405, "GET is not allowed");
 //!
 //!     let res = starterm::test::request()
 //!         .method("POST")
 //!         .path("/1/2")
-//!         .reply(&filter);
+//!         .reply(&filter)
+//!         .await;
 //!     assert_eq!(res.status(), 200);
 //!     assert_eq!(res.body(), "Sum is 3");
 //! }
@@ -101,7 +103,7 @@ use futures_util::StreamExt;
 use futures_util::{future, FutureExt, TryFutureExt};
 use http::{
     header::{HeaderName, HeaderValue},
-    Response,
+    Extensions, Response,
 };
 use serde::Serialize;
 #[cfg(feature = "websocket")]
@@ -111,8 +113,9 @@ use crate::filter::Filter;
 #[cfg(feature = "websocket")]
 use crate::filters::ws::Message;
 use crate::reject::IsReject;
-use crate::reply::Reply;
+use crate::reply::{Multipart, Reply};
 use crate::route::{self, Route};
+use crate::transport::ConnInfo;
 use crate::Request;
 #[cfg(feature = "websocket")]
 use crate::{Sink, Stream};
@@ -123,6 +126,7 @@ use self::inner::OneOrTuple;
 pub fn request() -> RequestBuilder {
     RequestBuilder {
         remote_addr: None,
+        conn: Extensions::new(),
         req: Request::default(),
     }
 }
@@ -140,6 +144,7 @@ pub fn ws() -> WsBuilder {
 #[derive(Debug)]
 pub struct RequestBuilder {
     remote_addr: Option<SocketAddr>,
+    conn: Extensions,
     req: Request,
 }
 
@@ -252,6 +257,26 @@ impl RequestBuilder {
         self
     }
 
+    /// Add a type to the information about the request's connection.
+    ///
+    /// Filters read it with [`conn::info`](crate::conn::info), like what a
+    /// [`Transport`](crate::Transport) provides.
+    ///
+    /// # Example
+    /// ```
+    /// use starterm::conn::TlsInfo;
+    ///
+    /// let req = starterm::test::request()
+    ///     .conn_info(TlsInfo::new().with_alpn_protocol("h2"));
+    /// ```
+    pub fn conn_info<T>(mut self, info: T) -> Self
+    where
+        T: Send + Sync + 'static,
+    {
+        self.conn.insert(info);
+        self
+    }
+
     /// Add a type to the request's `http::Extensions`.
     pub fn extension<T>(mut self, ext: T) -> Self
     where
@@ -294,6 +319,43 @@ impl RequestBuilder {
             .header("content-type", "application/json")
     }
 
+    /// Set the body of this request to a multipart body, and `content-type`
+    /// to its type and boundary.
+    ///
+    /// Use [`Multipart::form_data`] for a body the
+    /// [`multipart::form`](crate::multipart::form) filter accepts.
+    /// `content-length` is only set when no part is a stream.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use starterm::reply::MultipartPart;
+    ///
+    /// let form = starterm::reply::multipart()
+    ///     .form_data()
+    ///     .part(MultipartPart::new("hello").name("greeting"))
+    ///     .part(MultipartPart::new("data").name("file").filename("a.txt"));
+    ///
+    /// let req = starterm::test::request()
+    ///     .method("POST")
+    ///     .multipart(form);
+    /// ```
+    ///
+    /// # Panic
+    ///
+    /// This panics if a part or the boundary is invalid.
+    pub fn multipart(mut self, body: Multipart) -> Self {
+        let (content_type, len, body) = body
+            .into_body()
+            .expect("multipart() must be a valid multipart body");
+        *self.req.body_mut() = body;
+        let builder = match len {
+            Some(len) => self.header("content-length", len.to_string()),
+            None => self,
+        };
+        builder.header("content-type", content_type)
+    }
+
     /// Tries to apply the `Filter` on this request.
     ///
     /// # Example
@@ -375,7 +437,7 @@ impl RequestBuilder {
         // TODO: de-duplicate this and apply_filter()
         assert!(!route::is_set(), "nested test filter calls");
 
-        let route = Route::new(self.req, self.remote_addr);
+        let route = Route::new(self.req, ConnInfo::new(self.remote_addr, self.conn));
         let mut fut = Box::pin(
             route::set(&route, move || f.filter(crate::filter::Internal)).then(|result| {
                 let res = match result {
@@ -404,7 +466,7 @@ impl RequestBuilder {
     {
         assert!(!route::is_set(), "nested test filter calls");
 
-        let route = Route::new(self.req, self.remote_addr);
+        let route = Route::new(self.req, ConnInfo::new(self.remote_addr, self.conn));
         let mut fut = Box::pin(route::set(&route, move || {
             f.filter(crate::filter::Internal)
         }));
@@ -1,25 +1,31 @@
 use std::fmt;
 use std::fs::File;
-use std::future::Future;
 use std::io::{self, BufReader, Cursor, Read};
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
-use futures_util::ready;
+use futures_util::future::BoxFuture;
+use futures_util::stream::{FuturesUnordered, StreamExt};
 use hyper::server::accept::Accept;
-use hyper::server::conn::{AddrIncoming, AddrStream};
 use tokio_rustls::rustls::server::WebPkiClientVerifier;
 use tokio_rustls::rustls::{Error as TlsError, RootCertStore, ServerConfig};
 
+use crate::filters::conn::TlsInfo;
+use crate::tcp::{TcpConn, TcpIncoming};
 use crate::transport::Transport;
 
 /// Represents errors that can occur building the TlsConfig
+///
+/// *This type requires the `"tls"` feature.*
 #[derive(Debug)]
-pub(crate) enum TlsConfigError {
+#[non_exhaustive]
+pub enum TlsConfigError {
+    /// An error reading a key, certificate or trust anchor
     Io(io::Error),
     /// An Error parsing the Certificate
     CertParseError,
@@ -52,7 +58,15 @@ impl fmt::Display for TlsConfigError {
     }
 }
 
-impl std::error::Error for TlsConfigError {}
+impl std::error::Error for TlsConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TlsConfigError::Io(err) => Some(err),
+            TlsConfigError::InvalidKey(err) => Some(err),
+            _ => None,
+        }
+    }
+}
 
 /// Tls client authentication configuration.
 pub(crate) enum TlsClientAuth {
@@ -280,33 +294,35 @@ impl Read for LazyFile {
     }
 }
 
+/// A TLS connection, after its handshake.
+pub(crate) struct TlsStream {
+    inner: tokio_rustls::server::TlsStream<TcpConn>,
+    remote_addr: SocketAddr,
+    local_addr: SocketAddr,
+}
+
 impl Transport for TlsStream {
     fn remote_addr(&self) -> Option<SocketAddr> {
         Some(self.remote_addr)
     }
-}
 
-enum State {
-    Handshaking(tokio_rustls::Accept<AddrStream>),
-    Streaming(tokio_rustls::server::TlsStream<AddrStream>),
-}
-
-// tokio_rustls::server::TlsStream doesn't expose constructor methods,
-// so we have to TlsAcceptor::accept and handshake to have access to it
-// TlsStream implements AsyncRead/AsyncWrite handshaking tokio_rustls::Accept first
-pub(crate) struct TlsStream {
-    state: State,
-    remote_addr: SocketAddr,
-}
+    fn local_addr(&self) -> Option<SocketAddr> {
+        Some(self.local_addr)
+    }
 
-impl TlsStream {
-    fn new(stream: AddrStream, config: Arc<ServerConfig>) -> TlsStream {
-        let remote_addr = stream.remote_addr();
-        let accept = tokio_rustls::TlsAcceptor::from(config).accept(stream);
-        TlsStream {
-            state: State::Handshaking(accept),
-            remote_addr,
+    fn tls_info(&self) -> Option<TlsInfo> {
+        let (_, session) = self.inner.get_ref();
+        let mut info = TlsInfo::new();
+        if let Some(name) = session.server_name() {
+            info = info.with_server_name(name);
+        }
+        if let Some(protocol) = session.alpn_protocol() {
+            info = info.with_alpn_protocol(protocol);
+        }
+        if let Some(certs) = session.peer_certificates() {
+            info = info.with_peer_certificates(certs.iter().map(|cert| cert.to_vec()).collect());
         }
+        Some(info)
     }
 }
 
@@ -316,18 +332,7 @@ impl AsyncRead for TlsStream {
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
-        let pin = self.get_mut();
-        match pin.state {
-            State::Handshaking(ref mut accept) => match ready!(Pin::new(accept).poll(cx)) {
-                Ok(mut stream) => {
-                    let result = Pin::new(&mut stream).poll_read(cx, buf);
-                    pin.state = State::Streaming(stream);
-                    result
-                }
-                Err(err) => Poll::Ready(Err(err)),
-            },
-            State::Streaming(ref mut stream) => Pin::new(stream).poll_read(cx, buf),
-        }
+        Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
     }
 }
 
@@ -337,45 +342,73 @@ impl AsyncWrite for TlsStream {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
-        let pin = self.get_mut();
-        match pin.state {
-            State::Handshaking(ref mut accept) => match ready!(Pin::new(accept).poll(cx)) {
-                Ok(mut stream) => {
-                    let result = Pin::new(&mut stream).poll_write(cx, buf);
-                    pin.state = State::Streaming(stream);
-                    result
-                }
-                Err(err) => Poll::Ready(Err(err)),
-            },
-            State::Streaming(ref mut stream) => Pin::new(stream).poll_write(cx, buf),
-        }
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
     }
 
-    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        match self.state {
-            State::Handshaking(_) => Poll::Ready(Ok(())),
-            State::Streaming(ref mut stream) => Pin::new(stream).poll_flush(cx),
-        }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
     }
 
-    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        match self.state {
-            State::Handshaking(_) => Poll::Ready(Ok(())),
-            State::Streaming(ref mut stream) => Pin::new(stream).poll_shutdown(cx),
-        }
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
     }
 }
 
+/// Accepts TCP connections, and yields them once their TLS handshake is
+/// done. With the PROXY protocol, the header is read before the handshake.
+/// Handshakes run concurrently, so a slow client doesn't hold up the others,
+/// up to `max_pending` at once.
 pub(crate) struct TlsAcceptor {
-    config: Arc<ServerConfig>,
-    incoming: AddrIncoming,
+    acceptor: tokio_rustls::TlsAcceptor,
+    incoming: TcpIncoming,
+    timeout: Option<Duration>,
+    max_pending: usize,
+    handshakes: FuturesUnordered<BoxFuture<'static, Option<TlsStream>>>,
 }
 
 impl TlsAcceptor {
-    pub(crate) fn new(config: ServerConfig, incoming: AddrIncoming) -> TlsAcceptor {
+    pub(crate) fn new(
+        config: ServerConfig,
+        incoming: TcpIncoming,
+        timeout: Option<Duration>,
+        max_pending: usize,
+    ) -> TlsAcceptor {
         TlsAcceptor {
-            config: Arc::new(config),
+            acceptor: tokio_rustls::TlsAcceptor::from(Arc::new(config)),
             incoming,
+            timeout,
+            max_pending,
+            handshakes: FuturesUnordered::new(),
+        }
+    }
+}
+
+async fn handshake(
+    acceptor: tokio_rustls::TlsAcceptor,
+    stream: TcpConn,
+    timeout: Option<Duration>,
+) -> Option<TlsStream> {
+    // Those sent by the proxy, if any.
+    let remote_addr = stream.remote_addr()?;
+    let local_addr = stream.local_addr()?;
+    let accept = acceptor.accept(stream);
+    let result = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, accept).await,
+        None => Ok(accept.await),
+    };
+    match result {
+        Ok(Ok(inner)) => Some(TlsStream {
+            inner,
+            remote_addr,
+            local_addr,
+        }),
+        Ok(Err(err)) => {
+            tracing::debug!("TLS handshake with {} failed: {}", remote_addr, err);
+            None
+        }
+        Err(_) => {
+            tracing::debug!("TLS handshake with {} timed out", remote_addr);
+            None
         }
     }
 }
@@ -389,10 +422,30 @@ impl Accept for TlsAcceptor {
         cx: &mut Context<'_>,
     ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
         let pin = self.get_mut();
-        match ready!(Pin::new(&mut pin.incoming).poll_accept(cx)) {
-            Some(Ok(sock)) => Poll::Ready(Some(Ok(TlsStream::new(sock, pin.config.clone())))),
-            Some(Err(e)) => Poll::Ready(Some(Err(e))),
-            None => Poll::Ready(None),
+        let mut closed = false;
+        loop {
+            // Past `max_pending`, connections wait in the backlog, and this is
+            // woken by the handshakes instead.
+            while !closed && pin.handshakes.len() < pin.max_pending {
+                match Pin::new(&mut pin.incoming).poll_accept(cx) {
+                    Poll::Ready(Some(Ok(sock))) => {
+                        let handshake = handshake(pin.acceptor.clone(), sock, pin.timeout);
+                        pin.handshakes.push(Box::pin(handshake));
+                    }
+                    Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                    Poll::Ready(None) => closed = true,
+                    Poll::Pending => break,
+                }
+            }
+
+            match pin.handshakes.poll_next_unpin(cx) {
+                Poll::Ready(Some(Some(stream))) => return Poll::Ready(Some(Ok(stream))),
+                // A failed handshake makes room for another connection.
+                Poll::Ready(Some(None)) => continue,
+                Poll::Ready(None) if closed => return Poll::Ready(None),
+                // Woken by `incoming` for new connections.
+                Poll::Ready(None) | Poll::Pending => return Poll::Pending,
+            }
         }
     }
 }
@@ -400,6 +453,10 @@ impl Accept for TlsAcceptor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::convert::TryFrom;
+    use tokio_rustls::rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified};
+    use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+    use tokio_rustls::rustls::{ClientConfig, DigitallySignedStruct, SignatureScheme};
 
     #[test]
     fn file_cert_key() {
@@ -442,4 +499,174 @@ mod tests {
             .build()
             .unwrap();
     }
+
+    // Trusts any certificate, the test is about what the server reports.
+    #[derive(Debug)]
+    struct AnyCert;
+
+    impl tokio_rustls::rustls::client::danger::ServerCertVerifier for AnyCert {
+        fn verify_server_cert(
+            &self,
+            _: &CertificateDer<'_>,
+            _: &[CertificateDer<'_>],
+            _: &ServerName<'_>,
+            _: &[u8],
+            _: UnixTime,
+        ) -> Result<ServerCertVerified, TlsError> {
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            _: &[u8],
+            _: &CertificateDer<'_>,
+            _: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, TlsError> {
+            Ok(HandshakeSignatureValid::assertion())
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            _: &[u8],
+            _: &CertificateDer<'_>,
+            _: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, TlsError> {
+            Ok(HandshakeSignatureValid::assertion())
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            tokio_rustls::rustls::crypto::ring::default_provider()
+                .signature_verification_algorithms
+                .supported_schemes()
+        }
+    }
+
+    #[tokio::test]
+    async fn handshake_tls_info() {
+        use crate::Filter;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let routes = crate::conn::info::<TlsInfo>().map(|tls: TlsInfo| {
+            format!(
+                "{:?} {:?}",
+                tls.server_name(),
+                tls.alpn_protocol().map(String::from_utf8_lossy)
+            )
+        });
+        let (addr, server) = crate::serve(routes)
+            .tls()
+            .key_path("examples/tls/key.rsa")
+            .cert_path("examples/tls/cert.pem")
+            .bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        // A client that never sends a handshake doesn't hold up the others.
+        let _idle = tokio::net::TcpStream::connect(addr).await.unwrap();
+
+        let mut config = ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AnyCert))
+            .with_no_client_auth();
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut stream = tokio_rustls::TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost".to_owned()).unwrap(), tcp)
+            .await
+            .unwrap();
+
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut res = Vec::new();
+        // The server closes without a TLS close_notify.
+        let _ = stream.read_to_end(&mut res).await;
+        let res = String::from_utf8(res).unwrap();
+        assert!(
+            res.ends_with(r#"Some("localhost") Some("http/1.1")"#),
+            "{}",
+            res
+        );
+    }
+
+    #[tokio::test]
+    async fn handshake_limits() {
+        use crate::Filter;
+        use std::time::Instant;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let timeout = Duration::from_millis(200);
+        let config = crate::ServerConfig::new()
+            .tls_handshake_timeout(timeout)
+            .max_pending_handshakes(1);
+        let (addr, server) = crate::serve(crate::any().map(|| "hello"))
+            .config(config)
+            .tls()
+            .key_path("examples/tls/key.rsa")
+            .cert_path("examples/tls/cert.pem")
+            .bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        // Holds the only pending handshake, until it times out.
+        let start = Instant::now();
+        let mut idle = tokio::net::TcpStream::connect(addr).await.unwrap();
+
+        let config = ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AnyCert))
+            .with_no_client_auth();
+        let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut stream = tokio_rustls::TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost".to_owned()).unwrap(), tcp)
+            .await
+            .unwrap();
+        assert!(start.elapsed() >= timeout);
+        assert_eq!(idle.read(&mut [0; 1]).await.unwrap(), 0, "closed");
+
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut res = Vec::new();
+        let _ = stream.read_to_end(&mut res).await;
+        assert!(res.ends_with(b"hello"));
+    }
+
+    #[tokio::test]
+    async fn proxy_protocol_before_handshake() {
+        use crate::Filter;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let routes =
+            crate::addr::remote().map(|addr: Option<SocketAddr>| addr.unwrap().to_string());
+        let (addr, server) = crate::serve(routes)
+            .config(crate::ServerConfig::new().proxy_protocol(true))
+            .tls()
+            .key_path("examples/tls/key.rsa")
+            .cert_path("examples/tls/cert.pem")
+            .bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let config = ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AnyCert))
+            .with_no_client_auth();
+        let mut tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+        tcp.write_all(b"PROXY TCP4 192.0.2.1 198.51.100.2 56324 443\r\n")
+            .await
+            .unwrap();
+        let mut stream = tokio_rustls::TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost".to_owned()).unwrap(), tcp)
+            .await
+            .unwrap();
+
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut res = Vec::new();
+        let _ = stream.read_to_end(&mut res).await;
+        let res = String::from_utf8(res).unwrap();
+        assert!(res.ends_with("192.0.2.1:56324"), "{}", res);
+    }
 }
@@ -1,19 +1,135 @@
 use std::io;
 use std::net::SocketAddr;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
+use http::Extensions;
 use hyper::server::conn::AddrStream;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
+use crate::filters::conn::{LocalAddr, RemoteAddr, TlsInfo};
+
+/// A connection that a [`Server`](crate::Server) serves requests on.
+///
+/// The built-in listeners implement it for their connections. Implement it
+/// for the connections of a custom accept loop, such as vsock, in-memory
+/// pipes or another TLS stack, and pass them to
+/// [`Server::serve_transport`](crate::Server::serve_transport), so that
+/// filters can read what is known about the connection with
+/// [`conn::info`](crate::conn::info).
+///
+/// Only `remote_addr` is required, the other methods default to no
+/// information.
+///
+/// # Example
+///
+/// ```
+/// use std::net::SocketAddr;
+/// use std::pin::Pin;
+/// use std::task::{Context, Poll};
+///
+/// use starterm::http::Extensions;
+/// use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+///
+/// // Identifies the tenant a connection belongs to.
+/// #[derive(Clone, Debug)]
+/// struct Tenant(String);
+///
+/// struct TenantConn {
+///     io: DuplexStream,
+///     tenant: Tenant,
+/// }
+///
+/// impl starterm::Transport for TenantConn {
+///     fn remote_addr(&self) -> Option<SocketAddr> {
+///         None
+///     }
+///
+///     fn extensions(&self, extensions: &mut Extensions) {
+///         extensions.insert(self.tenant.clone());
+///     }
+/// }
+///
+/// # impl AsyncRead for TenantConn {
+/// #     fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+/// #         Pin::new(&mut self.get_mut().io).poll_read(cx, buf)
+/// #     }
+/// # }
+/// # impl AsyncWrite for TenantConn {
+/// #     fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+/// #         Pin::new(&mut self.get_mut().io).poll_write(cx, buf)
+/// #     }
+/// #     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+/// #         Pin::new(&mut self.get_mut().io).poll_flush(cx)
+/// #     }
+/// #     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+/// #         Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+/// #     }
+/// # }
+/// ```
 pub trait Transport: AsyncRead + AsyncWrite {
+    /// Returns the address of the remote peer, if it has one.
     fn remote_addr(&self) -> Option<SocketAddr>;
+
+    /// Returns the local address the connection was accepted on, if it has
+    /// one.
+    fn local_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    /// Returns the TLS session of the connection, once its handshake is done.
+    fn tls_info(&self) -> Option<TlsInfo> {
+        None
+    }
+
+    /// Adds typed information about the connection, for
+    /// [`conn::info`](crate::conn::info).
+    fn extensions(&self, extensions: &mut Extensions) {
+        let _ = extensions;
+    }
 }
 
 impl Transport for AddrStream {
     fn remote_addr(&self) -> Option<SocketAddr> {
         Some(self.remote_addr())
     }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        Some(self.local_addr())
+    }
+}
+
+/// What is known about the connection of a request, shared by the requests
+/// of a connection.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ConnInfo {
+    pub(crate) remote_addr: Option<SocketAddr>,
+    pub(crate) extensions: Arc<Extensions>,
+}
+
+impl ConnInfo {
+    pub(crate) fn new(remote_addr: Option<SocketAddr>, mut extensions: Extensions) -> ConnInfo {
+        if let Some(addr) = remote_addr {
+            extensions.insert(RemoteAddr(addr));
+        }
+        ConnInfo {
+            remote_addr,
+            extensions: Arc::new(extensions),
+        }
+    }
+
+    pub(crate) fn from_transport<T: Transport + ?Sized>(transport: &T) -> ConnInfo {
+        let mut extensions = Extensions::new();
+        transport.extensions(&mut extensions);
+        if let Some(addr) = transport.local_addr() {
+            extensions.insert(LocalAddr(addr));
+        }
+        if let Some(tls) = transport.tls_info() {
+            extensions.insert(tls);
+        }
+        ConnInfo::new(transport.remote_addr(), extensions)
+    }
 }
 
 pub(crate) struct LiftIo<T>(pub(crate) T);
@@ -0,0 +1,212 @@
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt, PermissionsExt};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_util::ready;
+use http::Extensions;
+use hyper::server::accept::Accept;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::time::{sleep, Sleep};
+
+use crate::filters::addr::UnixPeer;
+use crate::transport::Transport;
+
+/// Accepts connections on a Unix domain socket, and removes the socket file
+/// when dropped.
+pub(crate) struct UnixIncoming {
+    listener: UnixListener,
+    path: Arc<Path>,
+    // To only remove the socket file if it's still the one we bound.
+    ino: u64,
+    timeout: Option<Pin<Box<Sleep>>>,
+}
+
+impl UnixIncoming {
+    /// Binds a socket at `path`, replacing a stale socket file left by a
+    /// server that is no longer running.
+    pub(crate) fn bind(path: &Path, mode: Option<u32>) -> io::Result<UnixIncoming> {
+        remove_stale_socket(path)?;
+        let listener = match mode {
+            Some(mode) => bind_with_mode(path, mode)?,
+            None => UnixListener::bind(path)?,
+        };
+        let ino = match fs::symlink_metadata(path) {
+            Ok(meta) => meta.ino(),
+            Err(err) => {
+                let _ = fs::remove_file(path);
+                return Err(err);
+            }
+        };
+        Ok(UnixIncoming {
+            listener,
+            path: Arc::from(path),
+            ino,
+            timeout: None,
+        })
+    }
+}
+
+// Binds the socket in a private directory next to `path`, so that no one can
+// connect before its permissions are set, and then links it at `path`. Like
+// binding, linking fails if `path` exists.
+fn bind_with_mode(path: &Path, mode: u32) -> io::Result<UnixListener> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let dir = parent.join(format!(
+        ".starterm-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::DirBuilder::new().mode(0o700).create(&dir)?;
+
+    let temp = dir.join("sock");
+    let res = UnixListener::bind(&temp).and_then(|listener| {
+        fs::set_permissions(&temp, fs::Permissions::from_mode(mode))?;
+        fs::hard_link(&temp, path)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_file(&temp);
+    let _ = fs::remove_dir(&dir);
+    res
+}
+
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    let meta = match fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    if !meta.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} exists and is not a socket", path.display()),
+        ));
+    }
+    match std::os::unix::net::UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is in use by a running server", path.display()),
+        )),
+        Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {
+            tracing::debug!("removing stale socket {}", path.display());
+            fs::remove_file(path)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+impl Drop for UnixIncoming {
+    fn drop(&mut self) {
+        let ours = fs::symlink_metadata(&self.path).is_ok_and(|meta| meta.ino() == self.ino);
+        if ours {
+            if let Err(err) = fs::remove_file(&self.path) {
+                tracing::debug!("error removing socket {}: {}", self.path.display(), err);
+            }
+        }
+    }
+}
+
+impl Accept for UnixIncoming {
+    type Conn = UnixConn;
+    type Error = io::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let pin = self.get_mut();
+        if let Some(ref mut timeout) = pin.timeout {
+            ready!(timeout.as_mut().poll(cx));
+            pin.timeout = None;
+        }
+
+        loop {
+            match ready!(pin.listener.poll_accept(cx)) {
+                Ok((io, _)) => {
+                    let peer = UnixPeer::new(pin.path.clone(), io.peer_cred().ok());
+                    return Poll::Ready(Some(Ok(UnixConn { io, peer })));
+                }
+                // Errors of the accepted connection, not of the listener.
+                Err(err) if is_connection_error(&err) => {
+                    tracing::debug!("accepted connection already errored: {}", err);
+                }
+                // Such as running out of file descriptors, which won't be
+                // fixed by trying again right away.
+                Err(err) => {
+                    tracing::error!("accept error: {}", err);
+                    let mut timeout = Box::pin(sleep(Duration::from_secs(1)));
+                    if timeout.as_mut().poll(cx).is_pending() {
+                        pin.timeout = Some(timeout);
+                        return Poll::Pending;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn is_connection_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+/// A connection accepted by `UnixIncoming`.
+pub(crate) struct UnixConn {
+    io: UnixStream,
+    peer: UnixPeer,
+}
+
+impl AsyncRead for UnixConn {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for UnixConn {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+    }
+}
+
+impl Transport for UnixConn {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    fn extensions(&self, extensions: &mut Extensions) {
+        extensions.insert(self.peer.clone());
+    }
+}
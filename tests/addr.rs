@@ -1,7 +1,8 @@
 #![deny(warnings)]
 
-use starterm::addr::remote;
+use starterm::addr::{remote, Cidr, ForwardedHeader, TrustedProxies};
 use starterm::test::request;
+use starterm::Filter;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 
 /// Ensures that extracting remote address returns None when no remote address is set.
@@ -28,3 +29,190 @@ async fn remote_addr_present() {
     let expected = Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)), 5678));
     assert_eq!(resp, expected, "Expected {:?}, got {:?}", expected, resp);
 }
+
+/// Ensures that CIDRs parse and match addresses of their range.
+#[test]
+fn cidr() {
+    let cidr: Cidr = "10.0.0.0/8".parse().unwrap();
+    assert!(cidr.contains("10.255.0.1".parse().unwrap()));
+    assert!(cidr.contains("::ffff:10.0.0.1".parse().unwrap()));
+    assert!(!cidr.contains("11.0.0.1".parse().unwrap()));
+    assert!(!cidr.contains("::1".parse().unwrap()));
+
+    let cidr: Cidr = "2001:db8::/32".parse().unwrap();
+    assert!(cidr.contains("2001:db8:1::1".parse().unwrap()));
+    assert!(!cidr.contains("2001:db9::1".parse().unwrap()));
+
+    let cidr: Cidr = "192.0.2.1".parse().unwrap();
+    assert_eq!(cidr.to_string(), "192.0.2.1/32");
+    assert!(!cidr.contains("192.0.2.2".parse().unwrap()));
+
+    let any: Cidr = "0.0.0.0/0".parse().unwrap();
+    assert!(any.contains("203.0.113.9".parse().unwrap()));
+
+    for invalid in &[
+        "10.0.0.0/33",
+        "10.0.0.0/",
+        "10.0.0.0/+8",
+        "::/129",
+        "example.com",
+    ] {
+        assert!(invalid.parse::<Cidr>().is_err(), "{}", invalid);
+    }
+}
+
+fn trusted() -> TrustedProxies {
+    TrustedProxies::new(vec![
+        "10.0.0.0/8".parse().unwrap(),
+        "2001:db8::/32".parse().unwrap(),
+    ])
+}
+
+/// Ensures that the client IP is the first untrusted hop from the right.
+#[tokio::test]
+async fn client_ip() {
+    let route = starterm::addr::client_ip(trusted().header(ForwardedHeader::XForwardedFor));
+    let ip = |s: &str| Some(s.parse::<IpAddr>().unwrap());
+
+    // Without a trusted peer, headers are ignored.
+    let req = request()
+        .remote_addr("192.0.2.1:5678".parse().unwrap())
+        .header("x-forwarded-for", "198.51.100.1");
+    assert_eq!(req.filter(&route).await.unwrap(), ip("192.0.2.1"));
+
+    let req = request()
+        .remote_addr("10.0.0.1:5678".parse().unwrap())
+        .header("x-forwarded-for", "203.0.113.9, 198.51.100.1, 10.0.0.2");
+    assert_eq!(req.filter(&route).await.unwrap(), ip("198.51.100.1"));
+
+    // All hops trusted, the leftmost is the client.
+    let req = request()
+        .remote_addr("10.0.0.1:5678".parse().unwrap())
+        .header("x-forwarded-for", "10.0.0.3, 10.0.0.2");
+    assert_eq!(req.filter(&route).await.unwrap(), ip("10.0.0.3"));
+
+    let req = request().header("x-forwarded-for", "198.51.100.1");
+    assert_eq!(req.filter(&route).await.unwrap(), None);
+
+    let route = starterm::addr::client_ip(trusted());
+    let req = request()
+        .remote_addr("10.0.0.1:5678".parse().unwrap())
+        .header(
+            "forwarded",
+            r#"for=198.51.100.1;proto=https, for="[2001:db8::1]:4711";by=10.0.0.1"#,
+        );
+    assert_eq!(req.filter(&route).await.unwrap(), ip("198.51.100.1"));
+
+    // Past an unknown hop, the last known one is used.
+    let req = request()
+        .remote_addr("10.0.0.1:5678".parse().unwrap())
+        .header("forwarded", "for=198.51.100.1, for=unknown, for=10.0.0.2");
+    assert_eq!(req.filter(&route).await.unwrap(), ip("10.0.0.2"));
+
+    let route = starterm::addr::client_ip(trusted().header(ForwardedHeader::XRealIp));
+    let req = request()
+        .remote_addr("10.0.0.1:5678".parse().unwrap())
+        .header("x-real-ip", "198.51.100.1");
+    assert_eq!(req.filter(&route).await.unwrap(), ip("198.51.100.1"));
+}
+
+/// Ensures that only the configured header is read, so that a client can't
+/// spoof its address with another one that the proxies pass through.
+#[tokio::test]
+async fn client_ip_ignores_other_headers() {
+    let ip = |s: &str| Some(s.parse::<IpAddr>().unwrap());
+
+    let route = starterm::addr::client_ip(trusted().header(ForwardedHeader::XForwardedFor));
+    let req = request()
+        .remote_addr("10.0.0.1:5678".parse().unwrap())
+        .header("forwarded", "for=203.0.113.66")
+        .header("x-real-ip", "203.0.113.66")
+        .header("x-forwarded-for", "198.51.100.1");
+    assert_eq!(req.filter(&route).await.unwrap(), ip("198.51.100.1"));
+
+    // Without the configured header, the proxy is the client.
+    let req = request()
+        .remote_addr("10.0.0.1:5678".parse().unwrap())
+        .header("forwarded", "for=203.0.113.66");
+    assert_eq!(req.filter(&route).await.unwrap(), ip("10.0.0.1"));
+
+    let route = starterm::addr::client_ip(trusted());
+    let req = request()
+        .remote_addr("10.0.0.1:5678".parse().unwrap())
+        .header("x-forwarded-for", "203.0.113.66")
+        .header("forwarded", "for=198.51.100.1");
+    assert_eq!(req.filter(&route).await.unwrap(), ip("198.51.100.1"));
+}
+
+/// Ensures that the forwarded scheme and host are only read from trusted
+/// proxies.
+#[tokio::test]
+async fn forwarded_proto_and_host() {
+    use starterm::http::uri::{Authority, Scheme};
+
+    let route = starterm::addr::forwarded_proto(trusted())
+        .and(starterm::addr::forwarded_host(trusted()))
+        .map(|proto: Option<Scheme>, host: Option<Authority>| (proto, host));
+
+    let req = request()
+        .remote_addr("10.0.0.1:5678".parse().unwrap())
+        .header(
+            "forwarded",
+            r#"for=198.51.100.1;proto=http, for=192.0.2.1;proto=https;host="example.com:8443""#,
+        );
+    let (proto, host) = req.filter(&route).await.unwrap();
+    assert_eq!(proto, Some(Scheme::HTTPS));
+    assert_eq!(host.unwrap(), "example.com:8443");
+
+    // Not the configured header.
+    let req = request()
+        .remote_addr("10.0.0.1:5678".parse().unwrap())
+        .header("x-forwarded-proto", "https")
+        .header("x-forwarded-host", "example.com");
+    assert_eq!(req.filter(&route).await.unwrap(), (None, None));
+
+    let trusted = trusted().header(ForwardedHeader::XForwardedFor);
+    let route = starterm::addr::forwarded_proto(trusted.clone())
+        .and(starterm::addr::forwarded_host(trusted))
+        .map(|proto: Option<Scheme>, host: Option<Authority>| (proto, host));
+
+    let req = request()
+        .remote_addr("10.0.0.1:5678".parse().unwrap())
+        .header("x-forwarded-proto", "http, https")
+        .header("x-forwarded-host", "example.com");
+    let (proto, host) = req.filter(&route).await.unwrap();
+    assert_eq!(proto, Some(Scheme::HTTPS));
+    assert_eq!(host.unwrap(), "example.com");
+
+    let req = request()
+        .remote_addr("192.0.2.1:5678".parse().unwrap())
+        .header("x-forwarded-proto", "https")
+        .header("x-forwarded-host", "example.com");
+    assert_eq!(req.filter(&route).await.unwrap(), (None, None));
+}
+
+/// Ensures that log lines can use the resolved client IP.
+#[tokio::test]
+async fn log_client_ip() {
+    use std::sync::{Arc, Mutex};
+
+    let logged = Arc::new(Mutex::new(None));
+    let log = {
+        let logged = logged.clone();
+        let trusted = trusted().header(ForwardedHeader::XForwardedFor);
+        starterm::log::custom(move |info| {
+            *logged.lock().unwrap() = info.client_ip(&trusted);
+        })
+    };
+    let route = starterm::any().map(starterm::reply).with(log);
+
+    request()
+        .remote_addr("10.0.0.1:5678".parse().unwrap())
+        .header("x-forwarded-for", "198.51.100.1")
+        .reply(&route)
+        .await;
+    assert_eq!(
+        *logged.lock().unwrap(),
+        Some("198.51.100.1".parse().unwrap())
+    );
+}
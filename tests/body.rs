@@ -200,3 +200,84 @@ async fn stream() {
     assert_eq!(bufs.len(), 1);
     assert_eq!(bufs[0].chunk(), b"foo=bar");
 }
+
+#[cfg(feature = "compression-gzip")]
+async fn gzip(data: &[u8]) -> Vec<u8> {
+    use async_compression::tokio::write::GzipEncoder;
+    use tokio::io::AsyncWriteExt;
+
+    let mut encoder = GzipEncoder::new(Vec::new());
+    encoder.write_all(data).await.unwrap();
+    encoder.shutdown().await.unwrap();
+    encoder.into_inner()
+}
+
+#[cfg(feature = "compression-gzip")]
+#[tokio::test]
+async fn decompressed_json() {
+    let _ = pretty_env_logger::try_init();
+
+    let json = starterm::body::decompressed(1024).and(starterm::body::json::<Vec<i32>>());
+
+    let req = starterm::test::request()
+        .header("content-encoding", "gzip")
+        .header("content-type", "application/json")
+        .body(gzip(b"[1, 2, 3]").await);
+
+    let vec = req.filter(&json).await.unwrap();
+    assert_eq!(vec, &[1, 2, 3]);
+
+    // Plain bodies pass through.
+    let req = starterm::test::request().json(&[4, 5]);
+
+    let vec = req.filter(&json).await.unwrap();
+    assert_eq!(vec, &[4, 5]);
+}
+
+#[cfg(feature = "compression-gzip")]
+#[tokio::test]
+async fn decompressed_rejects_unknown_encoding() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = starterm::body::decompressed(1024)
+        .and(starterm::body::bytes())
+        .map(|_| starterm::reply());
+
+    let res = starterm::test::request()
+        .header("content-encoding", "compress")
+        .body("whatever")
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), 415);
+}
+
+#[cfg(feature = "compression-gzip")]
+#[tokio::test]
+async fn decompressed_limit() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = starterm::body::decompressed(4096)
+        .and(starterm::body::bytes())
+        .map(|_| starterm::reply());
+
+    // A few bytes of gzip that expand to a megabyte.
+    let bomb = gzip(&[0; 1024 * 1024]).await;
+    assert!(bomb.len() < 4096);
+
+    let res = starterm::test::request()
+        .header("content-encoding", "gzip")
+        .body(bomb)
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), 413);
+
+    let res = starterm::test::request()
+        .header("content-encoding", "gzip")
+        .body("not gzip at all")
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), 400);
+}
@@ -0,0 +1,312 @@
+#![deny(warnings)]
+use std::time::Duration;
+
+use async_compression::tokio::bufread::GzipDecoder;
+use async_compression::tokio::write::GzipDecoder as GzipWriteDecoder;
+use starterm::hyper::body::{Bytes, HttpBody};
+use starterm::{hyper::Body, Filter, Reply};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const BODY: &str = "starterm compresses this body when the client asks for it";
+
+async fn gunzip(bytes: &[u8]) -> String {
+    let mut decoded = String::new();
+    GzipDecoder::new(bytes)
+        .read_to_string(&mut decoded)
+        .await
+        .expect("gzip body");
+    decoded
+}
+
+#[tokio::test]
+async fn gzip() {
+    let route = starterm::any()
+        .map(|| BODY)
+        .with(starterm::compression::gzip());
+
+    let res = starterm::test::request().reply(&route).await;
+
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-encoding"], "gzip");
+    assert!(res.headers().get("content-length").is_none());
+    assert_eq!(gunzip(res.body()).await, BODY);
+}
+
+#[tokio::test]
+async fn auto_negotiates_accept_encoding() {
+    let route = starterm::any()
+        .map(|| BODY)
+        .with(starterm::compression::auto());
+
+    let res = starterm::test::request()
+        .header("accept-encoding", "deflate;q=0.5, gzip")
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.headers()["content-encoding"], "gzip");
+    assert_eq!(res.headers()["vary"], "accept-encoding");
+    assert_eq!(gunzip(res.body()).await, BODY);
+
+    let res = starterm::test::request()
+        .header("accept-encoding", "gzip;q=0.2, br;q=0.8")
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.headers()["content-encoding"], "br");
+
+    let res = starterm::test::request()
+        .header("accept-encoding", "*;q=0.1, zstd;q=0, br;q=0")
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.headers()["content-encoding"], "gzip");
+}
+
+#[cfg(feature = "compression-zstd")]
+#[tokio::test]
+async fn zstd() {
+    use async_compression::tokio::bufread::ZstdDecoder;
+
+    let route = starterm::any()
+        .map(|| BODY)
+        .with(starterm::compression::zstd());
+
+    let res = starterm::test::request().reply(&route).await;
+
+    assert_eq!(res.headers()["content-encoding"], "zstd");
+    let mut decoded = String::new();
+    ZstdDecoder::new(&res.body()[..])
+        .read_to_string(&mut decoded)
+        .await
+        .expect("zstd body");
+    assert_eq!(decoded, BODY);
+
+    let route = starterm::any()
+        .map(|| BODY)
+        .with(starterm::compression::auto());
+
+    let res = starterm::test::request()
+        .header("accept-encoding", "gzip, deflate, br, zstd")
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.headers()["content-encoding"], "zstd");
+}
+
+#[tokio::test]
+async fn auto_passes_through_without_acceptable_encoding() {
+    let route = starterm::any()
+        .map(|| BODY)
+        .with(starterm::compression::auto());
+
+    let res = starterm::test::request().reply(&route).await;
+
+    assert!(res.headers().get("content-encoding").is_none());
+    assert_eq!(res.headers()["vary"], "accept-encoding");
+    assert_eq!(res.body(), BODY);
+
+    let res = starterm::test::request()
+        .header("accept-encoding", "identity, gzip;q=0, br;q=0, deflate;q=0")
+        .reply(&route)
+        .await;
+
+    assert!(res.headers().get("content-encoding").is_none());
+    assert_eq!(res.body(), BODY);
+}
+
+#[tokio::test]
+async fn auto_adds_vary_when_skipped() {
+    let route = starterm::any()
+        .map(|| starterm::reply::with_status(BODY, starterm::http::StatusCode::NOT_MODIFIED))
+        .with(starterm::compression::auto());
+
+    let res = starterm::test::request()
+        .header("accept-encoding", "gzip")
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), 304);
+    assert!(res.headers().get("content-encoding").is_none());
+    assert_eq!(res.headers()["vary"], "accept-encoding");
+
+    let route = starterm::any()
+        .map(|| starterm::reply::with_header(BODY, "content-type", "image/png"))
+        .with(starterm::compression::auto());
+
+    let res = starterm::test::request()
+        .header("accept-encoding", "gzip")
+        .reply(&route)
+        .await;
+
+    assert!(res.headers().get("content-encoding").is_none());
+    assert_eq!(res.headers()["vary"], "accept-encoding");
+}
+
+#[tokio::test]
+async fn auto_keeps_existing_vary() {
+    let route = starterm::any()
+        .map(|| starterm::reply::with_header(BODY, "vary", "Origin, Accept-Encoding"))
+        .with(starterm::compression::auto());
+
+    let res = starterm::test::request()
+        .header("accept-encoding", "gzip")
+        .reply(&route)
+        .await;
+
+    let vary = res.headers().get_all("vary").iter().count();
+    assert_eq!(vary, 1);
+}
+
+#[tokio::test]
+async fn does_not_encode_twice() {
+    let route = starterm::any()
+        .map(|| starterm::reply::with_header(BODY, "content-encoding", "identity"))
+        .with(starterm::compression::gzip());
+
+    let res = starterm::test::request().reply(&route).await;
+
+    assert_eq!(res.headers()["content-encoding"], "identity");
+    assert_eq!(res.body(), BODY);
+}
+
+#[tokio::test]
+async fn min_length() {
+    let route = starterm::any()
+        .map(|| BODY)
+        .with(starterm::compression::gzip().min_length(1024));
+
+    let res = starterm::test::request().reply(&route).await;
+
+    assert!(res.headers().get("content-encoding").is_none());
+    assert_eq!(res.body(), BODY);
+
+    let route = starterm::any()
+        .map(|| BODY)
+        .with(starterm::compression::gzip().min_length(16));
+
+    let res = starterm::test::request().reply(&route).await;
+
+    assert_eq!(res.headers()["content-encoding"], "gzip");
+}
+
+#[tokio::test]
+async fn skips_incompressible_content_types() {
+    let route = starterm::any()
+        .map(|| starterm::reply::with_header(BODY, "content-type", "image/png"))
+        .with(starterm::compression::gzip());
+
+    let res = starterm::test::request().reply(&route).await;
+
+    assert!(res.headers().get("content-encoding").is_none());
+
+    let route = starterm::any()
+        .map(|| starterm::reply::with_header(BODY, "content-type", "text/event-stream"))
+        .with(starterm::compression::gzip());
+
+    let res = starterm::test::request().reply(&route).await;
+
+    assert!(res.headers().get("content-encoding").is_none());
+
+    let route = starterm::any()
+        .map(|| starterm::reply::with_header(BODY, "content-type", "image/png"))
+        .with(starterm::compression::gzip().mime_filter(|_| true));
+
+    let res = starterm::test::request().reply(&route).await;
+
+    assert_eq!(res.headers()["content-encoding"], "gzip");
+}
+
+#[tokio::test]
+async fn skips_partial_and_bodiless_responses() {
+    let route = starterm::path("file")
+        .and(starterm::fs::file("README.md"))
+        .with(starterm::compression::gzip());
+
+    let res = starterm::test::request()
+        .path("/file")
+        .header("range", "bytes=0-9")
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), 206);
+    assert!(res.headers().get("content-encoding").is_none());
+    assert_eq!(res.body().len(), 10);
+
+    let route = starterm::any()
+        .map(|| starterm::http::StatusCode::NO_CONTENT)
+        .with(starterm::compression::gzip());
+
+    let res = starterm::test::request().reply(&route).await;
+
+    assert!(res.headers().get("content-encoding").is_none());
+}
+
+#[tokio::test]
+async fn without_compression() {
+    let route = starterm::any()
+        .map(|| starterm::compression::without_compression(BODY))
+        .with(starterm::compression::auto());
+
+    let res = starterm::test::request()
+        .header("accept-encoding", "gzip")
+        .reply(&route)
+        .await;
+
+    assert!(res.headers().get("content-encoding").is_none());
+    assert_eq!(res.body(), BODY);
+}
+
+#[tokio::test]
+async fn level() {
+    let body = BODY.repeat(64);
+    let fastest = {
+        let body = body.clone();
+        starterm::any()
+            .map(move || body.clone())
+            .with(starterm::compression::gzip().level(starterm::compression::Level::Fastest))
+    };
+    let best = {
+        let body = body.clone();
+        starterm::any()
+            .map(move || body.clone())
+            .with(starterm::compression::gzip().level(starterm::compression::Level::Best))
+    };
+
+    let res = starterm::test::request().reply(&fastest).await;
+    assert_eq!(gunzip(res.body()).await, body);
+
+    let res = starterm::test::request().reply(&best).await;
+    assert_eq!(gunzip(res.body()).await, body);
+}
+
+#[tokio::test]
+async fn flush_every_chunk() {
+    let route = starterm::any()
+        .map(|| {
+            let (mut tx, body) = Body::channel();
+            tokio::spawn(async move {
+                tx.send_data(Bytes::from_static(b"data: first\n\n"))
+                    .await
+                    .unwrap();
+                // Keep the body open, the first event must not wait for more.
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                drop(tx);
+            });
+            starterm::http::Response::new(body)
+        })
+        .with(starterm::compression::gzip().flush_every_chunk(true));
+
+    let reply = starterm::test::request().filter(&route).await.unwrap();
+    let mut body = reply.into_response().into_body();
+
+    let chunk = tokio::time::timeout(Duration::from_secs(5), body.data())
+        .await
+        .expect("first chunk must be flushed")
+        .unwrap()
+        .unwrap();
+
+    let mut decoder = GzipWriteDecoder::new(Vec::new());
+    decoder.write_all(&chunk).await.unwrap();
+    decoder.flush().await.unwrap();
+    assert_eq!(decoder.get_ref().as_slice(), b"data: first\n\n");
+}
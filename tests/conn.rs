@@ -0,0 +1,150 @@
+#![deny(warnings)]
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use starterm::conn::{LocalAddr, RemoteAddr, TlsInfo};
+use starterm::http::Extensions;
+use starterm::Filter;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream, ReadBuf};
+
+#[derive(Clone, Debug, PartialEq)]
+struct Tenant(&'static str);
+
+#[tokio::test]
+async fn info() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = starterm::conn::info::<Tenant>();
+    let extracted = starterm::test::request()
+        .conn_info(Tenant("acme"))
+        .filter(&route)
+        .await
+        .unwrap();
+    assert_eq!(extracted, Tenant("acme"));
+
+    let res = starterm::test::request()
+        .reply(&route.map(|_| "unreachable"))
+        .await;
+    assert_eq!(res.status(), 500);
+
+    let route = starterm::conn::optional::<TlsInfo>();
+    let extracted = starterm::test::request().filter(&route).await.unwrap();
+    assert_eq!(extracted, None);
+
+    let addr: SocketAddr = ([10, 0, 0, 1], 4000).into();
+    let route = starterm::conn::info::<RemoteAddr>();
+    let extracted = starterm::test::request()
+        .remote_addr(addr)
+        .filter(&route)
+        .await
+        .unwrap();
+    assert_eq!(extracted, RemoteAddr(addr));
+}
+
+struct TenantConn {
+    io: DuplexStream,
+}
+
+impl starterm::Transport for TenantConn {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        Some(([10, 0, 0, 2], 5000).into())
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        Some(([10, 0, 0, 3], 80).into())
+    }
+
+    fn tls_info(&self) -> Option<TlsInfo> {
+        Some(TlsInfo::new().with_alpn_protocol("http/1.1"))
+    }
+
+    fn extensions(&self, extensions: &mut Extensions) {
+        extensions.insert(Tenant("acme"));
+    }
+}
+
+impl AsyncRead for TenantConn {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TenantConn {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.get_mut().io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+    }
+}
+
+#[tokio::test]
+async fn serve_transport() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = starterm::addr::remote()
+        .and(starterm::conn::info::<LocalAddr>())
+        .and(starterm::conn::info::<TlsInfo>())
+        .and(starterm::conn::info::<Tenant>())
+        .map(
+            |remote: Option<SocketAddr>, local: LocalAddr, tls: TlsInfo, tenant: Tenant| {
+                format!(
+                    "{} {} {} {}",
+                    remote.unwrap(),
+                    local.0,
+                    String::from_utf8_lossy(tls.alpn_protocol().unwrap()),
+                    tenant.0
+                )
+            },
+        );
+
+    let (client, server) = tokio::io::duplex(4096);
+    let incoming =
+        futures_util::stream::iter(vec![Ok::<_, std::io::Error>(TenantConn { io: server })]);
+    tokio::spawn(starterm::serve(route).serve_transport(incoming));
+
+    let mut client = client;
+    client
+        .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut res = String::new();
+    client.read_to_string(&mut res).await.unwrap();
+    assert!(
+        res.ends_with("10.0.0.2:5000 10.0.0.3:80 http/1.1 acme"),
+        "{}",
+        res
+    );
+}
+
+#[tokio::test]
+async fn local_addr() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = starterm::conn::info::<LocalAddr>().map(|local: LocalAddr| local.0.to_string());
+    let (addr, server) = starterm::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut res = String::new();
+    stream.read_to_string(&mut res).await.unwrap();
+    assert!(res.ends_with(&addr.to_string()), "{}", res);
+}
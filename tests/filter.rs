@@ -15,6 +15,7 @@ async fn flattens_tuples() {
     assert_eq!(ext, "starterm");
 
     // just 1 unit
+    #[allow(clippy::let_unit_value)]
     let ext = starterm::test::request().filter(&unit1).await.unwrap();
     assert_eq!(ext, ());
 
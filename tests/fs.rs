@@ -174,6 +174,131 @@ async fn precondition() {
     assert_eq!(res.body(), "");
 }
 
+#[tokio::test]
+async fn etag() {
+    let _ = pretty_env_logger::try_init();
+
+    let file = starterm::fs::file("README.md");
+
+    let res1 = starterm::test::request().reply(&file).await;
+    assert_eq!(res1.status(), 200);
+    let etag = res1.headers()["etag"].clone();
+    assert!(!etag.to_str().unwrap().starts_with("W/"));
+
+    // if-none-match
+    let res = starterm::test::request()
+        .header("if-none-match", &etag)
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 304);
+    assert_eq!(res.headers()["etag"], etag);
+    assert_eq!(res.body(), "");
+
+    // if-none-match takes precedence over if-modified-since
+    let res = starterm::test::request()
+        .header("if-none-match", "\"other\"")
+        .header("if-modified-since", &res1.headers()["last-modified"])
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+
+    // if-none-match on an unsafe method
+    let res = starterm::test::request()
+        .method("POST")
+        .header("if-none-match", "*")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 412);
+
+    // if-match
+    let res = starterm::test::request()
+        .header("if-match", &etag)
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+
+    // if-match takes precedence over if-unmodified-since
+    let res = starterm::test::request()
+        .header("if-match", &etag)
+        .header("if-unmodified-since", "Mon, 07 Nov 1994 01:00:00 GMT")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let res = starterm::test::request()
+        .header("if-match", "\"other\"")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 412);
+
+    // if-range
+    let res = starterm::test::request()
+        .header("range", "bytes=0-9")
+        .header("if-range", &etag)
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 206);
+
+    let res = starterm::test::request()
+        .header("range", "bytes=0-9")
+        .header("if-range", "\"other\"")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+}
+
+#[tokio::test]
+async fn etag_modes() {
+    use starterm::fs::{ETagMode, FileOptions};
+
+    let _ = pretty_env_logger::try_init();
+
+    let file = starterm::fs::file_with("README.md", FileOptions::new().etag(ETagMode::Weak));
+    let res = starterm::test::request().reply(&file).await;
+    let etag = res.headers()["etag"].clone();
+    assert!(etag.to_str().unwrap().starts_with("W/"));
+
+    // weak tags can't satisfy if-match or if-range
+    let res = starterm::test::request()
+        .header("if-none-match", &etag)
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 304);
+    let res = starterm::test::request()
+        .header("if-match", &etag)
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 412);
+
+    let file = starterm::fs::file_with("README.md", FileOptions::new().etag(ETagMode::ContentHash));
+    let res1 = starterm::test::request().reply(&file).await;
+    let res2 = starterm::test::request().reply(&file).await;
+    assert_eq!(res1.headers()["etag"], res2.headers()["etag"]);
+    assert_eq!(res1.headers()["etag"].len(), 42);
+    assert_eq!(res1.body(), &*fs::read("README.md").unwrap());
+
+    let file = starterm::fs::file_with("README.md", FileOptions::new().etag(ETagMode::Disabled));
+    let res = starterm::test::request().reply(&file).await;
+    assert_eq!(res.headers().get("etag"), None);
+
+    // `*` matches the existing file even without an `ETag`.
+    let res = starterm::test::request()
+        .header("if-match", "*")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+    let res = starterm::test::request()
+        .header("if-match", "\"abc\"")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 412);
+    let res = starterm::test::request()
+        .header("if-none-match", "*")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 304);
+}
+
 #[tokio::test]
 async fn byte_ranges() {
     let _ = pretty_env_logger::try_init();
@@ -269,3 +394,842 @@ async fn byte_ranges_with_excluded_file_size() {
     );
     assert_eq!(res.body(), &contents[100..=contents.len() - 1]);
 }
+
+#[tokio::test]
+async fn multiple_byte_ranges() {
+    let _ = pretty_env_logger::try_init();
+
+    let contents = fs::read("README.md").expect("fs::read README.md");
+    let file = starterm::fs::file("README.md");
+    let mime = starterm::test::request().reply(&file).await.headers()["content-type"].clone();
+
+    let res = starterm::test::request()
+        .header("range", "bytes=100-109, 0-9")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(res.headers().get("content-range"), None);
+
+    let content_type = res.headers()["content-type"].to_str().unwrap();
+    let boundary = content_type
+        .strip_prefix("multipart/byteranges; boundary=")
+        .expect("multipart/byteranges");
+    assert_eq!(
+        res.headers()["content-length"],
+        res.body().len().to_string()
+    );
+
+    let mut expected = Vec::new();
+    for (i, &(start, end)) in [(0usize, 9usize), (100, 109)].iter().enumerate() {
+        if i > 0 {
+            expected.extend_from_slice(b"\r\n");
+        }
+        expected.extend_from_slice(
+            format!(
+                "--{}\r\ncontent-type: {}\r\ncontent-range: bytes {}-{}/{}\r\n\r\n",
+                boundary,
+                mime.to_str().unwrap(),
+                start,
+                end,
+                contents.len()
+            )
+            .as_bytes(),
+        );
+        expected.extend_from_slice(&contents[start..=end]);
+    }
+    expected.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    assert_eq!(res.body(), &expected);
+
+    // overlapping ranges are served as one
+    let res = starterm::test::request()
+        .header("range", "bytes=0-49, 10-99")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(
+        res.headers()["content-range"],
+        format!("bytes 0-99/{}", contents.len())
+    );
+    assert_eq!(res.body(), &contents[..100]);
+}
+
+/// A directory under the system temp dir, removed when dropped, even if the
+/// test panics.
+struct TempDir(std::path::PathBuf);
+
+impl std::ops::Deref for TempDir {
+    type Target = std::path::PathBuf;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn temp_dir(name: &str) -> TempDir {
+    let dir = std::env::temp_dir().join(format!("starterm-fs-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("create_dir_all");
+    TempDir(dir)
+}
+
+fn precompressed_dir(name: &str) -> TempDir {
+    let dir = temp_dir(name);
+    fs::write(dir.join("app.js"), "console.log('plain');").unwrap();
+    fs::write(dir.join("app.js.gz"), "gzip bytes").unwrap();
+    fs::write(dir.join("app.js.br"), "brotli bytes").unwrap();
+    dir
+}
+
+#[tokio::test]
+async fn dir_precompressed() {
+    let _ = pretty_env_logger::try_init();
+
+    let base = precompressed_dir("dir-precompressed");
+    let file = starterm::fs::dir_with(
+        base.clone(),
+        starterm::fs::DirOptions::new().precompressed(true),
+    );
+
+    let res = starterm::test::request()
+        .path("/app.js")
+        .header("accept-encoding", "gzip, br")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-encoding"], "br");
+    assert_eq!(res.headers()["content-type"], "text/javascript");
+    assert_eq!(res.headers()["content-length"], "12");
+    assert_eq!(res.headers()["vary"], "accept-encoding");
+    assert_eq!(res.body(), "brotli bytes");
+
+    let res = starterm::test::request()
+        .path("/app.js")
+        .header("accept-encoding", "gzip, br;q=0.5")
+        .reply(&file)
+        .await;
+    assert_eq!(res.headers()["content-encoding"], "gzip");
+    assert_eq!(res.body(), "gzip bytes");
+
+    // zstd is accepted, but there is no `.zst` sidecar
+    let res = starterm::test::request()
+        .path("/app.js")
+        .header("accept-encoding", "zstd")
+        .reply(&file)
+        .await;
+    assert_eq!(res.headers().get("content-encoding"), None);
+    assert_eq!(res.headers()["vary"], "accept-encoding");
+    assert_eq!(res.body(), "console.log('plain');");
+
+    let res = starterm::test::request()
+        .path("/app.js")
+        .header("accept-encoding", "gzip;q=0, br;q=0")
+        .reply(&file)
+        .await;
+    assert_eq!(res.headers().get("content-encoding"), None);
+    assert_eq!(res.body(), "console.log('plain');");
+}
+
+#[tokio::test]
+async fn file_precompressed() {
+    let _ = pretty_env_logger::try_init();
+
+    let base = precompressed_dir("file-precompressed");
+
+    let plain = starterm::fs::file(base.join("app.js"));
+    let res = starterm::test::request()
+        .header("accept-encoding", "gzip")
+        .reply(&plain)
+        .await;
+    assert_eq!(res.headers().get("content-encoding"), None);
+    assert_eq!(res.headers().get("vary"), None);
+    assert_eq!(res.body(), "console.log('plain');");
+
+    let file = starterm::fs::file_with(
+        base.join("app.js"),
+        starterm::fs::FileOptions::new().precompressed(true),
+    );
+    let res = starterm::test::request()
+        .header("accept-encoding", "gzip")
+        .header("range", "bytes=0-3")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(res.headers()["content-encoding"], "gzip");
+    assert_eq!(res.headers()["content-range"], "bytes 0-3/10");
+    assert_eq!(res.body(), "gzip");
+}
+
+#[tokio::test]
+async fn dir_index_files() {
+    let _ = pretty_env_logger::try_init();
+
+    let base = temp_dir("index-files");
+    fs::create_dir(base.join("docs")).unwrap();
+    fs::write(base.join("docs/index.htm"), "htm").unwrap();
+
+    let file = starterm::fs::dir(base.clone());
+    let res = starterm::test::request().path("/docs/").reply(&file).await;
+    assert_eq!(res.status(), 404);
+
+    let file = starterm::fs::dir_with(
+        base.clone(),
+        starterm::fs::DirOptions::new().index_files(["index.html", "index.htm"]),
+    );
+    let res = starterm::test::request().path("/docs/").reply(&file).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), "htm");
+}
+
+#[tokio::test]
+async fn dir_hidden_files() {
+    let _ = pretty_env_logger::try_init();
+
+    let base = temp_dir("hidden-files");
+    fs::write(base.join(".env"), "SECRET=1").unwrap();
+    fs::create_dir(base.join(".git")).unwrap();
+    fs::write(base.join(".git/config"), "[core]").unwrap();
+
+    let file = starterm::fs::dir(base.clone());
+    for path in &["/.env", "/.git/config", "/%2Eenv"] {
+        let res = starterm::test::request().path(path).reply(&file).await;
+        assert_eq!(res.status(), 404, "{}", path);
+    }
+
+    let file = starterm::fs::dir_with(
+        base.clone(),
+        starterm::fs::DirOptions::new().hidden_files(true),
+    );
+    let res = starterm::test::request()
+        .path("/.git/config")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn dir_symlinks_outside_base() {
+    let _ = pretty_env_logger::try_init();
+
+    let base = temp_dir("symlinks");
+    let outside = temp_dir("symlinks-outside");
+    fs::write(outside.join("secret.txt"), "secret").unwrap();
+    fs::write(base.join("public.txt"), "public").unwrap();
+    std::os::unix::fs::symlink(outside.join("secret.txt"), base.join("secret.txt")).unwrap();
+    std::os::unix::fs::symlink(base.join("public.txt"), base.join("alias.txt")).unwrap();
+
+    let file = starterm::fs::dir(base.clone());
+    let res = starterm::test::request()
+        .path("/secret.txt")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let file = starterm::fs::dir_with(
+        base.clone(),
+        starterm::fs::DirOptions::new().symlinks_outside_base(false),
+    );
+    let res = starterm::test::request()
+        .path("/secret.txt")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 404);
+
+    let res = starterm::test::request()
+        .path("/alias.txt")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), "public");
+}
+
+#[tokio::test]
+async fn dir_cache_control() {
+    let _ = pretty_env_logger::try_init();
+
+    let file = starterm::fs::dir_with(
+        "examples",
+        starterm::fs::DirOptions::new()
+            .cache_control_for_path("dir", "no-cache")
+            .cache_control_for_extension(".RS", "public, max-age=31536000, immutable")
+            .cache_control("public, max-age=60"),
+    );
+
+    let res = starterm::test::request()
+        .path("/todos.rs")
+        .reply(&file)
+        .await;
+    assert_eq!(
+        res.headers()["cache-control"],
+        "public, max-age=31536000, immutable"
+    );
+
+    let res = starterm::test::request()
+        .path("/dir/another.html")
+        .reply(&file)
+        .await;
+    assert_eq!(res.headers()["cache-control"], "no-cache");
+
+    let res = starterm::test::request()
+        .path("/README.md")
+        .reply(&file)
+        .await;
+    assert_eq!(res.headers()["cache-control"], "public, max-age=60");
+
+    let res = starterm::test::request()
+        .path("/README.md")
+        .header("if-none-match", &res.headers()["etag"])
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 304);
+    assert_eq!(res.headers()["cache-control"], "public, max-age=60");
+
+    let file = starterm::fs::file_with(
+        "README.md",
+        starterm::fs::FileOptions::new().cache_control("no-store"),
+    );
+    let res = starterm::test::request().reply(&file).await;
+    assert_eq!(res.headers()["cache-control"], "no-store");
+}
+
+#[tokio::test]
+async fn dir_listing() {
+    let _ = pretty_env_logger::try_init();
+
+    let base = temp_dir("listing");
+    fs::create_dir(base.join("sub dir")).unwrap();
+    fs::write(base.join("<b>&.txt"), "12345").unwrap();
+    fs::write(base.join(".secret"), "").unwrap();
+    fs::create_dir(base.join("with-index")).unwrap();
+    fs::write(base.join("with-index/index.html"), "index").unwrap();
+
+    // not listed by default
+    let file = starterm::fs::dir(base.clone());
+    let res = starterm::test::request()
+        .path("/sub%20dir/")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 404);
+
+    let file = starterm::fs::dir_with(base.clone(), starterm::fs::DirOptions::new().listing(true));
+
+    let res = starterm::test::request().path("/").reply(&file).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-type"], "text/html; charset=utf-8");
+    let html = std::str::from_utf8(res.body()).unwrap();
+    assert!(
+        html.contains(r#"<a href="sub%20dir/">sub dir/</a>"#),
+        "{}",
+        html
+    );
+    assert!(
+        html.contains(r#"<a href="%3Cb%3E%26.txt">&lt;b&gt;&amp;.txt</a></td><td>5</td>"#),
+        "{}",
+        html
+    );
+    assert!(!html.contains("secret"), "{}", html);
+    assert!(!html.contains("../"), "{}", html);
+
+    let res = starterm::test::request()
+        .path("/sub%20dir/")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert!(std::str::from_utf8(res.body()).unwrap().contains("../"));
+
+    // directories are redirected to a trailing slash
+    let res = starterm::test::request()
+        .path("/sub%20dir?sort=name")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 301);
+    assert_eq!(res.headers()["location"], "/sub%20dir/?sort=name");
+
+    // index files still win
+    let res = starterm::test::request()
+        .path("/with-index/")
+        .reply(&file)
+        .await;
+    assert_eq!(res.body(), "index");
+
+    let res = starterm::test::request()
+        .path("/")
+        .header("accept", "text/html;q=0.5, application/json")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-type"], "application/json");
+    let json: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    let entries = json.as_array().unwrap();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0]["name"], "sub dir");
+    assert_eq!(entries[0]["type"], "dir");
+    assert_eq!(entries[2]["name"], "<b>&.txt");
+    assert_eq!(entries[2]["type"], "file");
+    assert_eq!(entries[2]["size"], 5);
+    assert!(entries[2]["modified"].is_u64());
+
+    // media ranges match the listing formats, the most specific wins
+    for (accept, content_type) in [
+        ("application/*", "application/json"),
+        ("text/*", "text/html; charset=utf-8"),
+        ("*/*", "text/html; charset=utf-8"),
+        ("text/html;q=0.5, */*", "application/json"),
+        ("text/*;q=0.5, application/json", "application/json"),
+        ("application/json;q=0, */*", "text/html; charset=utf-8"),
+        ("text/html;q=0, */*", "application/json"),
+    ] {
+        let res = starterm::test::request()
+            .path("/")
+            .header("accept", accept)
+            .reply(&file)
+            .await;
+        assert_eq!(res.headers()["content-type"], content_type, "{}", accept);
+    }
+
+    // the usual protections apply
+    for path in &["/.secret", "/../", "/%2E%2E/"] {
+        let res = starterm::test::request().path(path).reply(&file).await;
+        assert_eq!(res.status(), 404, "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn dir_fallback() {
+    let _ = pretty_env_logger::try_init();
+
+    let base = temp_dir("fallback");
+    fs::write(base.join("index.html"), "<app>").unwrap();
+    fs::write(base.join("app.js"), "js").unwrap();
+
+    let file = starterm::fs::dir_with(
+        base.clone(),
+        starterm::fs::DirOptions::new()
+            .fallback("index.html")
+            .cache_control("public, max-age=60"),
+    );
+
+    let res = starterm::test::request()
+        .path("/settings/profile")
+        .header("accept", "text/html,application/xhtml+xml,*/*;q=0.8")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-type"], "text/html");
+    assert_eq!(res.headers()["cache-control"], "no-cache");
+    assert_eq!(res.body(), "<app>");
+
+    // conditionals apply to the fallback
+    let res = starterm::test::request()
+        .path("/settings/profile")
+        .header("accept", "text/html")
+        .header("if-none-match", &res.headers()["etag"])
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 304);
+    assert_eq!(res.headers()["cache-control"], "no-cache");
+
+    // media ranges that include text/html, like the default of curl
+    for accept in ["*/*", "text/*", "application/json, */*;q=0.1"] {
+        let res = starterm::test::request()
+            .path("/settings/profile")
+            .header("accept", accept)
+            .reply(&file)
+            .await;
+        assert_eq!(res.body(), "<app>", "{}", accept);
+    }
+
+    // existing files are served as usual
+    let res = starterm::test::request()
+        .path("/app.js")
+        .header("accept", "text/html")
+        .reply(&file)
+        .await;
+    assert_eq!(res.body(), "js");
+    assert_eq!(res.headers()["cache-control"], "public, max-age=60");
+
+    // asset-looking misses
+    let res = starterm::test::request()
+        .path("/app.js.map")
+        .header("accept", "text/html")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 404);
+
+    // not a page navigation
+    for accept in ["application/json", "application/*", "text/*;q=0, */*"] {
+        let res = starterm::test::request()
+            .path("/api/users")
+            .header("accept", accept)
+            .reply(&file)
+            .await;
+        assert_eq!(res.status(), 404, "{}", accept);
+    }
+
+    let res = starterm::test::request()
+        .method("POST")
+        .path("/settings/profile")
+        .header("accept", "text/html")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 405);
+}
+
+#[tokio::test]
+async fn embedded() {
+    use starterm::fs::EmbeddedFiles;
+
+    let _ = pretty_env_logger::try_init();
+
+    let files = vec![
+        ("/index.html", &b"<h1>index</h1>"[..]),
+        ("css/app.css", &b"h1 { color: red; }"[..]),
+        ("docs/index.html", &b"docs"[..]),
+        (".env", &b"SECRET=1"[..]),
+    ]
+    .into_iter()
+    .collect::<EmbeddedFiles>();
+    let file = starterm::fs::embedded(files);
+
+    let res = starterm::test::request()
+        .path("/css/app.css")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-type"], "text/css");
+    assert_eq!(res.headers()["content-length"], "18");
+    assert_eq!(res.headers()["accept-ranges"], "bytes");
+    assert_eq!(res.body(), "h1 { color: red; }");
+    let etag = res.headers()["etag"].clone();
+    let last_modified = res.headers()["last-modified"].clone();
+
+    let res = starterm::test::request().path("/").reply(&file).await;
+    assert_eq!(res.body(), "<h1>index</h1>");
+    let res = starterm::test::request().path("/docs").reply(&file).await;
+    assert_eq!(res.body(), "docs");
+
+    let res = starterm::test::request()
+        .path("/css/app.css")
+        .header("if-none-match", &etag)
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 304);
+
+    let res = starterm::test::request()
+        .path("/css/app.css")
+        .header("if-modified-since", &last_modified)
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 304);
+
+    let res = starterm::test::request()
+        .path("/css/app.css")
+        .header("range", "bytes=0-1")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(res.headers()["content-range"], "bytes 0-1/18");
+    assert_eq!(res.body(), "h1");
+
+    let res = starterm::test::request()
+        .path("/css/app.css")
+        .header("range", "bytes=100-")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 416);
+
+    let res = starterm::test::request()
+        .method("HEAD")
+        .path("/css/app.css")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let res = starterm::test::request()
+        .method("POST")
+        .path("/css/app.css")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 405);
+
+    for path in &[
+        "/missing.css",
+        "/.env",
+        "/../css/app.css",
+        "/css/%2E%2E/index.html",
+    ] {
+        let res = starterm::test::request().path(path).reply(&file).await;
+        assert_eq!(res.status(), 404, "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn read_buffer_size_and_read_ahead() {
+    use starterm::fs::{DirOptions, FileOptions};
+
+    let _ = pretty_env_logger::try_init();
+
+    let contents = fs::read("README.md").expect("fs::read README.md");
+    let file = starterm::fs::file_with(
+        "README.md",
+        FileOptions::new().read_buffer_size(7).read_ahead(2),
+    );
+
+    let res = starterm::test::request().reply(&file).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), &*contents);
+
+    let res = starterm::test::request()
+        .header("range", "bytes=100-200, 300-310")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(
+        res.headers()["content-length"],
+        res.body().len().to_string()
+    );
+
+    let dir = starterm::fs::dir_with("examples", DirOptions::new().read_ahead(1));
+    let res = starterm::test::request()
+        .path("/todos.rs")
+        .reply(&dir)
+        .await;
+    assert_eq!(res.body(), &*fs::read("examples/todos.rs").unwrap());
+}
+
+#[tokio::test]
+async fn dir_from_storage() {
+    use std::collections::HashMap;
+    use std::io;
+    use std::ops::Range;
+    use std::path::{Path, PathBuf};
+
+    use bytes::Bytes;
+    use futures_util::future::{self, BoxFuture};
+    use futures_util::stream::{self, BoxStream, StreamExt};
+    use starterm::fs::{DirOptions, Storage, StorageFile, StorageMetadata};
+
+    struct MemoryStorage(HashMap<PathBuf, Bytes>);
+
+    struct MemoryFile(Bytes, StorageMetadata);
+
+    impl Storage for MemoryStorage {
+        type File = MemoryFile;
+
+        fn metadata<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, io::Result<StorageMetadata>> {
+            let meta = match self.0.get(path) {
+                Some(contents) => Ok(StorageMetadata::file(contents.len() as u64).with_id(7)),
+                None if self.0.keys().any(|p| p.starts_with(path)) => Ok(StorageMetadata::dir()),
+                None => Err(io::ErrorKind::NotFound.into()),
+            };
+            Box::pin(future::ready(meta))
+        }
+
+        fn open<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, io::Result<MemoryFile>> {
+            let file = match self.0.get(path) {
+                Some(contents) => Ok(MemoryFile(
+                    contents.clone(),
+                    StorageMetadata::file(contents.len() as u64).with_id(7),
+                )),
+                None => Err(io::ErrorKind::NotFound.into()),
+            };
+            Box::pin(future::ready(file))
+        }
+    }
+
+    impl StorageFile for MemoryFile {
+        fn metadata(&self) -> &StorageMetadata {
+            &self.1
+        }
+
+        fn read(
+            &self,
+            range: Range<u64>,
+            _buf_size: Option<usize>,
+        ) -> BoxStream<'static, io::Result<Bytes>> {
+            let chunk = self.0.slice(range.start as usize..range.end as usize);
+            stream::once(future::ok(chunk)).boxed()
+        }
+    }
+
+    let _ = pretty_env_logger::try_init();
+
+    let storage = MemoryStorage(
+        vec![
+            (
+                PathBuf::from("index.html"),
+                Bytes::from_static(b"<h1>index</h1>"),
+            ),
+            (
+                PathBuf::from("js/app.js"),
+                Bytes::from_static(b"console.log(1);"),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    let file = starterm::fs::dir_from_with(storage, DirOptions::new().listing(true));
+
+    let res = starterm::test::request()
+        .path("/js/app.js")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-type"], "text/javascript");
+    assert_eq!(res.headers()["content-length"], "15");
+    assert_eq!(res.headers()["etag"], "\"7-f-0\"");
+    assert!(!res.headers().contains_key("last-modified"));
+    assert_eq!(res.body(), "console.log(1);");
+
+    let res = starterm::test::request()
+        .path("/js/app.js")
+        .header("if-none-match", "\"7-f-0\"")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 304);
+
+    let res = starterm::test::request()
+        .path("/js/app.js")
+        .header("range", "bytes=8-")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(res.headers()["content-range"], "bytes 8-14/15");
+    assert_eq!(res.body(), "log(1);");
+
+    let res = starterm::test::request().path("/").reply(&file).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), "<h1>index</h1>");
+
+    // Listing needs `Storage::read_dir`, which this storage doesn't implement.
+    let res = starterm::test::request().path("/js/").reply(&file).await;
+    assert_eq!(res.status(), 404);
+
+    let res = starterm::test::request()
+        .path("/missing.js")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 404);
+
+    // The paths of the files are the ones in the storage.
+    let file = starterm::fs::dir_from(starterm::fs::LocalStorage::new("examples"));
+    let served = starterm::test::request()
+        .path("/todos.rs")
+        .filter(&file)
+        .await
+        .expect("filter");
+    assert_eq!(served.path(), Path::new("todos.rs"));
+}
+
+#[tokio::test]
+async fn file_cache() {
+    use std::time::Duration;
+
+    use starterm::fs::{DirOptions, FileCache, FileOptions};
+
+    let _ = pretty_env_logger::try_init();
+
+    let base = temp_dir("cache");
+    fs::write(base.join("a.txt"), "aaaa").unwrap();
+    fs::write(base.join("b.txt"), "bbbb").unwrap();
+    fs::write(base.join("big.txt"), vec![b'x'; 100]).unwrap();
+
+    let cache = FileCache::new().max_entries(1).max_file_size(50);
+    let dir = starterm::fs::dir_with(base.clone(), DirOptions::new().cache(cache.clone()));
+
+    let res = starterm::test::request().path("/a.txt").reply(&dir).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), "aaaa");
+    assert_eq!((cache.hits(), cache.misses()), (0, 1));
+    let etag = res.headers()["etag"].clone();
+
+    let res = starterm::test::request().path("/a.txt").reply(&dir).await;
+    assert_eq!(res.body(), "aaaa");
+    assert_eq!(res.headers()["etag"], etag);
+    assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+    let res = starterm::test::request()
+        .path("/a.txt")
+        .header("range", "bytes=1-2")
+        .reply(&dir)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(res.body(), "aa");
+
+    let res = starterm::test::request()
+        .path("/a.txt")
+        .header("if-none-match", &etag)
+        .reply(&dir)
+        .await;
+    assert_eq!(res.status(), 304);
+    assert_eq!((cache.hits(), cache.misses()), (3, 1));
+
+    // A change of size is noticed on the next request.
+    fs::write(base.join("a.txt"), "aaaaaa").unwrap();
+    let res = starterm::test::request().path("/a.txt").reply(&dir).await;
+    assert_eq!(res.body(), "aaaaaa");
+    assert_eq!((cache.hits(), cache.misses()), (3, 2));
+    assert_eq!((cache.entries(), cache.bytes()), (1, 6));
+
+    // Only one entry fits, `a.txt` is evicted.
+    let res = starterm::test::request().path("/b.txt").reply(&dir).await;
+    assert_eq!(res.body(), "bbbb");
+    assert_eq!((cache.entries(), cache.bytes()), (1, 4));
+
+    // Too large to be cached.
+    for _ in 0..2 {
+        let res = starterm::test::request().path("/big.txt").reply(&dir).await;
+        assert_eq!(res.body().len(), 100);
+    }
+    assert_eq!((cache.hits(), cache.misses()), (3, 5));
+    assert_eq!(cache.entries(), 1);
+
+    // Within the TTL, files aren't checked for changes.
+    let cache = FileCache::new().ttl(Duration::from_secs(3600));
+    let file = starterm::fs::file_with(base.join("b.txt"), FileOptions::new().cache(cache.clone()));
+    let res = starterm::test::request().reply(&file).await;
+    assert_eq!(res.body(), "bbbb");
+    fs::write(base.join("b.txt"), "changed").unwrap();
+    let res = starterm::test::request().reply(&file).await;
+    assert_eq!(res.body(), "bbbb");
+    assert_eq!((cache.hits(), cache.misses()), (1, 1));
+}
+
+#[tokio::test]
+async fn file_cache_shared_by_storages() {
+    use starterm::fs::{DirOptions, FileCache, LocalStorage};
+
+    let _ = pretty_env_logger::try_init();
+
+    let one = temp_dir("cache-one");
+    let two = temp_dir("cache-two");
+    fs::write(one.join("a.txt"), "one").unwrap();
+    fs::write(two.join("a.txt"), "two").unwrap();
+
+    // Both storages serve `a.txt`, and must not get each other's file.
+    let cache = FileCache::new();
+    let options = DirOptions::new().cache(cache.clone());
+    let dir_one = starterm::fs::dir_from_with(LocalStorage::new(one.clone()), options.clone());
+    let dir_two = starterm::fs::dir_from_with(LocalStorage::new(two.clone()), options);
+
+    for _ in 0..2 {
+        let res = starterm::test::request()
+            .path("/a.txt")
+            .reply(&dir_one)
+            .await;
+        assert_eq!(res.body(), "one");
+        let res = starterm::test::request()
+            .path("/a.txt")
+            .reply(&dir_two)
+            .await;
+        assert_eq!(res.body(), "two");
+    }
+    assert_eq!((cache.hits(), cache.misses()), (2, 2));
+    assert_eq!(cache.entries(), 2);
+}
@@ -1,6 +1,7 @@
 #![deny(warnings)]
 use bytes::BufMut;
 use futures_util::{TryFutureExt, TryStreamExt};
+use starterm::reply::MultipartPart;
 use starterm::{multipart, Filter};
 
 #[tokio::test]
@@ -100,3 +101,496 @@ async fn max_length_can_be_disabled() {
     let resp = req.filter(&route).await;
     assert!(resp.is_ok());
 }
+
+// Parts are (name, filename, contents).
+fn form_request(parts: &[(&str, Option<&str>, &str)]) -> starterm::test::RequestBuilder {
+    let boundary = "--abcdef1234--";
+    let mut body = String::new();
+    for (name, filename, contents) in parts {
+        body.push_str(&format!("--{}\r\n", boundary));
+        match filename {
+            Some(filename) => body.push_str(&format!(
+                "content-disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\r\n",
+                name, filename
+            )),
+            None => body.push_str(&format!(
+                "content-disposition: form-data; name=\"{}\"\r\n\r\n",
+                name
+            )),
+        }
+        body.push_str(contents);
+        body.push_str("\r\n");
+    }
+    body.push_str(&format!("--{}--\r\n", boundary));
+
+    starterm::test::request()
+        .method("POST")
+        .header("content-length", body.len())
+        .header(
+            "content-type",
+            format!("multipart/form-data; boundary={}", boundary),
+        )
+        .body(body)
+}
+
+fn upload_request(filename: &str, contents: &str) -> starterm::test::RequestBuilder {
+    form_request(&[("upload", Some(filename), contents)])
+}
+
+#[tokio::test]
+async fn save_part_to_file() {
+    let _ = pretty_env_logger::try_init();
+
+    let dir = std::env::temp_dir().join(format!("starterm-multipart-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let save_dir = dir.clone();
+    let route = multipart::form().and_then(move |mut form: multipart::FormData| {
+        let dir = save_dir.clone();
+        async move {
+            let part = form.try_next().await.unwrap().unwrap();
+            let name = part.sanitized_filename().unwrap().to_owned();
+            let saved = part
+                .save_to_with(dir.join(&name), multipart::SaveOptions::new().sha1(true))
+                .await
+                .unwrap();
+            Ok::<_, starterm::Rejection>((name, saved))
+        }
+    });
+
+    let (name, saved) = upload_request("C:\\Users\\me\\hello.txt", "hello world")
+        .filter(&route)
+        .await
+        .unwrap();
+    assert_eq!(name, "hello.txt");
+    assert_eq!(saved.path(), dir.join("hello.txt"));
+    assert_eq!(saved.size(), 11);
+    assert_eq!(
+        saved.sha1(),
+        Some("2aae6c35c94fcfb415dbe95f408b9ce91ee846ed")
+    );
+    assert_eq!(std::fs::read(saved.path()).unwrap(), b"hello world");
+
+    let route = multipart::form().and_then(|mut form: multipart::FormData| async move {
+        let part = form.try_next().await.unwrap().unwrap();
+        Ok::<_, starterm::Rejection>(part.save_to_temp().await.unwrap())
+    });
+    let saved = upload_request("a.txt", "temporary")
+        .filter(&route)
+        .await
+        .unwrap();
+    assert!(saved.path().starts_with(std::env::temp_dir()));
+    assert_eq!(saved.sha1(), None);
+    assert_eq!(std::fs::read(saved.path()).unwrap(), b"temporary");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(saved.path())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    std::fs::remove_file(saved.path()).unwrap();
+
+    // Over the size limit, the partial file is removed, and the file
+    // already at the path is kept.
+    std::fs::write(dir.join("big.txt"), "original").unwrap();
+    let save_dir = dir.clone();
+    let route = multipart::form().and_then(move |mut form: multipart::FormData| {
+        let path = save_dir.join("big.txt");
+        async move {
+            let part = form.try_next().await.unwrap().unwrap();
+            let saved = part
+                .save_to_with(&path, multipart::SaveOptions::new().max_size(4))
+                .await;
+            let saved = saved.map_err(|e| {
+                assert!(e.limit().is_some());
+                e.to_string()
+            });
+            Ok::<_, starterm::Rejection>(saved.map(|_| ()))
+        }
+    });
+    let res = upload_request("big.txt", "too large")
+        .filter(&route)
+        .await
+        .unwrap();
+    assert_eq!(
+        res,
+        Err("Multipart part is over the length limit".to_owned())
+    );
+    assert_eq!(std::fs::read(dir.join("big.txt")).unwrap(), b"original");
+    let mut names = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["big.txt", "hello.txt"]);
+
+    // A saved part replaces the file at the path.
+    let res = upload_request("big.txt", "tiny")
+        .filter(&route)
+        .await
+        .unwrap();
+    assert_eq!(res, Ok(()));
+    assert_eq!(std::fs::read(dir.join("big.txt")).unwrap(), b"tiny");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn sanitized_filename() {
+    let route = multipart::form().and_then(|mut form: multipart::FormData| async move {
+        let part = form.try_next().await.unwrap().unwrap();
+        Ok::<_, starterm::Rejection>(part.sanitized_filename().map(str::to_owned))
+    });
+
+    for (filename, expected) in [
+        ("report.pdf", Some("report.pdf")),
+        ("../../etc/passwd", Some("passwd")),
+        ("dir/..", None),
+        ("..", None),
+        ("..hidden", None),
+        ("uploads/", None),
+    ] {
+        let res = upload_request(filename, "x").filter(&route).await.unwrap();
+        assert_eq!(res.as_deref(), expected, "{:?}", filename);
+    }
+}
+
+#[derive(Debug, serde_derive::Deserialize)]
+struct Upload {
+    title: String,
+    count: u32,
+}
+
+#[tokio::test]
+async fn typed_form() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = multipart::typed::<Upload>().spill_threshold(8);
+    let mut form = form_request(&[
+        ("title", None, "holiday"),
+        ("small", Some("a.txt"), "tiny"),
+        ("count", None, "2"),
+        ("large", Some("b.txt"), "more than eight bytes"),
+    ])
+    .filter(&route)
+    .await
+    .unwrap();
+
+    assert_eq!(form.fields().title, "holiday");
+    assert_eq!(form.fields().count, 2);
+    assert_eq!(form.files().len(), 2);
+
+    let small = form.file("small").unwrap();
+    assert_eq!(small.filename(), Some("a.txt"));
+    assert_eq!(small.size(), 4);
+    assert_eq!(small.bytes().unwrap().as_ref(), b"tiny");
+    assert!(small.temp_path().is_none());
+
+    let large = form.take_file("large").unwrap();
+    assert!(large.bytes().is_none());
+    let temp = large.temp_path().unwrap().to_owned();
+    assert_eq!(std::fs::read(&temp).unwrap(), b"more than eight bytes");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&temp).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let dest = std::env::temp_dir().join(format!("starterm-typed-{}", std::process::id()));
+    large.persist_to(&dest).await.unwrap();
+    assert_eq!(std::fs::read(&dest).unwrap(), b"more than eight bytes");
+    assert!(!temp.exists());
+    std::fs::remove_file(&dest).unwrap();
+
+    // Dropping the form removes its temporary files.
+    let form = form_request(&[
+        ("title", None, "t"),
+        ("count", None, "1"),
+        ("large", Some("b.txt"), "more than eight bytes"),
+    ])
+    .filter(&route)
+    .await
+    .unwrap();
+    let temp = form.file("large").unwrap().temp_path().unwrap().to_owned();
+    drop(form);
+    assert!(removed(&temp).await);
+}
+
+// Dropped temporary files are removed in the background.
+async fn removed(path: &std::path::Path) -> bool {
+    for _ in 0..100 {
+        if !path.exists() {
+            return true;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn typed_form_rejections() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = multipart::typed::<Upload>()
+        .max_field_length(8)
+        .max_file_length(4);
+
+    let res = form_request(&[("title", None, "t")])
+        .reply(&route.clone().map(|_| ""))
+        .await;
+    assert_eq!(res.status(), 400, "missing field");
+
+    let res = form_request(&[
+        ("title", None, "t"),
+        ("count", None, "1"),
+        ("count", None, "2"),
+    ])
+    .reply(&route.clone().map(|_| ""))
+    .await;
+    assert_eq!(res.status(), 400, "duplicate field");
+
+    let res = form_request(&[("title", None, "t"), ("count", None, "many")])
+        .reply(&route.clone().map(|_| ""))
+        .await;
+    assert_eq!(res.status(), 400, "invalid field");
+
+    let res = form_request(&[("title", None, "much too long"), ("count", None, "1")])
+        .reply(&route.clone().map(|_| ""))
+        .await;
+    assert_eq!(res.status(), 413, "field too long");
+
+    let res = form_request(&[
+        ("title", None, "t"),
+        ("count", None, "1"),
+        ("file", Some("a.txt"), "too long"),
+    ])
+    .reply(&route.clone().map(|_| ""))
+    .await;
+    assert_eq!(res.status(), 413, "file too long");
+}
+
+async fn read_parts(mut form: multipart::FormData) -> Result<String, starterm::Rejection> {
+    let mut count = 0;
+    while let Some(part) = form.try_next().await? {
+        part.stream().try_for_each(|_| async { Ok(()) }).await?;
+        count += 1;
+    }
+    Ok(count.to_string())
+}
+
+#[tokio::test]
+async fn form_limits() {
+    let _ = pretty_env_logger::try_init();
+
+    let parts = [("a", None, "1"), ("b", None, "22"), ("c", None, "333")];
+
+    let route = multipart::form().max_parts(3).and_then(read_parts);
+    let res = form_request(&parts).reply(&route).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), "3");
+
+    let route = multipart::form().max_parts(2).and_then(read_parts);
+    let res = form_request(&parts).reply(&route).await;
+    assert_eq!(res.status(), 413);
+    assert_eq!(res.body(), "Multipart body has too many parts");
+
+    let route = multipart::form().max_field_length(2).and_then(read_parts);
+    let res = form_request(&parts).reply(&route).await;
+    assert_eq!(res.status(), 413);
+    assert_eq!(res.body(), "Multipart part is over the length limit");
+
+    let route = multipart::form()
+        .allowed_fields(vec!["a", "b"])
+        .and_then(read_parts);
+    let res = form_request(&parts).reply(&route).await;
+    assert_eq!(res.status(), 400);
+    assert_eq!(res.body(), "Multipart field is not allowed");
+
+    let route = multipart::form()
+        .max_part_headers_length(20)
+        .and_then(read_parts);
+    let res = form_request(&parts).reply(&route).await;
+    assert_eq!(res.status(), 413);
+    assert_eq!(
+        res.body(),
+        "Multipart part headers are over the length limit"
+    );
+
+    let route = multipart::form()
+        .max_part_headers_length(1024)
+        .and_then(read_parts);
+    let res = form_request(&parts).reply(&route).await;
+    assert_eq!(res.status(), 200);
+
+    let route = multipart::form().max_parts(1).and_then(read_parts).recover(
+        |rej: starterm::Rejection| async move {
+            assert!(rej.find::<multipart::MultipartLimitError>().is_some());
+            Ok::<_, std::convert::Infallible>("recovered")
+        },
+    );
+    let res = form_request(&parts).reply(&route).await;
+    assert_eq!(res.body(), "recovered");
+
+    let route = multipart::typed::<Upload>().form(multipart::form().max_parts(1));
+    let res = form_request(&[("title", None, "t"), ("count", None, "1")])
+        .reply(&route.map(|_| ""))
+        .await;
+    assert_eq!(res.status(), 413);
+}
+
+#[tokio::test]
+async fn form_allowed_content_types() {
+    let _ = pretty_env_logger::try_init();
+
+    let boundary = "--abcdef1234--";
+    let request = |content_type: &str| {
+        let body = format!(
+            "\
+             --{0}\r\n\
+             content-disposition: form-data; name=\"file\"; filename=\"a\"\r\n\
+             {1}\r\n\
+             data\r\n\
+             --{0}--\r\n\
+             ",
+            boundary, content_type
+        );
+        starterm::test::request()
+            .method("POST")
+            .header("content-length", body.len())
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .body(body)
+    };
+
+    let route = multipart::form()
+        .allowed_content_types(vec!["image/*", "application/pdf"])
+        .and_then(read_parts);
+
+    let res = request("content-type: image/png\r\n").reply(&route).await;
+    assert_eq!(res.status(), 200);
+    let res = request("content-type: Application/PDF\r\n")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let res = request("content-type: text/html\r\n").reply(&route).await;
+    assert_eq!(res.status(), 415);
+    assert_eq!(res.body(), "Multipart part content-type is not allowed");
+
+    // Without a content-type, a part is `text/plain`.
+    let res = request("").reply(&route).await;
+    assert_eq!(res.status(), 415);
+}
+
+#[tokio::test]
+async fn multipart_reply() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = starterm::any().map(|| {
+        starterm::reply::multipart()
+            .boundary("abc123")
+            .part(MultipartPart::json(&[1, 2]))
+            .part(
+                MultipartPart::new("data")
+                    .content_type("application/octet-stream")
+                    .filename("a \"b\".bin"),
+            )
+    });
+    let res = starterm::test::request().reply(&route).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        res.headers()["content-type"],
+        "multipart/mixed; boundary=abc123"
+    );
+
+    let body = "\
+        --abc123\r\n\
+        content-type: application/json\r\n\r\n\
+        [1,2]\r\n\
+        --abc123\r\n\
+        content-disposition: attachment; filename=\"a %22b%22.bin\"\r\n\
+        content-type: application/octet-stream\r\n\r\n\
+        data\r\n\
+        --abc123--\r\n";
+    assert_eq!(res.headers()["content-length"], body.len().to_string());
+    assert_eq!(res.body(), body);
+
+    let route = starterm::any().map(|| {
+        let chunks = vec![Ok::<_, std::io::Error>("he"), Ok("llo")];
+        starterm::reply::multipart()
+            .boundary("abc123")
+            .part(MultipartPart::stream(futures_util::stream::iter(chunks)))
+    });
+    let res = starterm::test::request().reply(&route).await;
+    assert!(!res.headers().contains_key("content-length"));
+    assert_eq!(res.body(), "--abc123\r\n\r\nhello\r\n--abc123--\r\n");
+
+    let route = starterm::any().map(|| starterm::reply::multipart().boundary("a b"));
+    let res = starterm::test::request().reply(&route).await;
+    assert_eq!(res.status(), 500, "invalid boundary");
+
+    let route = starterm::any()
+        .map(|| starterm::reply::multipart().part(MultipartPart::new("").header("x-a", "\n")));
+    let res = starterm::test::request().reply(&route).await;
+    assert_eq!(res.status(), 500, "invalid header");
+}
+
+#[tokio::test]
+async fn multipart_request_builder() {
+    let _ = pretty_env_logger::try_init();
+
+    // The streamed part leaves the request without a content-length.
+    let route = multipart::form()
+        .max_length(None)
+        .and_then(|form: multipart::FormData| async {
+            let parts: Vec<(String, Option<String>, String)> = form
+                .and_then(|part| async move {
+                    let name = part.name().to_owned();
+                    let filename = part.filename().map(str::to_owned);
+                    let mut contents = Vec::new();
+                    let mut stream = part.stream();
+                    while let Some(data) = stream.try_next().await? {
+                        contents.put(data);
+                    }
+                    Ok((name, filename, String::from_utf8(contents).unwrap()))
+                })
+                .try_collect()
+                .await
+                .map_err(multipart::into_rejection)?;
+            Ok::<_, starterm::Rejection>(parts)
+        });
+
+    let stream = futures_util::stream::iter(vec![Ok::<_, std::io::Error>("file "), Ok("body")]);
+    let form = starterm::reply::multipart()
+        .form_data()
+        .part(MultipartPart::new("hello").name("greeting"))
+        .part(
+            MultipartPart::stream(stream)
+                .name("upload")
+                .filename("a.txt"),
+        );
+
+    let parts = starterm::test::request()
+        .method("POST")
+        .multipart(form)
+        .filter(&route)
+        .await
+        .unwrap();
+    assert_eq!(
+        parts,
+        vec![
+            ("greeting".to_owned(), None, "hello".to_owned()),
+            (
+                "upload".to_owned(),
+                Some("a.txt".to_owned()),
+                "file body".to_owned()
+            ),
+        ]
+    );
+}
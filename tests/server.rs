@@ -0,0 +1,316 @@
+#![deny(warnings)]
+use std::net::SocketAddr;
+
+use starterm::{BindError, Filter, ServerConfig};
+
+fn spawn(config: ServerConfig) -> SocketAddr {
+    let routes = starterm::any().map(|| "hello");
+    let (addr, server) = starterm::serve(routes)
+        .config(config)
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr
+}
+
+async fn get(
+    client: &hyper::Client<hyper::client::HttpConnector>,
+    addr: SocketAddr,
+) -> hyper::Result<String> {
+    let uri = format!("http://{}/", addr).parse().unwrap();
+    let res = client.get(uri).await?;
+    let body = hyper::body::to_bytes(res.into_body()).await?;
+    Ok(String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn config_http2_only() {
+    let _ = pretty_env_logger::try_init();
+
+    let addr = spawn(
+        ServerConfig::new()
+            .http2_only()
+            .http2_max_concurrent_streams(10),
+    );
+
+    let h2 = hyper::Client::builder().http2_only(true).build_http();
+    assert_eq!(get(&h2, addr).await.unwrap(), "hello");
+
+    let h1 = hyper::Client::new();
+    assert!(get(&h1, addr).await.is_err(), "HTTP/1 is refused");
+}
+
+#[tokio::test]
+async fn config_http1_only() {
+    let _ = pretty_env_logger::try_init();
+
+    let addr = spawn(
+        ServerConfig::new()
+            .http1_only()
+            .http1_keep_alive(false)
+            .http1_max_buf_size(1024)
+            .tcp_nodelay(false)
+            .tcp_keepalive(std::time::Duration::from_secs(30))
+            .tcp_backlog(16),
+    );
+
+    let h1 = hyper::Client::new();
+    assert_eq!(get(&h1, addr).await.unwrap(), "hello");
+
+    let h2 = hyper::Client::builder().http2_only(true).build_http();
+    assert!(get(&h2, addr).await.is_err(), "HTTP/2 is refused");
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn config_tcp_reuse_port() {
+    let _ = pretty_env_logger::try_init();
+
+    let routes = starterm::any().map(|| "hello");
+    let reuse = || ServerConfig::new().tcp_reuse_port(true);
+
+    // The listeners are dropped with the server futures.
+    let (addr, _server) = starterm::serve(routes)
+        .config(reuse())
+        .try_bind_ephemeral(([127, 0, 0, 1], 0))
+        .unwrap();
+    assert!(starterm::serve(routes)
+        .config(reuse())
+        .try_bind_ephemeral(addr)
+        .is_ok());
+    assert!(
+        starterm::serve(routes).try_bind_ephemeral(addr).is_err(),
+        "SO_REUSEPORT is needed on both"
+    );
+}
+
+#[tokio::test]
+async fn try_bind_errors() {
+    let _ = pretty_env_logger::try_init();
+
+    let routes = starterm::any().map(|| "hello");
+    let (addr, _server) = starterm::serve(routes)
+        .try_bind_ephemeral(([127, 0, 0, 1], 0))
+        .unwrap();
+
+    match starterm::serve(routes).try_bind_ephemeral(addr) {
+        Err(err) => assert!(matches!(bind_error(&err), Some(BindError::AddrInUse(_)))),
+        Ok(_) => panic!("bound an address in use"),
+    }
+    assert!(matches!(
+        starterm::serve(routes).try_run(addr).await,
+        Err(BindError::AddrInUse(_))
+    ));
+
+    // Logs the error instead.
+    starterm::serve(routes).try_bind(addr).await;
+}
+
+fn bind_error(err: &starterm::Error) -> Option<&BindError> {
+    std::error::Error::source(err)?.downcast_ref()
+}
+
+#[cfg(feature = "tls")]
+#[tokio::test]
+async fn try_bind_tls_errors() {
+    let _ = pretty_env_logger::try_init();
+
+    let routes = starterm::any().map(|| "hello");
+    let res = starterm::serve(routes)
+        .tls()
+        .cert_path("examples/tls/cert.pem")
+        .try_bind_ephemeral(([127, 0, 0, 1], 0));
+    match res {
+        Err(err) => assert!(matches!(
+            bind_error(&err),
+            Some(BindError::Tls(starterm::TlsConfigError::EmptyKey))
+        )),
+        Ok(_) => panic!("bound without a key"),
+    }
+
+    let res = starterm::serve(routes)
+        .tls()
+        .cert_path("examples/tls/cert.pem")
+        .key_path("examples/tls/missing.rsa")
+        .try_run(([127, 0, 0, 1], 0))
+        .await;
+    assert!(matches!(
+        res,
+        Err(BindError::Tls(starterm::TlsConfigError::Io(_)))
+    ));
+
+    starterm::serve(routes)
+        .tls()
+        .cert_path("examples/tls/cert.pem")
+        .try_bind(([127, 0, 0, 1], 0))
+        .await;
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn bind_unix() {
+    use starterm::addr::UnixPeer;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let _ = pretty_env_logger::try_init();
+
+    let path = std::env::temp_dir().join(format!("starterm-{}.sock", std::process::id()));
+    // A stale socket, with no server listening.
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+    let routes = starterm::addr::unix_peer().map(|peer: Option<UnixPeer>| {
+        let peer = peer.expect("unix peer");
+        format!("{} {}", peer.path().display(), peer.uid().unwrap())
+    });
+    let server = starterm::serve(routes)
+        .config(ServerConfig::new().unix_socket_mode(0o600))
+        .try_bind_unix(&path)
+        .unwrap();
+    let meta = std::fs::metadata(&path).unwrap();
+    assert_eq!(meta.permissions().mode() & 0o777, 0o600);
+    // The private directory it was bound in is gone.
+    let leftovers = std::fs::read_dir(std::env::temp_dir())
+        .unwrap()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with(&format!(".starterm-{}-", std::process::id()))
+        })
+        .count();
+    assert_eq!(leftovers, 0);
+
+    assert!(matches!(
+        starterm::serve(routes).try_bind_unix(&path),
+        Err(BindError::AddrInUse(_))
+    ));
+
+    let server = tokio::spawn(server);
+    let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut res = String::new();
+    stream.read_to_string(&mut res).await.unwrap();
+    assert!(res.starts_with("HTTP/1.1 200 OK\r\n"), "{}", res);
+    // The socket file is owned by the user running this test.
+    assert!(
+        res.ends_with(&format!("{} {}", path.display(), meta.uid())),
+        "{}",
+        res
+    );
+
+    server.abort();
+    let _ = server.await;
+    assert!(!path.exists(), "socket file is removed");
+}
+
+async fn send_raw(addr: SocketAddr, bytes: &[u8]) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    stream.write_all(bytes).await.unwrap();
+    let mut res = String::new();
+    // A closed connection may also be reset.
+    let _ = stream.read_to_string(&mut res).await;
+    res
+}
+
+fn spawn_remote(config: ServerConfig) -> SocketAddr {
+    let routes = starterm::addr::remote()
+        .and(starterm::conn::info::<starterm::conn::LocalAddr>())
+        .map(
+            |remote: Option<SocketAddr>, local: starterm::conn::LocalAddr| {
+                format!("{} {}", remote.unwrap(), local.0)
+            },
+        );
+    let (addr, server) = starterm::serve(routes)
+        .config(config)
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr
+}
+
+const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n";
+
+#[tokio::test]
+async fn proxy_protocol() {
+    let _ = pretty_env_logger::try_init();
+
+    let addr = spawn_remote(ServerConfig::new().proxy_protocol(true));
+
+    let mut v1 = b"PROXY TCP4 192.0.2.1 198.51.100.2 56324 443\r\n".to_vec();
+    v1.extend_from_slice(REQUEST);
+    let res = send_raw(addr, &v1).await;
+    assert!(res.ends_with("192.0.2.1:56324 198.51.100.2:443"), "{}", res);
+
+    let mut v2 = b"\r\n\r\n\0\r\nQUIT\n\x21\x11\0\x0c".to_vec();
+    v2.extend_from_slice(&[192, 0, 2, 1, 198, 51, 100, 2]);
+    v2.extend_from_slice(&56324u16.to_be_bytes());
+    v2.extend_from_slice(&443u16.to_be_bytes());
+    v2.extend_from_slice(REQUEST);
+    let res = send_raw(addr, &v2).await;
+    assert!(res.ends_with("192.0.2.1:56324 198.51.100.2:443"), "{}", res);
+
+    // UNKNOWN keeps the addresses of the connection.
+    let mut unknown = b"PROXY UNKNOWN\r\n".to_vec();
+    unknown.extend_from_slice(REQUEST);
+    let res = send_raw(addr, &unknown).await;
+    assert!(res.ends_with(&format!(" {}", addr)), "{}", res);
+
+    // A missing or malformed header closes the connection.
+    assert_eq!(send_raw(addr, REQUEST).await, "");
+    let mut malformed = b"PROXY TCP4 192.0.2.1\r\n".to_vec();
+    malformed.extend_from_slice(REQUEST);
+    assert_eq!(send_raw(addr, &malformed).await, "");
+}
+
+#[tokio::test]
+async fn proxy_protocol_trusted() {
+    let _ = pretty_env_logger::try_init();
+
+    let mut v1 = b"PROXY TCP4 192.0.2.1 198.51.100.2 56324 443\r\n".to_vec();
+    v1.extend_from_slice(REQUEST);
+
+    let addr = spawn_remote(
+        ServerConfig::new().proxy_protocol_trusted(vec!["127.0.0.0/8".parse().unwrap()]),
+    );
+    let res = send_raw(addr, &v1).await;
+    assert!(res.ends_with("192.0.2.1:56324 198.51.100.2:443"), "{}", res);
+
+    let addr = spawn_remote(
+        ServerConfig::new().proxy_protocol_trusted(vec!["10.0.0.0/8".parse().unwrap()]),
+    );
+    assert_eq!(send_raw(addr, &v1).await, "", "untrusted peer is closed");
+
+    let addr = spawn_remote(ServerConfig::new().proxy_protocol_trusted(Vec::new()));
+    assert_eq!(send_raw(addr, &v1).await, "", "an empty list trusts no one");
+}
+
+#[tokio::test]
+async fn proxy_protocol_timeout() {
+    use std::time::{Duration, Instant};
+    use tokio::io::AsyncReadExt;
+
+    let _ = pretty_env_logger::try_init();
+
+    let timeout = Duration::from_millis(200);
+    let addr = spawn_remote(
+        ServerConfig::new()
+            .proxy_protocol(true)
+            .proxy_protocol_timeout(timeout)
+            .max_pending_handshakes(1),
+    );
+
+    // Holds the only pending header, until it times out.
+    let start = Instant::now();
+    let mut idle = tokio::net::TcpStream::connect(addr).await.unwrap();
+
+    let mut v1 = b"PROXY TCP4 192.0.2.1 198.51.100.2 56324 443\r\n".to_vec();
+    v1.extend_from_slice(REQUEST);
+    let res = send_raw(addr, &v1).await;
+    assert!(res.ends_with("192.0.2.1:56324 198.51.100.2:443"), "{}", res);
+    assert!(start.elapsed() >= timeout);
+    assert_eq!(idle.read(&mut [0; 1]).await.unwrap(), 0, "closed");
+}